@@ -1,10 +1,28 @@
-use super::request_handler::{handler, send_request};
+use super::client_config::ClientConfig;
+use super::client_options::ClientOptions;
+use super::client_summary::ClientSummary;
+use super::request_handler::{
+    acknowledges, base_primes, compute_save, compute_verified, handler, into_sum_only,
+    resolve_server, save_accepted, send_request, split_range,
+};
+use super::round_robin::RoundRobinState;
+use super::worker::Worker;
 use crate::utils;
+use crate::utils::chunk::{recv_message, Reassembler, MAX_DATAGRAM_SIZE};
+use crate::utils::logging;
+use log::{debug, error, info, log_enabled, warn, Level};
 use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+use std::thread;
 use tokio::net::UdpSocket;
-use tokio::time::{timeout, Duration};
-use utils::json::{Request, Response};
+use tokio::runtime::Runtime;
+use tokio::sync::Mutex;
+use tokio::time::{sleep, timeout, Duration};
+use utils::json::{Request, Response, Task, PROTOCOL_VERSION};
 
 /// Starts a UDP client that sends requests to the server and handles the response.
 ///
@@ -13,149 +31,1571 @@ use utils::json::{Request, Response};
 ///
 /// # Arguments
 ///
-/// * `ip` - The IP address of the server (e.g., "127.0.0.1").
+/// * `ip` - The IP address of the server (e.g., "127.0.0.1", or "::1" or "[::1]" for IPv6).
 /// * `port` - The UDP port where the server is listening.
-/// * `verbose` - Optional verbosity level for logging output.
+/// * `verbose` - Optional verbosity level for logging output, mapped to a `log` level
+///   filter as in `start_server`.
 /// * `timeout_seconds` - Optional timeout in seconds for receiving responses.
+/// * `max_payload` - Optional largest datagram size, in bytes, the client accepts. The
+///   server splits larger responses into chunks that fit.
+/// * `worker` - Optional name of the worker computing assigned ranges: `"sieve"` (default),
+///   or `"mersenne"` when built with the `mersenne` feature.
+/// * `sieve_block_size` - Optional number of candidates the sieve worker marks at once.
+///   Smaller blocks stay in cache; defaults to `utils::sieve::DEFAULT_BLOCK_SIZE`.
+/// * `stream_block_size` - Optional number of values above which an assigned range is
+///   sieved and saved in sub-blocks of this size, keeping memory and payloads bounded.
+///   This buffer step is independent of the server's `assignment_unit`.
+/// * `self_verify` - Optional flag checking every result locally (with Miller-Rabin for the
+///   sieve worker) before submitting it. Bad results are recomputed, never submitted.
+/// * `max_retries` - Optional number of times a `"save"` is resent when no matching
+///   `"save_ack"` arrives within the timeout. Defaults to `3`.
+/// * `format` - Optional encoding of requests and responses: `"json"` (default), or
+///   `"bincode"` when built with the `binary` feature. It must match the server's.
+/// * `workers` - Optional number of ranges computed in parallel. Each worker fetches,
+///   computes and saves its own ranges; they share the client socket. Defaults to `1`.
+/// * `connect_retries` - Optional number of times the first `"start"` is resent when the
+///   server doesn't answer it within the timeout. Defaults to `0`.
+/// * `retry_backoff_ms` - Optional delay before the first of those retries, in milliseconds,
+///   doubled before each next one. Defaults to `500`.
+/// * `local_port` - Optional port to send from, for firewalls or NAT rules expecting a fixed
+///   source port. Defaults to an ephemeral port.
+/// * `checksum` - Optional flag starting every datagram with a CRC-32 of its content;
+///   received datagrams that don't match are dropped as corrupted. It must match the
+///   server's. Defaults to `False`.
+///
+/// # Returns
+///
+/// A `ClientSummary` of the work the client contributed, returned when it exits. A server
+/// that already completed answers right away, and the summary holds no ranges then.
 ///
 /// # Errors
 ///
-/// Returns a `PyValueError` if the client fails to initialize, send a request, or receive a response.
+/// Returns a `PyValueError` if `port` or `workers` is `0`, if the worker or the format is unknown,
+/// if `local_port` is already in use, if the client fails to initialize, or if the server never answered the first `"start"`.
+/// Failures to send a request or receive a response later end the run, and are only logged.
+/// An unknown keyword argument raises a `TypeError`.
 ///
 /// # Example (Python)
 ///
 /// ```python
 /// import primesocket_core
-/// primesocket_core.start_client("127.0.0.1", 8080)
+/// summary = primesocket_core.start_client("127.0.0.1", 8080)
+/// print(summary.ranges, summary.primes)
 /// ```
-#[pyfunction(signature = (ip, port, verbose=None, timeout_seconds=None))]
+#[pyfunction(signature = (ip, port, verbose=None, **options))]
 pub fn start_client(
+    py: Python<'_>,
     ip: &str,
     port: u16,
     verbose: Option<u8>,
-    timeout_seconds: Option<u64>,
-) -> PyResult<()> {
-    let verbose = verbose.unwrap_or(0);
-    let timeout_seconds = timeout_seconds.unwrap_or(120);
+    options: Option<&Bound<'_, PyDict>>,
+) -> PyResult<ClientSummary> {
+    let options = ClientOptions::from_kwargs(py, options)?;
+    start_client_with_options(py, ip, port, verbose, &options)
+}
+
+/// Runs a client until it exits, as `start_client` does, from options already collected.
+///
+/// # Errors
+///
+/// Returns a `PyValueError` for the invalid arguments listed by `start_client`, or if the
+/// client fails to initialize.
+pub(crate) fn start_client_with_options(
+    py: Python<'_>,
+    ip: &str,
+    port: u16,
+    verbose: Option<u8>,
+    options: &ClientOptions,
+) -> PyResult<ClientSummary> {
+    check_server_port(port)?;
+    let mut config = ClientConfig::new(ip, port, verbose.unwrap_or(0));
+    logging::init(config.verbose);
+    options.apply(&mut config)?;
+    let worker = options.worker()?;
 
     // Create a new Tokio runtime to execute asynchronous operations
     let rt = tokio::runtime::Runtime::new().map_err(|e| {
         PyErr::new::<PyValueError, _>(format!("Failed to create Tokio runtime: {}", e))
     })?;
 
-    // Run the client within the Tokio runtime
-    rt.block_on(async {
-        if let Err(e) = run_client(ip, port, verbose, timeout_seconds).await {
-            if verbose > 0 {
-                eprintln!("❌ Client encountered an error: {:?}", e);
+    // Run the client workers within the Tokio runtime, releasing the GIL so that a
+    // server spawned in this process can still call its progress callback.
+    let mut summary = ClientSummary::default();
+    let result = py.allow_threads(|| {
+        let opened = rt.block_on(open(&config, &mut summary))?;
+        Ok::<_, PyErr>(opened.map_or(Ok(()), |(connection, first)| {
+            run_workers(&rt, connection, first, worker.as_ref())
+        }))
+    })?;
+    if let Err(e) = result {
+        error!("❌ Client encountered an error: {:?}", e);
+    }
+
+    Ok(summary)
+}
+
+/// Downloads the primes of a completed server, page by page.
+///
+/// The server must be done computing: either a read replica, or a completed server
+/// still within its grace period. Pages are requested in order with `"download"`
+/// and concatenated; each page is a message of its own, split into chunks when it
+/// exceeds a datagram.
+///
+/// # Arguments
+///
+/// * `ip` - The IP address of the server (e.g., "127.0.0.1", or "::1" or "[::1]" for IPv6).
+/// * `port` - The UDP port where the server is listening.
+/// * `page_size` - Optional number of primes requested per page. The server may send fewer.
+///   Defaults to `10000`.
+/// * `verbose` - Optional verbosity level for logging output.
+/// * `offset` - Optional index of the first prime to download, to resume an interrupted
+///   download from the number of primes already received. Defaults to `0`.
+/// * `timeout_seconds` - Optional timeout in seconds for receiving each page.
+/// * `format` - Optional encoding of requests and responses, `"json"` (default) or
+///   `"bincode"`. It must match the server's.
+/// * `checksum` - Optional flag sealing every datagram with a CRC-32, as in `start_client`.
+///   It must match the server's.
+///
+/// # Returns
+///
+/// The sorted primes of the server, from `offset` on.
+///
+/// # Errors
+///
+/// Returns a `PyValueError` if `port` or `page_size` is `0`, if `format` is unknown, if the server
+/// refuses the download (e.g. because it is still computing, or `offset` is past its last
+/// prime), or if a page keeps timing out; the error names the offset to resume from. An unknown
+/// keyword argument raises a `TypeError`.
+///
+/// # Example (Python)
+///
+/// ```python
+/// import primesocket_core
+/// primes = primesocket_core.download_primes("127.0.0.1", 8080)
+/// # After an interruption, fetch the rest:
+/// primes += primesocket_core.download_primes("127.0.0.1", 8080, offset=len(primes))
+/// ```
+#[pyfunction(signature = (ip, port, page_size=None, verbose=None, offset=None, **options))]
+pub fn download_primes(
+    py: Python<'_>,
+    ip: &str,
+    port: u16,
+    page_size: Option<u64>,
+    verbose: Option<u8>,
+    offset: Option<u64>,
+    options: Option<&Bound<'_, PyDict>>,
+) -> PyResult<Vec<u64>> {
+    let options = ClientOptions::from_kwargs(py, options)?;
+    download_with_options(py, ip, port, page_size, verbose, offset, &options)
+}
+
+/// Downloads the primes of a completed server, as `download_primes` does, from options
+/// already collected.
+///
+/// # Errors
+///
+/// Returns a `PyValueError` for the invalid arguments listed by `download_primes`, or if
+/// the download fails.
+pub(crate) fn download_with_options(
+    py: Python<'_>,
+    ip: &str,
+    port: u16,
+    page_size: Option<u64>,
+    verbose: Option<u8>,
+    offset: Option<u64>,
+    options: &ClientOptions,
+) -> PyResult<Vec<u64>> {
+    check_server_port(port)?;
+    let page_size = page_size.unwrap_or(10_000);
+    if page_size == 0 {
+        return Err(PyErr::new::<PyValueError, _>(
+            "Parameter 'page_size' must be positive",
+        ));
+    }
+    let mut config = ClientConfig::new(ip, port, verbose.unwrap_or(0));
+    logging::init(config.verbose);
+    options.apply(&mut config)?;
+
+    let rt = tokio::runtime::Runtime::new().map_err(|e| {
+        PyErr::new::<PyValueError, _>(format!("Failed to create Tokio runtime: {}", e))
+    })?;
+    let mut summary = ClientSummary::default();
+    py.allow_threads(|| {
+        rt.block_on(run_download(
+            &config,
+            page_size,
+            offset.unwrap_or(0),
+            &mut summary,
+        ))
+    })
+}
+
+/// Starts a client contributing to several servers in turn.
+///
+/// The client serves one range per turn, going round the servers in order, until each
+/// of them is done or stops answering. With `state_path`, the list of servers and the
+/// position in it are saved after every turn, so a restarted client resumes with the
+/// server whose turn was next instead of starting over. The file is removed once every
+/// server is done.
+///
+/// # Arguments
+///
+/// * `servers` - The `(ip, port)` of every server, in turn order.
+/// * `state_path` - Optional file saving the round-robin state. A file written for another
+///   list of servers is ignored.
+/// * `verbose` - Optional verbosity level for logging output.
+/// * `options` - The options of `start_client`, such as `timeout_seconds`, `worker`, `format`
+///   or `checksum`, applied to every server. `format` and `checksum` must match the servers'.
+///
+/// # Returns
+///
+/// A `ClientSummary` of the work the client contributed to all servers during this run.
+///
+/// # Errors
+///
+/// Returns a `PyValueError` if `servers` is empty or holds the port `0`, for the invalid options
+/// listed by `start_client`, or if the state file can't be read or written. An unknown keyword
+/// argument raises a `TypeError`.
+///
+/// # Example (Python)
+///
+/// ```python
+/// import primesocket_core
+/// servers = [("127.0.0.1", 8080), ("127.0.0.1", 8081)]
+/// summary = primesocket_core.start_multi_client(servers, state_path="client.json")
+/// ```
+#[pyfunction(signature = (servers, state_path=None, verbose=None, **options))]
+pub fn start_multi_client(
+    py: Python<'_>,
+    servers: Vec<(String, u16)>,
+    state_path: Option<&str>,
+    verbose: Option<u8>,
+    options: Option<&Bound<'_, PyDict>>,
+) -> PyResult<ClientSummary> {
+    let options = ClientOptions::from_kwargs(py, options)?;
+    if servers.is_empty() {
+        return Err(PyErr::new::<PyValueError, _>(
+            "Parameter 'servers' must name at least one server",
+        ));
+    }
+    let mut configs = Vec::with_capacity(servers.len());
+    for (ip, port) in &servers {
+        check_server_port(*port)?;
+        let mut config = ClientConfig::new(ip, *port, verbose.unwrap_or(0));
+        options.apply(&mut config)?;
+        configs.push(config);
+    }
+    logging::init(verbose.unwrap_or(0));
+    let worker = options.worker()?;
+
+    let state_path = state_path.map(Path::new);
+    let mut state = match state_path {
+        Some(path) => RoundRobinState::load(path, &servers).map_err(|e| {
+            PyErr::new::<PyValueError, _>(format!("Failed to load client state: {}", e))
+        })?,
+        None => None,
+    }
+    .unwrap_or_else(|| RoundRobinState::new(servers));
+
+    let rt = tokio::runtime::Runtime::new().map_err(|e| {
+        PyErr::new::<PyValueError, _>(format!("Failed to create Tokio runtime: {}", e))
+    })?;
+    let mut summary = ClientSummary::default();
+    py.allow_threads(|| {
+        rt.block_on(run_round_robin(
+            &configs,
+            &mut state,
+            state_path,
+            worker.as_ref(),
+            &mut summary,
+        ))
+    })?;
+    Ok(summary)
+}
+
+/// Rejects the server port `0`, which no server can listen on.
+///
+/// # Errors
+///
+/// Returns a `PyValueError` if `port` is `0`.
+fn check_server_port(port: u16) -> PyResult<()> {
+    if port == 0 {
+        return Err(PyErr::new::<PyValueError, _>(
+            "port must be between 1 and 65535",
+        ));
+    }
+    Ok(())
+}
+
+/// How many times a `"download"` page is requested before the download fails.
+const MAX_DOWNLOAD_ATTEMPTS: u32 = 3;
+
+/// How many times a rejected `"save"` is resubmitted before the range is abandoned.
+const MAX_SAVE_ATTEMPTS: u32 = 3;
+
+/// The outcome of submitting a `"save"`.
+enum SaveOutcome {
+    /// The server accepted the save and has more work.
+    Accepted,
+    /// The server accepted the save and the computation is over.
+    Done,
+    /// The server kept rejecting the save.
+    Rejected,
+    /// No acknowledgment arrived within the timeout, however often the save was resent.
+    Lost,
+}
+
+/// Tracks the connection to the server during a client run.
+///
+/// # Fields
+///
+/// * `config` - The settings of the run.
+/// * `server` - The resolved address of the server.
+/// * `socket` - The client socket.
+/// * `reassembler` - Holds the chunks of incomplete responses.
+/// * `max_payload` - The datagram size accepted by the server, updated once negotiated.
+/// * `summary` - The contribution of the run so far.
+struct Connection<'a> {
+    config: &'a ClientConfig,
+    server: SocketAddr,
+    socket: UdpSocket,
+    reassembler: Reassembler,
+    max_payload: usize,
+    summary: &'a mut ClientSummary,
+}
+
+impl Connection<'_> {
+    /// Sends a request to the server.
+    async fn send(&mut self, request: &Request) -> PyResult<()> {
+        let sent = send_request(
+            &self.socket,
+            self.server,
+            request,
+            self.max_payload,
+            self.config.format,
+            self.config.checksum,
+        )
+        .await?;
+        self.summary.bytes_sent += sent as u64;
+        Ok(())
+    }
+
+    /// Waits for the next valid response from the server.
+    ///
+    /// Datagrams that are not a valid `Response`, keepalives and messages exceeding
+    /// the reassembly limit are skipped.
+    ///
+    /// # Returns
+    ///
+    /// `Some(Response)`, or `None` if no response arrived within the timeout.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PyValueError` if receiving from the socket fails, or if the server
+    /// speaks another protocol version.
+    async fn receive(&mut self) -> PyResult<Option<Response>> {
+        loop {
+            match timeout(
+                Duration::from_secs(self.config.timeout_seconds),
+                recv_message(&self.socket, &mut self.reassembler, self.config.checksum),
+            )
+            .await
+            {
+                Ok(Ok((message, src))) => {
+                    self.summary.bytes_received += message.len() as u64;
+
+                    debug!(
+                        "📩 Received response from {}: {}",
+                        src,
+                        String::from_utf8_lossy(&message)
+                    );
+
+                    match self.config.format.decode::<Response>(&message) {
+                        Ok(response_data) => {
+                            if response_data.version != PROTOCOL_VERSION {
+                                error!(
+                                    "❌ Server speaks protocol version {} but this client speaks {}",
+                                    response_data.version, PROTOCOL_VERSION
+                                );
+                                return Err(PyErr::new::<PyValueError, _>(format!(
+                                    "Incompatible server protocol version {} (expected {})",
+                                    response_data.version, PROTOCOL_VERSION
+                                )));
+                            }
+                            if response_data.task == Task::Keepalive {
+                                continue;
+                            }
+                            debug!("✅ Server Response: {:?}", response_data);
+
+                            if let Some(negotiated) = response_data.max_payload {
+                                self.max_payload = negotiated as usize;
+                            }
+                            return Ok(Some(response_data));
+                        }
+                        Err(e) => {
+                            debug!("⚠️ Invalid response format: {}", e);
+                        }
+                    }
+                }
+                Ok(Err(e)) if e.kind() == ErrorKind::InvalidData => {
+                    warn!("⚠️ Discarding oversized response: {}", e);
+                }
+                Ok(Err(e)) => {
+                    debug!("❌ Failed to receive data: {:?}", e);
+                    return Err(PyErr::new::<PyValueError, _>(format!(
+                        "Failed to receive response: {}",
+                        e
+                    )));
+                }
+                Err(_) => {
+                    warn!(
+                        "⚠️ Connection lost: no response received within timeout. Disconnecting."
+                    );
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    /// Sends the first `"start"` and waits for the reply.
+    ///
+    /// While the server is silent, the request is resent up to `connect_retries` times,
+    /// waiting `retry_backoff` before the first retry and twice as long before each next.
+    ///
+    /// # Returns
+    ///
+    /// The first `Response` of the server: a range, or `"done"` if it already completed.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PyValueError` if the server never answered, or if sending or receiving fails.
+    async fn handshake(&mut self) -> PyResult<Response> {
+        let request = start_request(self.config);
+        let mut backoff = self.config.retry_backoff;
+        for attempt in 0..=self.config.connect_retries {
+            if attempt > 0 {
+                info!(
+                    "🔁 No answer from the server, retry {}/{} in {:?}",
+                    attempt, self.config.connect_retries, backoff
+                );
+                sleep(backoff).await;
+                backoff = backoff.saturating_mul(2);
+            }
+            self.send(&request).await?;
+            if let Some(response) = self.receive().await? {
+                return Ok(response);
+            }
+        }
+        Err(PyErr::new::<PyValueError, _>(format!(
+            "Server at {} never responded ({} attempts)",
+            self.server,
+            self.config.connect_retries + 1
+        )))
+    }
+
+    /// Sends a `"save"` and waits for a reply to it.
+    ///
+    /// The save is resent up to `max_retries` times when nothing arrives within the
+    /// timeout. Acknowledgments of other saves, arriving late, are skipped.
+    ///
+    /// # Returns
+    ///
+    /// `Some(Response)`, or `None` if every retry timed out.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PyValueError` if sending or receiving fails.
+    async fn send_save(&mut self, save: &Request) -> PyResult<Option<Response>> {
+        self.send(save).await?;
+        let mut retries = 0;
+        loop {
+            match self.receive().await? {
+                Some(ack) if acknowledges(&ack, save) => return Ok(Some(ack)),
+                Some(ack) => {
+                    debug!("⚠️ Skipping acknowledgment of another save: {:?}", ack.end);
+                }
+                None if retries < self.config.max_retries => {
+                    retries += 1;
+                    info!(
+                        "🔁 Resending save of [{:?}, {:?}], retry {}/{}",
+                        save.start, save.end, retries, self.config.max_retries
+                    );
+                    self.send(save).await?;
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// Submits a `"save"` and waits for its acknowledgment.
+    ///
+    /// A rejected save is resubmitted up to `MAX_SAVE_ATTEMPTS` times; a lost one is
+    /// resent up to `max_retries` times.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PyValueError` if sending or receiving fails.
+    async fn submit(&mut self, save: &Request) -> PyResult<SaveOutcome> {
+        for attempt in 1..=MAX_SAVE_ATTEMPTS {
+            let Some(ack) = self.send_save(save).await? else {
+                return Ok(SaveOutcome::Lost);
+            };
+            if save_accepted(&ack) {
+                self.summary.ranges += 1;
+                self.summary.primes += save
+                    .count
+                    .unwrap_or_else(|| save.primes.as_ref().map_or(0, Vec::len) as u64);
+                return Ok(match ack.task {
+                    Task::Done => SaveOutcome::Done,
+                    _ => SaveOutcome::Accepted,
+                });
+            }
+
+            warn!(
+                "⚠️ Save of [{:?}, {:?}] rejected ({}), attempt {}/{}",
+                save.start, save.end, ack.status, attempt, MAX_SAVE_ATTEMPTS
+            );
+        }
+        Ok(SaveOutcome::Rejected)
+    }
+}
+
+/// Binds the client socket and, when info records are logged, logs the address the
+/// server observes (`"whoami"`).
+///
+/// # Arguments
+///
+/// * `config` - The settings of the run.
+/// * `summary` - Accumulates the contribution of the run, including before a failure.
+///
+/// # Returns
+///
+/// The `Connection` to the server, or `None` if the server didn't answer `"whoami"`.
+///
+/// # Errors
+///
+/// Returns a `PyValueError` if the client fails to bind the socket or send a request.
+async fn connect<'a>(
+    config: &'a ClientConfig,
+    summary: &'a mut ClientSummary,
+) -> PyResult<Option<Connection<'a>>> {
+    let (socket, server) = bind_client_socket(config).await?;
+
+    let mut connection = Connection {
+        config,
+        server,
+        socket,
+        reassembler: Reassembler::new(),
+        max_payload: MAX_DATAGRAM_SIZE,
+        summary,
+    };
+
+    if log_enabled!(Level::Info) {
+        let whoami = Request {
+            task: Task::Whoami,
+            ..Default::default()
+        };
+        connection.send(&whoami).await?;
+        match connection.receive().await? {
+            Some(Response {
+                address: Some(address),
+                ..
+            }) => info!("🪪 Server sees this client as {}", address),
+            Some(_) => warn!("⚠️ Server did not report this client's address"),
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(connection))
+}
+
+/// Connects to the server and performs the handshake.
+///
+/// # Arguments
+///
+/// * `config` - The settings of the run.
+/// * `summary` - Accumulates the contribution of the run, including before a failure.
+///
+/// # Returns
+///
+/// The `Connection` with the first response of the server, or `None` if the server
+/// didn't answer `"whoami"`.
+///
+/// # Errors
+///
+/// Returns a `PyValueError` if the client fails to bind the socket, or if the server
+/// never answered the first `"start"`.
+async fn open<'a>(
+    config: &'a ClientConfig,
+    summary: &'a mut ClientSummary,
+) -> PyResult<Option<(Connection<'a>, Response)>> {
+    let Some(mut connection) = connect(config, summary).await? else {
+        return Ok(None);
+    };
+    let first = connection.handshake().await?;
+    Ok(Some((connection, first)))
+}
+
+/// Runs `config.workers` client loops in parallel over an opened connection.
+///
+/// Each loop runs on a thread of its own, so the ranges are computed in parallel,
+/// while the requests and their replies take turns on the shared socket. A single
+/// loop runs on the calling thread.
+///
+/// # Arguments
+///
+/// * `runtime` - The runtime driving the socket.
+/// * `connection` - The connection returned by `open`.
+/// * `first` - The first response of the server, handled by the first loop.
+/// * `worker` - The `Worker` computing the results of assigned ranges.
+///
+/// # Errors
+///
+/// Returns the first error a loop ended with, once every loop has ended.
+fn run_workers(
+    runtime: &Runtime,
+    connection: Connection<'_>,
+    first: Response,
+    worker: &dyn Worker,
+) -> PyResult<()> {
+    let workers = connection.config.workers;
+    let connection = Mutex::new(connection);
+    if workers == 1 {
+        return runtime.block_on(work(&connection, worker, Some(first)));
+    }
+    let mut first = Some(first);
+    thread::scope(|scope| {
+        let loops: Vec<_> = (0..workers)
+            .map(|_| {
+                let (connection, first) = (&connection, first.take());
+                scope.spawn(move || runtime.block_on(work(connection, worker, first)))
+            })
+            .collect();
+        loops
+            .into_iter()
+            .map(|handle| {
+                handle.join().unwrap_or_else(|_| {
+                    Err(PyErr::new::<PyValueError, _>("A client worker panicked"))
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .collect()
+    })
+}
+
+/// Requests, computes and saves ranges until the server is done.
+///
+/// Every `"save"` waits for its acknowledgment: a save whose acknowledgment is lost
+/// is resent up to `max_retries` times, and a rejected save is resubmitted up to
+/// `MAX_SAVE_ATTEMPTS` times, after which the range is abandoned and a new one is
+/// requested. Ranges larger than `stream_block_size` are sieved and saved one
+/// sub-block at a time. With `self_verify`, results failing local verification are
+/// never submitted.
+///
+/// The connection is only locked while a request waits for its reply, so several
+/// loops can share it and compute their ranges at the same time.
+///
+/// # Arguments
+///
+/// * `connection` - The connection to the server.
+/// * `worker` - The `Worker` computing the results of assigned ranges.
+/// * `first` - A response already received, handled before requesting more work.
+///
+/// # Errors
+///
+/// Returns a `PyValueError` if sending a request or receiving a response fails.
+async fn work(
+    connection: &Mutex<Connection<'_>>,
+    worker: &dyn Worker,
+    mut first: Option<Response>,
+) -> PyResult<()> {
+    let config = connection.lock().await.config;
+    'requests: loop {
+        let response = match first.take() {
+            Some(response) => Some(response),
+            None => {
+                let mut connection = connection.lock().await;
+                connection.send(&start_request(config)).await?;
+                connection.receive().await?
+            }
+        };
+        let Some(response) = response else {
+            break;
+        };
+
+        if response.task == Task::Range {
+            let (start, end) = (response.start.unwrap(), response.end.unwrap());
+            let primes = base_primes(&response);
+            let block = config.stream_block_size.map_or(u64::MAX, u64::from);
+
+            for (block_start, block_end) in split_range(start, end, block) {
+                let save = if config.self_verify {
+                    let verified = compute_verified(
+                        worker,
+                        block_start,
+                        block_end,
+                        primes.clone(),
+                        response.residue,
+                    );
+                    let Some(save) = verified else {
+                        warn!("⚠️ Results keep failing self-verification, requesting a new range");
+                        continue 'requests;
+                    };
+                    save
+                } else {
+                    compute_save(
+                        worker,
+                        block_start,
+                        block_end,
+                        primes.clone(),
+                        response.residue,
+                    )
+                };
+                let save = match response.sum_only {
+                    Some(true) => into_sum_only(save),
+                    _ => save,
+                };
+                // Echo the run the range came from, so a new run won't take it.
+                let save = Request {
+                    epoch: response.epoch,
+                    ..save
+                };
+                match connection.lock().await.submit(&save).await? {
+                    SaveOutcome::Accepted => {}
+                    SaveOutcome::Done => {
+                        debug!("✅ Client finished");
+                        break 'requests;
+                    }
+                    SaveOutcome::Rejected => {
+                        warn!("⚠️ Abandoning range, requesting a new one");
+                        continue 'requests;
+                    }
+                    SaveOutcome::Lost => break 'requests,
+                }
             }
+            continue;
         }
-    });
 
+        let request = handler(response, worker).await;
+        match request.task {
+            Task::Continue => {
+                continue;
+            }
+            _ => {
+                debug!("✅ Client finished");
+                break;
+            }
+        }
+    }
     Ok(())
 }
 
-/// Runs the UDP client that sends requests and handles server responses.
+/// Builds the `"start"` request asking the server for a range.
+fn start_request(config: &ClientConfig) -> Request {
+    Request {
+        task: Task::Start,
+        max_payload: config.max_payload,
+        ..Default::default()
+    }
+}
+
+/// Serves the servers of `state` in turn until every one of them is done.
 ///
-/// This function binds a UDP socket and repeatedly sends requests to the server.
-/// It waits for responses and processes them accordingly.
+/// The state is saved to `state_path` after every turn, and the file is removed once
+/// every server is done.
 ///
 /// # Arguments
 ///
-/// * `ip` - The IP address of the server.
-/// * `port` - The UDP port where the server is listening.
-/// * `verbose` - Verbosity level for logging output.
-/// * `timeout_seconds` - Timeout duration in seconds for receiving responses.
+/// * `configs` - The settings of each server, in the order of `state.servers`.
+/// * `state` - The round-robin state, possibly loaded from a previous run.
+/// * `state_path` - The file saving the state, if any.
+/// * `worker` - The `Worker` computing the results of assigned ranges.
+/// * `summary` - Accumulates the contribution of the run.
 ///
 /// # Errors
 ///
-/// Returns a `PyValueError` if the client fails to bind the socket, send a request, or process a response.
-async fn run_client(ip: &str, port: u16, verbose: u8, timeout_seconds: u64) -> PyResult<()> {
-    // Bind a UDP socket to any available port
-    let socket = match UdpSocket::bind("0.0.0.0:0").await {
-        Ok(sock) => sock,
-        Err(e) => {
-            if verbose > 0 {
-                eprintln!("❌ Failed to bind UDP socket: {:?}", e);
-            }
-            return Err(PyErr::new::<PyValueError, _>(format!(
-                "Failed to bind UDP socket: {}",
-                e
-            )));
+/// Returns a `PyValueError` if the state can't be saved, or if a socket fails.
+async fn run_round_robin(
+    configs: &[ClientConfig],
+    state: &mut RoundRobinState,
+    state_path: Option<&Path>,
+    worker: &dyn Worker,
+    summary: &mut ClientSummary,
+) -> PyResult<()> {
+    let save_state = |state: &RoundRobinState| {
+        state_path.map_or(Ok(()), |path| {
+            state.save(path).map_err(|e| {
+                PyErr::new::<PyValueError, _>(format!("Failed to save client state: {}", e))
+            })
+        })
+    };
+
+    while let Some(index) = state.next_server() {
+        let config = &configs[index];
+        let (socket, server) = bind_client_socket(config).await?;
+        let mut connection = Connection {
+            config,
+            server,
+            socket,
+            reassembler: Reassembler::new(),
+            max_payload: MAX_DATAGRAM_SIZE,
+            summary,
+        };
+        let has_more = serve_turn(&mut connection, worker).await?;
+        if !has_more {
+            info!("🏁 Server {}:{} has no more work", config.ip, config.port);
         }
+        state.advance(index, has_more);
+        save_state(state)?;
+    }
+
+    if let Some(path) = state_path {
+        if let Err(e) = std::fs::remove_file(path) {
+            warn!("⚠️ Failed to remove client state {}: {}", path.display(), e);
+        }
+    }
+    Ok(())
+}
+
+/// Requests, computes and saves one range of a server.
+///
+/// # Arguments
+///
+/// * `connection` - The connection to the server whose turn it is.
+/// * `worker` - The `Worker` computing the result of the range.
+///
+/// # Returns
+///
+/// `true` if the server may have more work, `false` if it is done or stopped answering.
+///
+/// # Errors
+///
+/// Returns a `PyValueError` if sending a request or receiving a response fails.
+async fn serve_turn(connection: &mut Connection<'_>, worker: &dyn Worker) -> PyResult<bool> {
+    connection.send(&start_request(connection.config)).await?;
+    let Some(response) = connection.receive().await? else {
+        return Ok(false);
     };
+    match response.task {
+        Task::Range => {
+            let save = compute_save(
+                worker,
+                response.start.unwrap(),
+                response.end.unwrap(),
+                base_primes(&response),
+                response.residue,
+            );
+            let save = match response.sum_only {
+                Some(true) => into_sum_only(save),
+                _ => save,
+            };
+            let save = Request {
+                epoch: response.epoch,
+                ..save
+            };
+            Ok(match connection.submit(&save).await? {
+                SaveOutcome::Accepted | SaveOutcome::Rejected => true,
+                SaveOutcome::Done | SaveOutcome::Lost => false,
+            })
+        }
+        Task::Continue => Ok(true),
+        _ => Ok(false),
+    }
+}
 
-    loop {
+/// Pages through the primes of a completed server until the last page.
+///
+/// A page that doesn't arrive within the timeout, or that answers another offset,
+/// is requested again, up to `MAX_DOWNLOAD_ATTEMPTS` times.
+///
+/// # Arguments
+///
+/// * `config` - The settings of the download.
+/// * `page_size` - The number of primes requested per page.
+/// * `offset` - The index of the first prime to download.
+/// * `summary` - Accumulates the traffic of the download.
+///
+/// # Returns
+///
+/// The primes of every page from `offset` on, in order.
+///
+/// # Errors
+///
+/// Returns a `PyValueError` if the server answers with an error, or if a page keeps
+/// getting lost.
+async fn run_download(
+    config: &ClientConfig,
+    page_size: u64,
+    offset: u64,
+    summary: &mut ClientSummary,
+) -> PyResult<Vec<u64>> {
+    let (socket, server) = bind_client_socket(config).await?;
+    let mut connection = Connection {
+        config,
+        server,
+        socket,
+        reassembler: Reassembler::new(),
+        max_payload: MAX_DATAGRAM_SIZE,
+        summary,
+    };
+
+    let mut primes = Vec::new();
+    'pages: loop {
         let request = Request {
-            task: "start".to_string(),
-            end: None,
-            primes: None,
+            task: Task::Download,
+            offset: Some(offset + primes.len() as u64),
+            limit: Some(page_size),
+            ..Default::default()
+        };
+        for _ in 0..MAX_DOWNLOAD_ATTEMPTS {
+            connection.send(&request).await?;
+            let Some(response) = connection.receive().await? else {
+                continue;
+            };
+            match response.task {
+                Task::Download if response.offset == request.offset => {
+                    let page = response.primes.unwrap_or_default();
+                    let last = page.is_empty();
+                    primes.extend(page);
+                    if last
+                        || response
+                            .total
+                            .is_none_or(|total| offset + primes.len() as u64 >= total)
+                    {
+                        return Ok(primes);
+                    }
+                    continue 'pages;
+                }
+                Task::Error => {
+                    return Err(PyErr::new::<PyValueError, _>(format!(
+                        "Download refused: {}",
+                        response.status
+                    )))
+                }
+                _ => {}
+            }
+        }
+        return Err(PyErr::new::<PyValueError, _>(format!(
+            "No page at offset {} after {} attempts",
+            offset + primes.len() as u64,
+            MAX_DOWNLOAD_ATTEMPTS
+        )));
+    }
+}
+
+/// Resolves the server and binds the client socket to `config.local_port`, or any
+/// available port, in the address family of the server.
+///
+/// # Arguments
+///
+/// * `config` - The settings of the run, naming the server.
+///
+/// # Returns
+///
+/// The bound socket along with the address of the server.
+///
+/// # Errors
+///
+/// Returns a `PyValueError` if the server can't be resolved or the socket can't be bound,
+/// naming the port when it is already in use.
+async fn bind_client_socket(config: &ClientConfig) -> PyResult<(UdpSocket, SocketAddr)> {
+    let server = resolve_server(&config.ip, config.port).await?;
+    let unspecified: IpAddr = match server {
+        SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+        SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+    };
+    let local = SocketAddr::new(unspecified, config.local_port.unwrap_or(0));
+    let socket = UdpSocket::bind(local).await.map_err(|e| {
+        error!("❌ Failed to bind UDP socket to {}: {:?}", local, e);
+        if e.kind() == ErrorKind::AddrInUse {
+            PyErr::new::<PyValueError, _>(format!("Local port {} is already in use", local.port()))
+        } else {
+            PyErr::new::<PyValueError, _>(format!("Failed to bind UDP socket: {}", e))
+        }
+    })?;
+    Ok((socket, server))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::worker::SieveWorker;
+    use crate::utils::sieve::primes_up_to;
+    use std::sync::Arc;
+    use tokio::sync::Notify;
+
+    /// Runs a single-worker client, as `start_client` does.
+    async fn run_client(
+        config: &ClientConfig,
+        worker: &dyn Worker,
+        summary: &mut ClientSummary,
+    ) -> PyResult<()> {
+        let Some((connection, first)) = open(config, summary).await? else {
+            return Ok(());
         };
+        work(&Mutex::new(connection), worker, Some(first)).await
+    }
 
-        send_request(&socket, ip, port, &request, verbose).await?;
+    /// Tests that the client resubmits a range whose save was rejected.
+    ///
+    /// A fake server hands out one range, rejects the first save and accepts
+    /// the second one with `"done"`. Both saves must carry the same range.
+    #[tokio::test]
+    async fn test_client_retries_rejected_save() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = server.local_addr().unwrap().port();
 
-        let mut buffer = vec![0; 65535];
+        let fake_server = tokio::spawn(async move {
+            let mut buffer = vec![0; 65535];
+            let mut saves = Vec::new();
+            while saves.len() < 2 {
+                let (size, src) = server.recv_from(&mut buffer).await.unwrap();
+                let request =
+                    Request::from_json(&String::from_utf8_lossy(&buffer[..size])).unwrap();
+                let response = match request.task {
+                    Task::Start => Response {
+                        task: Task::Range,
+                        start: Some(2),
+                        end: Some(100),
+                        primes: Some(vec![2, 3, 5, 7]),
+                        ..Default::default()
+                    },
+                    Task::Save => {
+                        saves.push((request.start, request.end));
+                        Response {
+                            task: if saves.len() == 1 {
+                                Task::Error
+                            } else {
+                                Task::Done
+                            },
+                            status: "rejected".to_string(),
+                            ..Default::default()
+                        }
+                    }
+                    _ => unreachable!(),
+                };
+                server
+                    .send_to(response.to_json().unwrap().as_bytes(), src)
+                    .await
+                    .unwrap();
+            }
+            saves
+        });
 
-        match timeout(
-            Duration::from_secs(timeout_seconds),
-            socket.recv_from(&mut buffer),
+        let mut config = ClientConfig::new("127.0.0.1", port, 0);
+        config.timeout_seconds = 2;
+        run_client(
+            &config,
+            &SieveWorker::default(),
+            &mut ClientSummary::default(),
         )
         .await
-        {
-            Ok(Ok((size, src))) => {
-                buffer.truncate(size);
-                let response = String::from_utf8_lossy(&buffer);
+        .unwrap();
 
-                if verbose > 1 {
-                    println!("📩 Received response from {}: {}", src, response);
-                }
+        let saves = fake_server.await.unwrap();
+        assert_eq!(saves, vec![(Some(2), Some(100)); 2]);
+    }
 
-                if let Some(response_data) = Response::from_json(&response) {
-                    if verbose > 1 {
-                        println!("✅ Server Response: {:?}", response_data);
-                    }
+    /// Tests that the client aborts when the server answers with another protocol version.
+    #[tokio::test]
+    async fn test_client_aborts_on_version_mismatch() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = server.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            let mut buffer = vec![0; 65535];
+            loop {
+                let (_, src) = server.recv_from(&mut buffer).await.unwrap();
+                let response = Response {
+                    version: PROTOCOL_VERSION + 1,
+                    task: Task::Range,
+                    start: Some(2),
+                    end: Some(100),
+                    primes: Some(vec![2, 3, 5, 7]),
+                    ..Default::default()
+                };
+                server
+                    .send_to(response.to_json().unwrap().as_bytes(), src)
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let mut config = ClientConfig::new("127.0.0.1", port, 0);
+        config.timeout_seconds = 2;
+        let mut summary = ClientSummary::default();
+        let error = run_client(&config, &SieveWorker::default(), &mut summary)
+            .await
+            .unwrap_err();
+        pyo3::prepare_freethreaded_python();
+        assert!(error.to_string().contains("protocol version"));
+        assert_eq!(summary.ranges, 0);
+    }
 
-                    let request = handler(response_data).await;
-                    match request.task.as_str() {
-                        "save" => {
-                            send_request(&socket, ip, port, &request, verbose).await?;
+    /// Tests that the client resends a save whose acknowledgment was lost.
+    ///
+    /// A fake server hands out one range, drops the reply to the first save and
+    /// acknowledges the second one with `"done"`. Both saves must carry the same range.
+    #[tokio::test]
+    async fn test_client_resends_save_after_lost_ack() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = server.local_addr().unwrap().port();
+
+        let fake_server = tokio::spawn(async move {
+            let mut buffer = vec![0; 65535];
+            let mut saves = Vec::new();
+            while saves.len() < 2 {
+                let (size, src) = server.recv_from(&mut buffer).await.unwrap();
+                let request =
+                    Request::from_json(&String::from_utf8_lossy(&buffer[..size])).unwrap();
+                let response = match request.task {
+                    Task::Start => Response {
+                        task: Task::Range,
+                        start: Some(2),
+                        end: Some(100),
+                        primes: Some(vec![2, 3, 5, 7]),
+                        ..Default::default()
+                    },
+                    Task::Save => {
+                        saves.push((request.start, request.end));
+                        if saves.len() == 1 {
                             continue;
                         }
-                        "continue" => {
-                            continue;
+                        Response {
+                            task: Task::Done,
+                            end: request.end,
+                            ..Default::default()
+                        }
+                    }
+                    _ => unreachable!(),
+                };
+                server
+                    .send_to(response.to_json().unwrap().as_bytes(), src)
+                    .await
+                    .unwrap();
+            }
+            saves
+        });
+
+        let mut config = ClientConfig::new("127.0.0.1", port, 0);
+        config.timeout_seconds = 1;
+        config.max_retries = 1;
+        let mut summary = ClientSummary::default();
+        run_client(&config, &SieveWorker::default(), &mut summary)
+            .await
+            .unwrap();
+
+        let saves = fake_server.await.unwrap();
+        assert_eq!(saves, vec![(Some(2), Some(100)); 2]);
+        assert_eq!(summary.ranges, 1);
+    }
+
+    /// Tests that a large range is saved in sub-blocks covering it exactly once.
+    #[tokio::test]
+    async fn test_client_streams_large_range() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = server.local_addr().unwrap().port();
+
+        let fake_server = tokio::spawn(async move {
+            let mut buffer = vec![0; 65535];
+            let mut saves = Vec::new();
+            loop {
+                let (size, src) = server.recv_from(&mut buffer).await.unwrap();
+                let request =
+                    Request::from_json(&String::from_utf8_lossy(&buffer[..size])).unwrap();
+                let response = match request.task {
+                    Task::Start => Response {
+                        task: Task::Range,
+                        start: Some(2),
+                        end: Some(100_000),
+                        primes: Some(primes_up_to(317)),
+                        ..Default::default()
+                    },
+                    Task::Save => {
+                        let end = request.end.unwrap();
+                        saves.push((request.start.unwrap(), end, request.primes.unwrap()));
+                        Response {
+                            task: if end == 100_000 {
+                                Task::Done
+                            } else {
+                                Task::SaveAck
+                            },
+                            end: Some(end),
+                            ..Default::default()
+                        }
+                    }
+                    _ => unreachable!(),
+                };
+                server
+                    .send_to(response.to_json().unwrap().as_bytes(), src)
+                    .await
+                    .unwrap();
+                if response.task == Task::Done {
+                    return saves;
+                }
+            }
+        });
+
+        let mut config = ClientConfig::new("127.0.0.1", port, 0);
+        config.timeout_seconds = 2;
+        config.stream_block_size = Some(30_000);
+        run_client(
+            &config,
+            &SieveWorker::default(),
+            &mut ClientSummary::default(),
+        )
+        .await
+        .unwrap();
+
+        let saves = fake_server.await.unwrap();
+        let bounds: Vec<(u64, u64)> = saves.iter().map(|(start, end, _)| (*start, *end)).collect();
+        assert_eq!(
+            bounds,
+            vec![
+                (2, 30_001),
+                (30_002, 60_001),
+                (60_002, 90_001),
+                (90_002, 100_000)
+            ]
+        );
+        let primes: Vec<u64> = saves
+            .into_iter()
+            .flat_map(|(_, _, primes)| primes)
+            .collect();
+        assert_eq!(primes, primes_up_to(100_000));
+    }
+
+    /// Tests that several workers hold ranges at the same time over one socket, and
+    /// that each save reports the range it was computed for.
+    ///
+    /// A fake server hands out 8 ranges of 1_000_000 numbers in sum-only mode,
+    /// acknowledges every save, then answers `"done"` to each worker.
+    #[test]
+    fn test_client_workers_run_in_parallel() {
+        const RANGES: u64 = 8;
+        const SIZE: u64 = 1_000_000;
+        let runtime = Runtime::new().unwrap();
+        let server = runtime.block_on(UdpSocket::bind("127.0.0.1:0")).unwrap();
+        let port = server.local_addr().unwrap().port();
+
+        let fake_server = runtime.spawn(async move {
+            let mut buffer = vec![0; 65535];
+            let (mut handed, mut dones, mut most_held) = (0, 0, 0);
+            let mut saves = Vec::new();
+            while saves.len() < RANGES as usize || dones < 4 {
+                let (size, src) = server.recv_from(&mut buffer).await.unwrap();
+                let request =
+                    Request::from_json(&String::from_utf8_lossy(&buffer[..size])).unwrap();
+                let response = match request.task {
+                    Task::Start if handed < RANGES => {
+                        let start = 2 + handed * SIZE;
+                        handed += 1;
+                        most_held = most_held.max(handed - saves.len() as u64);
+                        Response {
+                            task: Task::Range,
+                            start: Some(start),
+                            end: Some(start + SIZE - 1),
+                            primes: Some(primes_up_to(3_000)),
+                            sum_only: Some(true),
+                            ..Default::default()
                         }
-                        _ => {
-                            if verbose > 1 {
-                                eprintln!("✅ Client finished");
+                    }
+                    Task::Start => {
+                        dones += 1;
+                        Response {
+                            task: Task::Done,
+                            ..Default::default()
+                        }
+                    }
+                    Task::Save => {
+                        saves.push((request.start.unwrap(), request.end.unwrap(), request.count));
+                        Response {
+                            task: Task::SaveAck,
+                            end: request.end,
+                            ..Default::default()
+                        }
+                    }
+                    _ => unreachable!(),
+                };
+                server
+                    .send_to(response.to_json().unwrap().as_bytes(), src)
+                    .await
+                    .unwrap();
+            }
+            (saves, most_held)
+        });
+
+        let mut config = ClientConfig::new("127.0.0.1", port, 0);
+        config.timeout_seconds = 5;
+        config.workers = 4;
+        let mut summary = ClientSummary::default();
+        let (connection, first) = runtime
+            .block_on(open(&config, &mut summary))
+            .unwrap()
+            .unwrap();
+        run_workers(&runtime, connection, first, &SieveWorker::default()).unwrap();
+
+        let (mut saves, most_held) = runtime.block_on(fake_server).unwrap();
+        assert!(most_held > 1);
+        assert_eq!(summary.ranges as u64, RANGES);
+        saves.sort();
+        let primes = primes_up_to(1 + RANGES * SIZE);
+        for (i, (start, end, count)) in saves.into_iter().enumerate() {
+            assert_eq!(
+                (start, end),
+                (2 + i as u64 * SIZE, 1 + (i as u64 + 1) * SIZE)
+            );
+            let expected = primes
+                .iter()
+                .filter(|&&p| (start..=end).contains(&p))
+                .count();
+            assert_eq!(count, Some(expected as u64));
+        }
+    }
+
+    /// Tests that the first `"start"` is retried while the server is silent, then
+    /// reported as an error, while a server that already completed is not an error.
+    #[test]
+    fn test_start_client_retries_handshake() {
+        pyo3::prepare_freethreaded_python();
+        let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = silent.local_addr().unwrap().port();
+
+        let result = Python::with_gil(|py| {
+            start_client_with_options(
+                py,
+                "127.0.0.1",
+                port,
+                None,
+                &ClientOptions {
+                    timeout_seconds: Some(1),
+                    connect_retries: Some(2),
+                    retry_backoff_ms: Some(10),
+                    ..Default::default()
+                },
+            )
+        });
+
+        assert!(result.is_err());
+        silent.set_nonblocking(true).unwrap();
+        let mut buffer = vec![0; 65535];
+        let mut starts = 0;
+        while let Ok(size) = silent.recv(&mut buffer) {
+            let request = Request::from_json(&String::from_utf8_lossy(&buffer[..size])).unwrap();
+            assert_eq!(request.task, Task::Start);
+            starts += 1;
+        }
+        assert_eq!(starts, 3);
+
+        let completed = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = completed.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let mut buffer = vec![0; 65535];
+            let (_, src) = completed.recv_from(&mut buffer).unwrap();
+            let done = Response {
+                task: Task::Done,
+                status: "completed".to_string(),
+                ..Default::default()
+            };
+            completed
+                .send_to(done.to_json().unwrap().as_bytes(), src)
+                .unwrap();
+        });
+
+        let summary = Python::with_gil(|py| {
+            start_client_with_options(
+                py,
+                "127.0.0.1",
+                port,
+                None,
+                &ClientOptions {
+                    timeout_seconds: Some(1),
+                    connect_retries: Some(2),
+                    retry_backoff_ms: Some(10),
+                    ..Default::default()
+                },
+            )
+        })
+        .unwrap();
+
+        server.join().unwrap();
+        assert_eq!(summary.ranges, 0);
+    }
+
+    /// Tests that the client sends from `local_port`, and fails clearly when another
+    /// socket already holds it.
+    #[test]
+    fn test_start_client_local_port() {
+        pyo3::prepare_freethreaded_python();
+        let local_port = std::net::UdpSocket::bind("0.0.0.0:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = server.local_addr().unwrap().port();
+        let responder = std::thread::spawn(move || {
+            let mut buffer = vec![0; 65535];
+            let (_, src) = server.recv_from(&mut buffer).unwrap();
+            let done = Response {
+                task: Task::Done,
+                status: "completed".to_string(),
+                ..Default::default()
+            };
+            server
+                .send_to(done.to_json().unwrap().as_bytes(), src)
+                .unwrap();
+            src
+        });
+
+        let start = |local_port| {
+            Python::with_gil(|py| {
+                start_client_with_options(
+                    py,
+                    "127.0.0.1",
+                    port,
+                    None,
+                    &ClientOptions {
+                        timeout_seconds: Some(1),
+                        local_port: Some(local_port),
+                        ..Default::default()
+                    },
+                )
+            })
+        };
+        start(local_port).unwrap();
+        assert_eq!(responder.join().unwrap().port(), local_port);
+
+        let _holder = std::net::UdpSocket::bind(("0.0.0.0", local_port)).unwrap();
+        let error = start(local_port).unwrap_err();
+        assert!(error.to_string().contains("already in use"), "{}", error);
+    }
+
+    /// Runs a fake server handing out `RANGES` ranges of 100 values, for the
+    /// round-robin tests.
+    ///
+    /// Every `"start"` it receives is logged with its port. With `stall`, the first
+    /// `"start"` received after one save is left unanswered, and `stall` is notified.
+    ///
+    /// # Returns
+    ///
+    /// The port of the server, and its task returning the ranges saved once it
+    /// answered `"done"`.
+    async fn round_robin_server(
+        starts: Arc<std::sync::Mutex<Vec<u16>>>,
+        stall: Option<Arc<Notify>>,
+    ) -> (u16, tokio::task::JoinHandle<Vec<(u64, u64)>>) {
+        const RANGES: u64 = 3;
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = server.local_addr().unwrap().port();
+        let task = tokio::spawn(async move {
+            let mut buffer = vec![0; 65535];
+            let mut saves = Vec::new();
+            let mut stall = stall;
+            loop {
+                let (size, src) = server.recv_from(&mut buffer).await.unwrap();
+                let request =
+                    Request::from_json(&String::from_utf8_lossy(&buffer[..size])).unwrap();
+                let response = match request.task {
+                    Task::Start => {
+                        starts.lock().unwrap().push(port);
+                        if saves.len() == 1 {
+                            if let Some(stall) = stall.take() {
+                                stall.notify_one();
+                                continue;
+                            }
+                        }
+                        let next = saves.len() as u64;
+                        if next == RANGES {
+                            Response {
+                                task: Task::Done,
+                                status: "completed".to_string(),
+                                ..Default::default()
+                            }
+                        } else {
+                            Response {
+                                task: Task::Range,
+                                start: Some(2 + 100 * next),
+                                end: Some(2 + 100 * (next + 1)),
+                                primes: Some(primes_up_to(20)),
+                                ..Default::default()
                             }
-                            break;
                         }
                     }
-                } else {
-                    if verbose > 1 {
-                        eprintln!("⚠️ Invalid response format!");
+                    Task::Save => {
+                        saves.push((request.start.unwrap(), request.end.unwrap()));
+                        Response {
+                            task: Task::SaveAck,
+                            end: request.end,
+                            ..Default::default()
+                        }
                     }
-                    continue;
+                    _ => unreachable!(),
+                };
+                server
+                    .send_to(response.to_json().unwrap().as_bytes(), src)
+                    .await
+                    .unwrap();
+                if response.task == Task::Done {
+                    return saves;
                 }
             }
-            Ok(Err(e)) => {
-                if verbose > 1 {
-                    eprintln!("❌ Failed to receive data: {:?}", e);
-                }
-                return Err(PyErr::new::<PyValueError, _>(format!(
-                    "Failed to receive response: {}",
-                    e
-                )));
-            }
-            Err(_) => {
-                if verbose > 0 {
-                    eprintln!(
-                        "⚠️ Connection lost: no response received within timeout. Disconnecting."
-                    );
-                }
-                break;
+        });
+        (port, task)
+    }
+
+    /// Tests that a restarted multi-server client resumes its round robin.
+    ///
+    /// The client is killed while waiting on the second server during its fourth turn.
+    /// Restarted from the state file, it must go on with that server, then serve both
+    /// servers until each saved all of its ranges, and remove the file.
+    #[tokio::test]
+    async fn test_multi_client_resumes_round_robin() {
+        let starts = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let stall = Arc::new(Notify::new());
+        let (first_port, first) = round_robin_server(starts.clone(), None).await;
+        let (second_port, second) = round_robin_server(starts.clone(), Some(stall.clone())).await;
+        let servers = vec![
+            ("127.0.0.1".to_string(), first_port),
+            ("127.0.0.1".to_string(), second_port),
+        ];
+        let configs: Vec<_> = servers
+            .iter()
+            .map(|(ip, port)| {
+                let mut config = ClientConfig::new(ip, *port, 0);
+                config.timeout_seconds = 2;
+                config
+            })
+            .collect();
+        let path = std::env::temp_dir().join(format!(
+            "primesocket_multi_client_{}.json",
+            std::process::id()
+        ));
+        let worker = SieveWorker::default();
+
+        let mut state = RoundRobinState::new(servers.clone());
+        let mut summary = ClientSummary::default();
+        tokio::select! {
+            result = run_round_robin(&configs, &mut state, Some(&path), &worker, &mut summary) => {
+                panic!("the client ended before it was killed: {:?}", result)
             }
+            _ = stall.notified() => {}
         }
+        assert_eq!(summary.ranges, 3);
+        let starts_before = starts.lock().unwrap().len();
+
+        let mut state = RoundRobinState::load(&path, &servers).unwrap().unwrap();
+        assert_eq!(state.position, 1);
+        let mut summary = ClientSummary::default();
+        run_round_robin(&configs, &mut state, Some(&path), &worker, &mut summary)
+            .await
+            .unwrap();
+
+        assert_eq!(starts.lock().unwrap()[starts_before], second_port);
+        assert_eq!(summary.ranges, 3);
+        let expected = vec![(2, 102), (102, 202), (202, 302)];
+        assert_eq!(first.await.unwrap(), expected);
+        assert_eq!(second.await.unwrap(), expected);
+        assert!(!path.exists());
     }
-    Ok(())
 }
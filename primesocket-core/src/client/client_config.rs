@@ -0,0 +1,76 @@
+use crate::utils::codec::WireFormat;
+use std::time::Duration;
+
+/// Holds the settings of a client run.
+///
+/// # Fields
+///
+/// * `ip` - The IP address of the server.
+/// * `port` - The UDP port where the server is listening.
+/// * `verbose` - Verbosity level for logging output.
+/// * `timeout_seconds` - Timeout duration in seconds for receiving responses.
+/// * `max_payload` - The largest datagram size announced to the server, if any.
+/// * `stream_block_size` - When set, ranges larger than this many numbers are sieved
+///   and saved in sub-blocks of this size.
+/// * `self_verify` - Whether results are checked locally before being submitted.
+/// * `max_retries` - How many times a `"save"` is resent when its acknowledgment is lost.
+/// * `format` - The encoding of requests and responses; the server must use the same.
+/// * `workers` - How many ranges are computed in parallel over the client socket.
+/// * `connect_retries` - How many times the first `"start"` is resent while the server is silent.
+/// * `retry_backoff` - The delay before the first of those retries, doubled before each next.
+/// * `local_port` - The port the client socket is bound to; an ephemeral port when unset.
+/// * `checksum` - Whether datagrams start with the CRC-32 of their content; received
+///   datagrams that don't match are dropped. The server must use the same setting.
+#[derive(Clone, Debug)]
+pub struct ClientConfig {
+    pub ip: String,
+    pub port: u16,
+    pub verbose: u8,
+    pub timeout_seconds: u64,
+    pub max_payload: Option<u32>,
+    pub stream_block_size: Option<u32>,
+    pub self_verify: bool,
+    pub max_retries: u32,
+    pub format: WireFormat,
+    pub workers: usize,
+    pub connect_retries: u32,
+    pub retry_backoff: Duration,
+    pub local_port: Option<u16>,
+    pub checksum: bool,
+}
+
+impl ClientConfig {
+    /// Creates a new `ClientConfig` with the default settings.
+    ///
+    /// Responses are awaited for 120 seconds, ranges are saved in one piece,
+    /// results are submitted unverified, saves are resent up to 3 times, a single
+    /// range is computed at a time and the first `"start"` is sent once.
+    ///
+    /// # Arguments
+    ///
+    /// * `ip` - The IP address of the server.
+    /// * `port` - The UDP port where the server is listening.
+    /// * `verbose` - Verbosity level for logging output.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `ClientConfig`.
+    pub fn new(ip: &str, port: u16, verbose: u8) -> ClientConfig {
+        ClientConfig {
+            ip: ip.to_string(),
+            port,
+            verbose,
+            timeout_seconds: 120,
+            max_payload: None,
+            stream_block_size: None,
+            self_verify: false,
+            max_retries: 3,
+            format: WireFormat::Json,
+            workers: 1,
+            connect_retries: 0,
+            retry_backoff: Duration::from_millis(500),
+            local_port: None,
+            checksum: false,
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use super::client_config::ClientConfig;
+use super::worker::{worker_by_name, Worker};
+use crate::utils::codec::WireFormat;
+use pyo3::exceptions::{PyAttributeError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyString};
+use std::time::Duration;
+
+/// Holds the optional settings of a client, passed by keyword to `start_client`,
+/// `download_primes` and `start_multi_client`.
+///
+/// Every option is declared once here: the functions take them as `**options`, and
+/// `apply` validates them onto the `ClientConfig` of each server.
+///
+/// # Fields
+///
+/// Each field is the keyword argument of the same name, documented by `start_client`;
+/// `None` when it wasn't given.
+#[pyclass(set_all)]
+#[derive(Default)]
+pub struct ClientOptions {
+    pub timeout_seconds: Option<u64>,
+    pub max_payload: Option<u32>,
+    pub worker: Option<String>,
+    pub sieve_block_size: Option<usize>,
+    pub stream_block_size: Option<u32>,
+    pub self_verify: Option<bool>,
+    pub max_retries: Option<u32>,
+    pub format: Option<String>,
+    pub workers: Option<usize>,
+    pub connect_retries: Option<u32>,
+    pub retry_backoff_ms: Option<u64>,
+    pub local_port: Option<u16>,
+    pub checksum: Option<bool>,
+}
+
+impl ClientOptions {
+    /// Builds the options from the keyword arguments of a client function.
+    ///
+    /// Each argument is assigned to the field of the same name, so values are
+    /// converted exactly as typed arguments would be.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - The Python GIL token.
+    /// * `kwargs` - The keyword arguments, if any were given.
+    ///
+    /// # Returns
+    ///
+    /// The `ClientOptions`, with the options not given left to `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PyTypeError` if an argument is not an option of the client, or if
+    /// its value has the wrong type.
+    pub fn from_kwargs(
+        py: Python<'_>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<ClientOptions> {
+        let options = Bound::new(py, ClientOptions::default())?;
+        for (name, value) in kwargs.into_iter().flatten() {
+            let name = name.downcast_into::<PyString>()?;
+            options.as_any().setattr(&name, value).map_err(|e| {
+                if e.is_instance_of::<PyAttributeError>(py) {
+                    PyTypeError::new_err(format!("Unexpected keyword argument '{}'", name))
+                } else {
+                    e
+                }
+            })?;
+        }
+        let options = std::mem::take(&mut *options.borrow_mut());
+        Ok(options)
+    }
+
+    /// Sets the options given onto `config`, leaving its defaults for the others.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The settings of the run with a server.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PyValueError` if `workers` is `0` or if the format is unknown.
+    pub fn apply(&self, config: &mut ClientConfig) -> PyResult<()> {
+        if let Some(name) = &self.format {
+            config.format = WireFormat::from_name(name).ok_or_else(|| {
+                PyErr::new::<PyValueError, _>(format!("Unknown wire format '{}'", name))
+            })?;
+        }
+        if let Some(timeout_seconds) = self.timeout_seconds {
+            config.timeout_seconds = timeout_seconds;
+        }
+        config.max_payload = self.max_payload;
+        config.stream_block_size = self.stream_block_size;
+        config.self_verify = self.self_verify.unwrap_or(false);
+        if let Some(max_retries) = self.max_retries {
+            config.max_retries = max_retries;
+        }
+        match self.workers {
+            Some(0) => {
+                return Err(PyErr::new::<PyValueError, _>(
+                    "Invalid worker count 0 (expected at least 1)",
+                ))
+            }
+            Some(workers) => config.workers = workers,
+            None => {}
+        }
+        config.connect_retries = self.connect_retries.unwrap_or(0);
+        if let Some(backoff) = self.retry_backoff_ms {
+            config.retry_backoff = Duration::from_millis(backoff);
+        }
+        config.local_port = self.local_port;
+        config.checksum = self.checksum.unwrap_or(false);
+        Ok(())
+    }
+
+    /// Creates the worker named by the `worker` option, the sieve by default.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PyValueError` if the worker is unknown.
+    pub fn worker(&self) -> PyResult<Box<dyn Worker>> {
+        let name = self.worker.as_deref().unwrap_or("sieve");
+        worker_by_name(name, self.sieve_block_size)
+            .ok_or_else(|| PyErr::new::<PyValueError, _>(format!("Unknown worker '{}'", name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that keyword arguments fill the options of the same name and are applied
+    /// onto the config, and that unknown names or mistyped values raise a `TypeError`.
+    #[test]
+    fn test_from_kwargs() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let kwargs = PyDict::new(py);
+            kwargs.set_item("timeout_seconds", 5).unwrap();
+            kwargs.set_item("workers", 4).unwrap();
+            kwargs.set_item("format", py.None()).unwrap();
+            let options = ClientOptions::from_kwargs(py, Some(&kwargs)).unwrap();
+            assert_eq!(options.timeout_seconds, Some(5));
+            assert_eq!(options.format, None);
+            let mut config = ClientConfig::new("127.0.0.1", 8080, 0);
+            options.apply(&mut config).unwrap();
+            assert_eq!((config.timeout_seconds, config.workers), (5, 4));
+            assert_eq!(config.format, WireFormat::Json);
+
+            let unknown = PyDict::new(py);
+            unknown.set_item("timeout", 5).unwrap();
+            let mistyped = PyDict::new(py);
+            mistyped.set_item("workers", "many").unwrap();
+            for kwargs in [unknown, mistyped] {
+                let Err(error) = ClientOptions::from_kwargs(py, Some(&kwargs)) else {
+                    panic!("{} was accepted", kwargs);
+                };
+                assert!(error.is_instance_of::<PyTypeError>(py), "{}", error);
+            }
+        });
+    }
+
+    /// Tests that invalid option values are rejected when applied.
+    #[test]
+    fn test_apply_rejects_invalid_options() {
+        let mut config = ClientConfig::new("127.0.0.1", 8080, 0);
+        let workers = ClientOptions {
+            workers: Some(0),
+            ..Default::default()
+        };
+        let format = ClientOptions {
+            format: Some("xml".to_string()),
+            ..Default::default()
+        };
+        let worker = ClientOptions {
+            worker: Some("abacus".to_string()),
+            ..Default::default()
+        };
+        assert!(workers.apply(&mut config).is_err());
+        assert!(format.apply(&mut config).is_err());
+        assert!(worker.worker().is_err());
+    }
+}
@@ -0,0 +1,40 @@
+use pyo3::prelude::*;
+
+/// Summarizes the contribution of a client run for Python callers.
+///
+/// The `ClientSummary` is returned by `start_client` when the client exits, so
+/// orchestrators can aggregate the work done by each worker process. Like the
+/// per-client stats kept by the server, only saves the server accepted count.
+///
+/// # Fields
+///
+/// * `ranges` - The number of ranges (or sub-blocks) saved and accepted.
+/// * `primes` - The number of primes submitted in accepted saves.
+/// * `bytes_sent` - The size of the requests sent, in bytes.
+/// * `bytes_received` - The size of the responses received, in bytes.
+///
+/// # Example (Python)
+///
+/// ```python
+/// import primesocket_core
+/// summary = primesocket_core.start_client("127.0.0.1", 8080)
+/// print(summary.ranges, summary.primes, summary.bytes_sent, summary.bytes_received)
+/// ```
+#[pyclass(get_all, frozen)]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClientSummary {
+    pub ranges: u32,
+    pub primes: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+#[pymethods]
+impl ClientSummary {
+    fn __repr__(&self) -> String {
+        format!(
+            "ClientSummary(ranges={}, primes={}, bytes_sent={}, bytes_received={})",
+            self.ranges, self.primes, self.bytes_sent, self.bytes_received
+        )
+    }
+}
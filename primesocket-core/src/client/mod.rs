@@ -1,3 +1,9 @@
-mod request_handler;
+mod client_config;
+pub(crate) mod client_options;
+pub mod client_summary;
+pub(crate) mod request_handler;
+pub(crate) mod round_robin;
+pub mod worker;
 
+#[allow(clippy::module_inception)]
 pub mod client;
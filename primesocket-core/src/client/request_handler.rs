@@ -1,93 +1,340 @@
+use super::worker::Worker;
 use crate::utils;
+use crate::utils::chunk::send_message;
+use crate::utils::codec::WireFormat;
+use crate::utils::sieve::primes_up_to;
+use log::{debug, warn};
 use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
-use tokio::net::UdpSocket;
-use utils::json::{Request, Response};
-use utils::sieve::sieve_segment;
+use std::net::{IpAddr, SocketAddr};
+use tokio::net::{lookup_host, UdpSocket};
+use utils::json::{Request, Response, Task};
 
 /// Handles incoming requests and processes them based on the requested task.
 ///
 /// This function processes different types of tasks:
-/// - If the task is `"range"`, it computes a new range of prime numbers using the `sieve_segment` function.
+/// - If the task is `"range"`, it computes the results for the new range using `worker`,
+///   restricted to the residue class the server sent, if any.
 /// - If the task is `"continue"`, it indicates that the server should continue processing.
 /// - Any other task is handled with a `"close"` response.
 ///
 /// # Arguments
 ///
 /// * `response` - A `Response` object containing the task to be processed and optional parameters.
+/// * `worker` - The `Worker` computing the results of assigned ranges.
 ///
 /// # Returns
 ///
 /// A `Request` object containing the task to be processed next along with any relevant data.
-pub async fn handler(response: Response) -> Request {
-    match response.task.as_str() {
-        "range" => {
-            let start = response.start.unwrap();
-            let end = response.end.unwrap();
-            let primes = response.primes.unwrap();
-            let result = sieve_segment(start, end, primes);
-            Request {
-                task: "save".to_string(),
-                end: Some(end),
-                primes: Some(result),
-            }
-        }
-        "continue" => Request {
-            task: "continue".to_string(),
-            end: None,
-            primes: None,
+pub async fn handler(response: Response, worker: &dyn Worker) -> Request {
+    match response.task {
+        Task::Range => compute_save(
+            worker,
+            response.start.unwrap(),
+            response.end.unwrap(),
+            base_primes(&response),
+            response.residue,
+        ),
+        Task::Continue => Request {
+            task: Task::Continue,
+            ..Default::default()
         },
         _ => Request {
-            task: "close".to_string(),
-            end: None,
-            primes: None,
+            task: Task::Close,
+            ..Default::default()
         },
     }
 }
 
+/// Returns the base primes of a `"range"` response.
+///
+/// They are usually sent inline. When the server offloaded them to a file
+/// (`primes_path`) because the response was too large, they are read from it;
+/// if that file can't be read either, they are sieved locally up to `√end`.
+///
+/// # Arguments
+///
+/// * `response` - The `"range"` response.
+///
+/// # Returns
+///
+/// The base primes to sieve the range with.
+pub fn base_primes(response: &Response) -> Vec<u64> {
+    if let Some(primes) = &response.primes {
+        return primes.clone();
+    }
+
+    let from_file = response.primes_path.as_ref().and_then(|path| {
+        let contents = std::fs::read_to_string(path).ok()?;
+        contents
+            .lines()
+            .map(|line| line.trim().parse().ok())
+            .collect::<Option<Vec<u64>>>()
+    });
+    from_file.unwrap_or_else(|| {
+        let end = response.end.unwrap_or(0);
+        primes_up_to((end as f64).sqrt() as u64 + 1)
+    })
+}
+
+/// Computes the results of `[start, end]` and wraps them in a `"save"` request.
+///
+/// # Arguments
+///
+/// * `worker` - The `Worker` computing the results.
+/// * `start` - The start of the range (inclusive).
+/// * `end` - The end of the range (inclusive).
+/// * `primes` - The base primes sent by the server.
+/// * `residue` - The residue class `(a, m)` the results are restricted to, if any.
+///
+/// # Returns
+///
+/// A `"save"` `Request` carrying the results of the range.
+pub fn compute_save(
+    worker: &dyn Worker,
+    start: u64,
+    end: u64,
+    primes: Vec<u64>,
+    residue: Option<(u64, u64)>,
+) -> Request {
+    let result = match residue {
+        Some((a, m)) => worker.compute_in_class(start, end, primes, a, m),
+        None => worker.compute(start, end, primes),
+    };
+    Request {
+        task: Task::Save,
+        start: Some(start),
+        end: Some(end),
+        primes: Some(result),
+        ..Default::default()
+    }
+}
+
+/// Replaces the primes of a `"save"` with their sum and count, for a server in
+/// sum-only mode.
+///
+/// # Arguments
+///
+/// * `save` - The `"save"` carrying the primes of a range.
+///
+/// # Returns
+///
+/// The `"save"` with `sum` and `count` set and no `primes`.
+pub fn into_sum_only(save: Request) -> Request {
+    let primes = save.primes.unwrap_or_default();
+    Request {
+        sum: Some(primes.iter().map(|&p| u128::from(p)).sum()),
+        count: Some(primes.len() as u64),
+        primes: None,
+        ..save
+    }
+}
+
+/// How many times a range failing self-verification is recomputed before it is abandoned.
+pub const MAX_VERIFY_ATTEMPTS: u32 = 3;
+
+/// Computes a `"save"` like `compute_save`, checking the results before returning them.
+///
+/// Results rejected by `Worker::verify` are logged and recomputed, up to
+/// `MAX_VERIFY_ATTEMPTS` times, so a bad result is never submitted.
+///
+/// # Arguments
+///
+/// * `worker` - The `Worker` computing and verifying the results.
+/// * `start` - The start of the range (inclusive).
+/// * `end` - The end of the range (inclusive).
+/// * `primes` - The base primes sent by the server.
+/// * `residue` - The residue class `(a, m)` the results are restricted to, if any.
+///
+/// # Returns
+///
+/// `Some(Request)` with a verified `"save"`, or `None` if every attempt failed verification.
+pub fn compute_verified(
+    worker: &dyn Worker,
+    start: u64,
+    end: u64,
+    primes: Vec<u64>,
+    residue: Option<(u64, u64)>,
+) -> Option<Request> {
+    for attempt in 1..=MAX_VERIFY_ATTEMPTS {
+        let save = compute_save(worker, start, end, primes.clone(), residue);
+        let results = save.primes.as_deref().unwrap_or_default();
+        if worker.verify(start, end, results, residue) {
+            return Some(save);
+        }
+        warn!(
+            "⚠️ Self-verification of [{}, {}] failed, attempt {}/{}",
+            start, end, attempt, MAX_VERIFY_ATTEMPTS
+        );
+    }
+    None
+}
+
+/// Splits `[start, end]` into consecutive, disjoint sub-ranges.
+///
+/// # Arguments
+///
+/// * `start` - The start of the range (inclusive).
+/// * `end` - The end of the range (inclusive).
+/// * `block` - The largest number of values in a sub-range (at least `1`).
+///
+/// # Returns
+///
+/// An iterator over the `(start, end)` bounds of the sub-ranges, in ascending order.
+///
+/// # Example
+///
+/// ```
+/// let blocks: Vec<_> = split_range(0, 9, 4).collect();
+/// assert_eq!(blocks, vec![(0, 3), (4, 7), (8, 9)]);
+/// ```
+pub fn split_range(start: u64, end: u64, block: u64) -> impl Iterator<Item = (u64, u64)> {
+    let block = block.max(1);
+    let mut next = Some(start).filter(|&start| start <= end);
+    std::iter::from_fn(move || {
+        let low = next?;
+        let high = low.saturating_add(block - 1).min(end);
+        next = high.checked_add(1).filter(|&n| n <= end);
+        Some((low, high))
+    })
+}
+
+/// Checks whether the server accepted a `"save"` from its acknowledgment.
+///
+/// The server acknowledges accepted saves with `"save_ack"` (more work left) or
+/// `"done"`; older servers answer `"continue"` instead of `"save_ack"`. Any other
+/// reply, such as an `"error"`, means the save was rejected.
+///
+/// # Arguments
+///
+/// * `ack` - The response received after sending a `"save"`.
+///
+/// # Returns
+///
+/// `true` if the save was accepted.
+pub fn save_accepted(ack: &Response) -> bool {
+    matches!(ack.task, Task::SaveAck | Task::Continue | Task::Done)
+}
+
+/// Checks whether a reply received after sending a `"save"` answers that save.
+///
+/// Acknowledgments echo the `end` they recorded, so a late acknowledgment of an
+/// earlier save can be told apart. Replies without an `end` are taken as answers.
+///
+/// # Arguments
+///
+/// * `ack` - The response received after sending a `"save"`.
+/// * `save` - The `"save"` sent.
+///
+/// # Returns
+///
+/// `true` if the reply answers `save`.
+pub fn acknowledges(ack: &Response, save: &Request) -> bool {
+    ack.end.is_none_or(|end| save.end == Some(end))
+}
+
 /// Sends a request to the specified UDP socket and target address.
 ///
-/// This function serializes a `Request` into JSON format and sends it over the socket to the specified target address.
+/// This function serializes a `Request` in `format` and sends it over the socket to the specified target address.
+/// Requests larger than `max_payload` are split into chunks.
 ///
 /// # Arguments
 ///
 /// * `socket` - The `UdpSocket` to send the request through.
-/// * `ip` - The target IP address to send the request to.
-/// * `port` - The target port to send the request to.
+/// * `server` - The address of the server, from `resolve_server`.
 /// * `request` - The `Request` to be sent.
-/// * `verbose` - Verbosity level for logging output.
+/// * `max_payload` - The largest datagram size accepted by the server.
+/// * `format` - The encoding of the request.
+/// * `checksum` - Whether each datagram is sealed with its CRC-32.
 ///
 /// # Returns
 ///
-/// This function returns a `PyResult<()>`, indicating success or failure. If the request fails to send,
-/// an error is returned with a message describing the failure.
+/// This function returns a `PyResult<usize>` with the size of the serialized request, in bytes.
+/// If the request fails to serialize or to send, an error is returned with a message describing
+/// the failure.
 pub async fn send_request(
     socket: &UdpSocket,
-    ip: &str,
-    port: u16,
+    server: SocketAddr,
     request: &Request,
-    verbose: u8,
-) -> PyResult<()> {
-    let request_json = request.to_json();
-    if verbose > 1 {
-        println!("📩 Sending request to {}:{}: {}", ip, port, request_json);
-    }
+    max_payload: usize,
+    format: WireFormat,
+    checksum: bool,
+) -> PyResult<usize> {
+    let request_bytes = format.encode(request).map_err(|e| {
+        PyErr::new::<PyValueError, _>(format!("Failed to serialize request: {}", e))
+    })?;
+    debug!(
+        "📩 Sending request to {}: {}",
+        server,
+        String::from_utf8_lossy(&request_bytes)
+    );
 
-    let target = format!("{}:{}", ip, port);
-
-    socket
-        .send_to(request_json.as_bytes(), &target)
+    send_message(socket, &request_bytes, server, max_payload, checksum)
         .await
         .map_err(|e| PyErr::new::<PyValueError, _>(format!("Failed to send request: {}", e)))?;
 
-    Ok(())
+    Ok(request_bytes.len())
+}
+
+/// Resolves the address of the server.
+///
+/// IP literals are parsed directly, IPv6 ones with or without brackets (`"::1"` or
+/// `"[::1]"`); anything else is looked up as a host name, keeping the first address.
+///
+/// # Arguments
+///
+/// * `ip` - The IP address or host name of the server.
+/// * `port` - The port of the server.
+///
+/// # Returns
+///
+/// The `SocketAddr` of the server.
+///
+/// # Errors
+///
+/// Returns a `PyValueError` if the host name can't be resolved.
+pub async fn resolve_server(ip: &str, port: u16) -> PyResult<SocketAddr> {
+    let host = ip
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(ip);
+    if let Ok(addr) = host.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(addr, port));
+    }
+    lookup_host((host, port))
+        .await
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .ok_or_else(|| {
+            PyErr::new::<PyValueError, _>(format!("Failed to resolve address {}:{}", ip, port))
+        })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::client::worker::SieveWorker;
     use crate::utils::json::Response;
 
+    /// Tests that IPv4 and IPv6 literals, bracketed or not, resolve without a lookup,
+    /// and that host names are looked up.
+    #[tokio::test]
+    async fn test_resolve_server() {
+        let v6: SocketAddr = "[::1]:4000".parse().unwrap();
+        assert_eq!(resolve_server("[::1]", 4000).await.unwrap(), v6);
+        assert_eq!(resolve_server("::1", 4000).await.unwrap(), v6);
+        assert_eq!(
+            resolve_server("127.0.0.1", 4000).await.unwrap(),
+            "127.0.0.1:4000".parse().unwrap()
+        );
+        assert!(resolve_server("localhost", 4000)
+            .await
+            .unwrap()
+            .ip()
+            .is_loopback());
+        assert!(resolve_server("[::1", 4000).await.is_err());
+    }
+
     /// Tests the `handler` function when a "range" request is sent.
     ///
     /// This test ensures that when a client sends a `"range"` request:
@@ -96,15 +343,17 @@ mod tests {
     #[tokio::test]
     async fn test_handler_range_request() {
         let response = Response {
-            task: "range".to_string(),
+            task: Task::Range,
             status: "processing".to_string(),
             start: Some(0),
             end: Some(100),
             primes: Some(vec![2, 3, 5, 7, 11]),
+            ..Default::default()
         };
 
-        let request = handler(response).await;
-        assert_eq!(request.task, "save");
+        let request = handler(response, &SieveWorker::default()).await;
+        assert_eq!(request.task, Task::Save);
+        assert_eq!(request.start, Some(0));
         assert_eq!(request.end, Some(100));
         assert!(request.primes.is_some());
     }
@@ -116,16 +365,147 @@ mod tests {
     #[tokio::test]
     async fn test_handler_continue_request() {
         let response = Response {
-            task: "continue".to_string(),
+            task: Task::Continue,
             status: "completed".to_string(),
-            start: None,
-            end: None,
-            primes: None,
+            ..Default::default()
         };
 
-        let request = handler(response).await;
-        assert_eq!(request.task, "continue");
+        let request = handler(response, &SieveWorker::default()).await;
+        assert_eq!(request.task, Task::Continue);
         assert!(request.end.is_none());
         assert!(request.primes.is_none());
     }
+
+    /// Tests that base primes come inline, from the offload file, or are sieved locally.
+    #[test]
+    fn test_base_primes_fallbacks() {
+        let path = std::env::temp_dir().join(format!("primesocket_bp_{}.txt", std::process::id()));
+        std::fs::write(&path, "2\n3\n5\n").unwrap();
+        let range = |primes, primes_path| Response {
+            task: Task::Range,
+            end: Some(100),
+            primes,
+            primes_path,
+            ..Default::default()
+        };
+
+        let inline = base_primes(&range(Some(vec![2, 3]), None));
+        let from_file = base_primes(&range(None, Some(path.display().to_string())));
+        let missing = base_primes(&range(None, Some("/nonexistent/primes".to_string())));
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(inline, vec![2, 3]);
+        assert_eq!(from_file, vec![2, 3, 5]);
+        assert_eq!(missing, vec![2, 3, 5, 7, 11]);
+    }
+
+    /// A sieve worker corrupting the results of its first `corrupt` computations.
+    struct CorruptWorker {
+        corrupt: std::sync::atomic::AtomicU32,
+    }
+
+    impl Worker for CorruptWorker {
+        fn name(&self) -> &'static str {
+            "corrupt"
+        }
+
+        fn compute(&self, start: u64, end: u64, primes: Vec<u64>) -> Vec<u64> {
+            let mut results = SieveWorker::default().compute(start, end, primes);
+            let remaining = self.corrupt.load(std::sync::atomic::Ordering::Relaxed);
+            if remaining > 0 {
+                self.corrupt
+                    .store(remaining - 1, std::sync::atomic::Ordering::Relaxed);
+                results[0] += 1;
+            }
+            results
+        }
+
+        fn verify(
+            &self,
+            start: u64,
+            end: u64,
+            results: &[u64],
+            residue: Option<(u64, u64)>,
+        ) -> bool {
+            SieveWorker::default().verify(start, end, results, residue)
+        }
+    }
+
+    /// Tests that self-verification catches corrupted results before they are submitted.
+    ///
+    /// A transient corruption is recomputed into a correct save; a persistent one
+    /// never yields a save at all.
+    #[test]
+    fn test_compute_verified_catches_corruption() {
+        let primes = vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31];
+        let worker = |corrupt| CorruptWorker {
+            corrupt: std::sync::atomic::AtomicU32::new(corrupt),
+        };
+
+        let corrupted = compute_save(&worker(1), 100, 1_000, primes.clone(), None);
+        assert!(!SieveWorker::default().verify(
+            100,
+            1_000,
+            corrupted.primes.as_deref().unwrap(),
+            None
+        ));
+
+        let save = compute_verified(&worker(1), 100, 1_000, primes.clone(), None).unwrap();
+        assert_eq!(
+            save.primes.unwrap(),
+            SieveWorker::default().compute(100, 1_000, primes.clone())
+        );
+
+        let save = compute_verified(&worker(MAX_VERIFY_ATTEMPTS), 100, 1_000, primes, None);
+        assert!(save.is_none());
+    }
+
+    /// Tests that only `"save_ack"`, `"continue"` and `"done"` acknowledge a save.
+    #[test]
+    fn test_save_accepted() {
+        let ack = |task| Response {
+            task,
+            ..Default::default()
+        };
+
+        assert!(save_accepted(&ack(Task::SaveAck)));
+        assert!(save_accepted(&ack(Task::Continue)));
+        assert!(save_accepted(&ack(Task::Done)));
+        assert!(!save_accepted(&ack(Task::Error)));
+    }
+
+    /// Tests that replies echoing another `end` don't answer a save.
+    #[test]
+    fn test_acknowledges() {
+        let save = Request {
+            task: Task::Save,
+            start: Some(2),
+            end: Some(100),
+            ..Default::default()
+        };
+        let ack = |end| Response {
+            task: Task::SaveAck,
+            end,
+            ..Default::default()
+        };
+
+        assert!(acknowledges(&ack(Some(100)), &save));
+        assert!(acknowledges(&ack(None), &save));
+        assert!(!acknowledges(&ack(Some(50)), &save));
+    }
+
+    /// Tests that sub-ranges cover a range exactly once.
+    #[test]
+    fn test_split_range() {
+        assert_eq!(
+            split_range(0, 9, 4).collect::<Vec<_>>(),
+            vec![(0, 3), (4, 7), (8, 9)]
+        );
+        assert_eq!(split_range(5, 5, 10).collect::<Vec<_>>(), vec![(5, 5)]);
+        assert_eq!(split_range(6, 5, 10).count(), 0);
+        assert_eq!(
+            split_range(u64::MAX - 2, u64::MAX, 2).collect::<Vec<_>>(),
+            vec![(u64::MAX - 2, u64::MAX - 1), (u64::MAX, u64::MAX)]
+        );
+    }
 }
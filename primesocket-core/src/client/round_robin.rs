@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Tracks which servers a multi-server client still helps, and whose turn is next.
+///
+/// The client serves one range per turn, going round the servers in order and
+/// skipping those that are done. The state is written after every turn, so a
+/// restarted client resumes where it stopped.
+///
+/// # Fields
+///
+/// * `servers` - The `(ip, port)` of every server configured, in turn order.
+/// * `position` - The index in `servers` of the next server to serve.
+/// * `finished` - Whether each server is done, i.e. has no work left for the client.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RoundRobinState {
+    pub servers: Vec<(String, u16)>,
+    pub position: usize,
+    pub finished: Vec<bool>,
+}
+
+impl RoundRobinState {
+    /// Creates a `RoundRobinState` starting with the first server.
+    ///
+    /// # Arguments
+    ///
+    /// * `servers` - The `(ip, port)` of every server, in turn order.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `RoundRobinState` with no server finished.
+    pub fn new(servers: Vec<(String, u16)>) -> RoundRobinState {
+        let finished = vec![false; servers.len()];
+        RoundRobinState {
+            servers,
+            position: 0,
+            finished,
+        }
+    }
+
+    /// Loads the state a previous run left in `path`, if it was for the same servers.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The state file.
+    /// * `servers` - The servers configured for this run.
+    ///
+    /// # Returns
+    ///
+    /// `Some(RoundRobinState)` to resume from, or `None` if the file doesn't exist or
+    /// was written for another list of servers.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` if the file exists but can't be read or parsed.
+    pub fn load(path: &Path, servers: &[(String, u16)]) -> io::Result<Option<RoundRobinState>> {
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let state: RoundRobinState = serde_json::from_slice(&bytes)?;
+        let consistent =
+            state.finished.len() == state.servers.len() && state.position < state.servers.len();
+        Ok((consistent && state.servers == servers).then_some(state))
+    }
+
+    /// Writes the state to `path`, replacing the previous one atomically.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` if the file could not be written.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_vec(self)?;
+        // Write aside first, so a crash mid-write never corrupts the last state.
+        let staging = path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&staging, json)?;
+        fs::rename(staging, path)
+    }
+
+    /// Finds the next server still having work, from `position` on.
+    ///
+    /// # Returns
+    ///
+    /// The index of the server, or `None` once every server is finished.
+    pub fn next_server(&self) -> Option<usize> {
+        let count = self.servers.len();
+        (0..count)
+            .map(|offset| (self.position + offset) % count)
+            .find(|&index| !self.finished[index])
+    }
+
+    /// Records the turn of server `index`, and moves on to the server after it.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The server that was just served.
+    /// * `has_more` - Whether the server may still have work for the client.
+    pub fn advance(&mut self, index: usize, has_more: bool) {
+        self.finished[index] = !has_more;
+        self.position = (index + 1) % self.servers.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that turns go round the servers, skipping finished ones, and that the
+    /// state only resumes for the same servers.
+    #[test]
+    fn test_round_robin_state() {
+        let servers = vec![("127.0.0.1".to_string(), 4000), ("::1".to_string(), 4001)];
+        let mut state = RoundRobinState::new(servers.clone());
+        assert_eq!(state.next_server(), Some(0));
+        state.advance(0, true);
+        assert_eq!(state.next_server(), Some(1));
+        state.advance(1, false);
+        assert_eq!(state.next_server(), Some(0));
+        state.advance(0, false);
+        assert_eq!(state.next_server(), None);
+
+        let path = std::env::temp_dir().join(format!("primesocket_rr_{}.json", std::process::id()));
+        state.position = 1;
+        state.save(&path).unwrap();
+        assert_eq!(RoundRobinState::load(&path, &servers).unwrap(), Some(state));
+        assert_eq!(RoundRobinState::load(&path, &servers[..1]).unwrap(), None);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(RoundRobinState::load(&path, &servers).unwrap(), None);
+    }
+}
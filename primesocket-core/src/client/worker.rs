@@ -0,0 +1,198 @@
+#[cfg(feature = "mersenne")]
+use crate::utils::mersenne::lucas_lehmer;
+use crate::utils::primality::verify_primes;
+use crate::utils::sieve::{
+    sieve_residue_class, sieve_segment_blocked, sieve_segment_count, DEFAULT_BLOCK_SIZE,
+};
+
+/// Computes the results of a range assigned by the server.
+///
+/// Workers plug specialized computations into the distribution framework:
+/// the server hands out `[start, end]` ranges and merges whatever numbers the
+/// worker reports for them.
+pub trait Worker: Send + Sync {
+    /// Returns the name used to select the worker.
+    fn name(&self) -> &'static str;
+
+    /// Computes the numbers to report for `[start, end]`.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The start of the assigned range (inclusive).
+    /// * `end` - The end of the assigned range (inclusive).
+    /// * `primes` - The base primes sent by the server.
+    fn compute(&self, start: u64, end: u64, primes: Vec<u64>) -> Vec<u64>;
+
+    /// Computes the numbers to report for `[start, end]` that are congruent to `a` modulo `m`.
+    ///
+    /// The default implementation filters the results of `compute`.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The start of the assigned range (inclusive).
+    /// * `end` - The end of the assigned range (inclusive).
+    /// * `primes` - The base primes sent by the server.
+    /// * `a` - The residue of the class.
+    /// * `m` - The modulus of the class.
+    fn compute_in_class(&self, start: u64, end: u64, primes: Vec<u64>, a: u64, m: u64) -> Vec<u64> {
+        self.compute(start, end, primes)
+            .into_iter()
+            .filter(|n| n % m == a % m)
+            .collect()
+    }
+
+    /// Counts the numbers to report for `[start, end]`, for callers that only need the count.
+    ///
+    /// The default implementation counts the results of `compute`.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The start of the assigned range (inclusive).
+    /// * `end` - The end of the assigned range (inclusive).
+    /// * `primes` - The base primes sent by the server.
+    fn count(&self, start: u64, end: u64, primes: Vec<u64>) -> u64 {
+        self.compute(start, end, primes).len() as u64
+    }
+
+    /// Checks the results computed for `[start, end]` with an independent method.
+    ///
+    /// The default implementation accepts every result.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The start of the assigned range (inclusive).
+    /// * `end` - The end of the assigned range (inclusive).
+    /// * `results` - The numbers computed for the range.
+    /// * `residue` - The residue class `(a, m)` the results are restricted to, if any.
+    ///
+    /// # Returns
+    ///
+    /// `true` if no error was found.
+    fn verify(
+        &self,
+        _start: u64,
+        _end: u64,
+        _results: &[u64],
+        _residue: Option<(u64, u64)>,
+    ) -> bool {
+        true
+    }
+}
+
+/// The default worker, finding primes with a segmented sieve.
+///
+/// # Fields
+///
+/// * `block_size` - The number of candidates sieved at once.
+pub struct SieveWorker {
+    pub block_size: usize,
+}
+
+impl Default for SieveWorker {
+    fn default() -> SieveWorker {
+        SieveWorker {
+            block_size: DEFAULT_BLOCK_SIZE,
+        }
+    }
+}
+
+impl Worker for SieveWorker {
+    fn name(&self) -> &'static str {
+        "sieve"
+    }
+
+    fn compute(&self, start: u64, end: u64, primes: Vec<u64>) -> Vec<u64> {
+        sieve_segment_blocked(start, end, primes, self.block_size)
+    }
+
+    fn compute_in_class(&self, start: u64, end: u64, primes: Vec<u64>, a: u64, m: u64) -> Vec<u64> {
+        sieve_residue_class(start, end, primes, a, m)
+    }
+
+    fn count(&self, start: u64, end: u64, primes: Vec<u64>) -> u64 {
+        sieve_segment_count(start, end, primes)
+    }
+
+    fn verify(&self, start: u64, end: u64, results: &[u64], residue: Option<(u64, u64)>) -> bool {
+        verify_primes(start, end, results, residue)
+    }
+}
+
+/// A worker treating ranges as exponents and reporting the `p` for which
+/// the Mersenne number `2^p - 1` is prime, using the Lucas–Lehmer test.
+///
+/// Exponents above `utils::mersenne::MAX_EXPONENT` are not supported and are
+/// never reported.
+#[cfg(feature = "mersenne")]
+pub struct MersenneWorker;
+
+#[cfg(feature = "mersenne")]
+impl Worker for MersenneWorker {
+    fn name(&self) -> &'static str {
+        "mersenne"
+    }
+
+    fn compute(&self, start: u64, end: u64, _primes: Vec<u64>) -> Vec<u64> {
+        (start..=end)
+            .filter(|&p| u32::try_from(p).is_ok_and(|p| lucas_lehmer(p) == Some(true)))
+            .collect()
+    }
+}
+
+/// Looks up a worker by name.
+///
+/// # Arguments
+///
+/// * `name` - The worker name (`"sieve"`, or `"mersenne"` with the `mersenne` feature).
+/// * `sieve_block_size` - The block size used by the sieve worker, if not the default.
+///
+/// # Returns
+///
+/// `Some(Box<dyn Worker>)` if a worker with that name is available, otherwise `None`.
+pub fn worker_by_name(name: &str, sieve_block_size: Option<usize>) -> Option<Box<dyn Worker>> {
+    match name {
+        "sieve" => Some(Box::new(SieveWorker {
+            block_size: sieve_block_size.unwrap_or(DEFAULT_BLOCK_SIZE),
+        })),
+        #[cfg(feature = "mersenne")]
+        "mersenne" => Some(Box::new(MersenneWorker)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that workers are looked up by name.
+    #[test]
+    fn test_worker_by_name() {
+        assert_eq!(worker_by_name("sieve", None).unwrap().name(), "sieve");
+        assert!(worker_by_name("unknown", None).is_none());
+    }
+
+    /// Tests that the Mersenne worker reports the known prime exponents below 32.
+    #[cfg(feature = "mersenne")]
+    #[test]
+    fn test_mersenne_worker_known_exponents() {
+        let worker = worker_by_name("mersenne", None).unwrap();
+
+        assert_eq!(
+            worker.compute(2, 31, vec![]),
+            vec![2, 3, 5, 7, 13, 17, 19, 31]
+        );
+    }
+
+    /// Tests that the sieve worker gives the same primes for any block size.
+    #[test]
+    fn test_sieve_worker_block_size() {
+        let primes = vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31];
+        let default = worker_by_name("sieve", None).unwrap();
+        let tiny = worker_by_name("sieve", Some(16)).unwrap();
+
+        assert_eq!(
+            default.compute(1_000, 1_100, primes.clone()),
+            tiny.compute(1_000, 1_100, primes)
+        );
+    }
+}
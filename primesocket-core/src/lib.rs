@@ -2,14 +2,35 @@ pub mod client;
 pub mod server;
 pub mod utils;
 
-use crate::client::client::start_client;
-use crate::server::server::start_server;
+use crate::client::client::{download_primes, start_client, start_multi_client};
+use crate::client::client_summary::ClientSummary;
+use crate::server::autotune::autotune_step;
+use crate::server::computation_summary::ComputationSummary;
+use crate::server::local::run_local;
+use crate::server::run_result::RunResult;
+use crate::server::server::{spawn_server, start_server};
+use crate::server::server_handle::ServerHandle;
+use crate::utils::primality::{is_prime, next_prime, prev_prime};
+use crate::utils::sieve::primes_below;
 
 use pyo3::prelude::*;
 
 #[pymodule]
 fn primesocket_core(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(start_server, m)?)?;
+    m.add_function(wrap_pyfunction!(spawn_server, m)?)?;
     m.add_function(wrap_pyfunction!(start_client, m)?)?;
+    m.add_function(wrap_pyfunction!(start_multi_client, m)?)?;
+    m.add_function(wrap_pyfunction!(download_primes, m)?)?;
+    m.add_function(wrap_pyfunction!(run_local, m)?)?;
+    m.add_function(wrap_pyfunction!(autotune_step, m)?)?;
+    m.add_function(wrap_pyfunction!(is_prime, m)?)?;
+    m.add_function(wrap_pyfunction!(next_prime, m)?)?;
+    m.add_function(wrap_pyfunction!(prev_prime, m)?)?;
+    m.add_function(wrap_pyfunction!(primes_below, m)?)?;
+    m.add_class::<RunResult>()?;
+    m.add_class::<ComputationSummary>()?;
+    m.add_class::<ClientSummary>()?;
+    m.add_class::<ServerHandle>()?;
     Ok(())
 }
@@ -0,0 +1,213 @@
+use crate::utils::json::OutstandingRange;
+use std::collections::{BTreeMap, HashSet};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The default time a client has to submit an assigned range.
+pub const DEFAULT_LEASE_DURATION: Duration = Duration::from_secs(120);
+
+/// The order in which ranges are handed out.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum AssignmentOrder {
+    /// From the low end of `[start, end]` upwards.
+    #[default]
+    Ascending,
+    /// From the high end of `[start, end]` downwards, so the largest numbers start early.
+    Descending,
+}
+
+impl AssignmentOrder {
+    /// Looks up an order by name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - `"ascending"` or `"descending"`.
+    ///
+    /// # Returns
+    ///
+    /// `Some(AssignmentOrder)`, or `None` if the name is unknown.
+    pub fn from_name(name: &str) -> Option<AssignmentOrder> {
+        match name {
+            "ascending" => Some(AssignmentOrder::Ascending),
+            "descending" => Some(AssignmentOrder::Descending),
+            _ => None,
+        }
+    }
+}
+
+/// Represents a range handed out to a client that has not been saved yet.
+///
+/// # Fields
+///
+/// * `start` - The first number of the range.
+/// * `end` - The last number of the range.
+/// * `assigned_to` - The address of the client working on the range.
+/// * `lease_expires` - The moment the assignment is considered stale.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Assignment {
+    pub start: u64,
+    pub end: u64,
+    pub assigned_to: SocketAddr,
+    pub lease_expires: Instant,
+}
+
+/// Keeps track of the ranges assigned to clients and not completed yet.
+///
+/// # Fields
+///
+/// * `lease_duration` - How long a client holds an assignment.
+/// * `assignments` - The outstanding assignments, keyed by the start of their range.
+/// * `reclaimed` - The unsaved ranges whose lease expired, as `start -> end`, waiting
+///   to be handed out again.
+#[derive(Clone, Debug)]
+pub struct AssignmentTracker {
+    pub lease_duration: Duration,
+    pub assignments: BTreeMap<u64, Assignment>,
+    pub reclaimed: BTreeMap<u64, u64>,
+}
+
+impl Default for AssignmentTracker {
+    fn default() -> AssignmentTracker {
+        AssignmentTracker {
+            lease_duration: DEFAULT_LEASE_DURATION,
+            assignments: BTreeMap::new(),
+            reclaimed: BTreeMap::new(),
+        }
+    }
+}
+
+impl AssignmentTracker {
+    /// Records that `[start, end]` was handed out to a client.
+    ///
+    /// A range handed out again replaces its previous assignment, and leaves the
+    /// reclaimed pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The first number of the range.
+    /// * `end` - The last number of the range.
+    /// * `client` - The address of the client receiving the range.
+    pub fn assign(&mut self, start: u64, end: u64, client: SocketAddr) {
+        self.reclaimed.remove(&start);
+        self.assignments.insert(
+            start,
+            Assignment {
+                start,
+                end,
+                assigned_to: client,
+                lease_expires: Instant::now() + self.lease_duration,
+            },
+        );
+    }
+
+    /// Marks the assignment ending at `end` as completed, along with a reclaimed range
+    /// ending there.
+    ///
+    /// # Arguments
+    ///
+    /// * `end` - The last number of the saved range.
+    pub fn complete(&mut self, end: u64) {
+        self.assignments
+            .retain(|_, assignment| assignment.end != end);
+        self.reclaimed
+            .retain(|_, &mut reclaimed_end| reclaimed_end != end);
+    }
+
+    /// Removes the assignments whose lease expired.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The moment leases are checked against.
+    ///
+    /// # Returns
+    ///
+    /// The expired assignments, ordered by range start.
+    pub fn take_expired(&mut self, now: Instant) -> Vec<Assignment> {
+        let expired: Vec<u64> = self
+            .assignments
+            .values()
+            .filter(|assignment| assignment.lease_expires <= now)
+            .map(|assignment| assignment.start)
+            .collect();
+        expired
+            .into_iter()
+            .filter_map(|start| self.assignments.remove(&start))
+            .collect()
+    }
+
+    /// Finds the outstanding assignment in flight at `n`.
+    ///
+    /// Consecutive ranges share their bounds, so an assignment holds the numbers
+    /// from its `start` up to, but not including, its `end`.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - The number to look up.
+    ///
+    /// # Returns
+    ///
+    /// `Some(&Assignment)` if a range with `start <= n < end` is outstanding.
+    pub fn in_flight_at(&self, n: u64) -> Option<&Assignment> {
+        self.assignments
+            .range(..=n)
+            .next_back()
+            .map(|(_, assignment)| assignment)
+            .filter(|assignment| n < assignment.end)
+    }
+
+    /// Returns the addresses of the clients holding an assignment.
+    pub fn holders(&self) -> HashSet<SocketAddr> {
+        self.assignments
+            .values()
+            .map(|assignment| assignment.assigned_to)
+            .collect()
+    }
+
+    /// Lists the outstanding assignments in the wire format.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec<OutstandingRange>` ordered by range start, with lease expirations
+    /// expressed as UNIX timestamps in milliseconds.
+    pub fn outstanding(&self) -> Vec<OutstandingRange> {
+        let now = Instant::now();
+        let system_now = SystemTime::now();
+
+        self.assignments
+            .values()
+            .map(|assignment| {
+                let lease_expires =
+                    system_now + assignment.lease_expires.saturating_duration_since(now);
+                OutstandingRange {
+                    start: assignment.start,
+                    end: assignment.end,
+                    assigned_to: assignment.assigned_to.to_string(),
+                    lease_expires: lease_expires
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |d| d.as_millis() as u64),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that completing a range removes only its assignment.
+    #[test]
+    fn test_assignment_tracker_complete() {
+        let client: SocketAddr = "127.0.0.1:4000".parse().unwrap();
+        let mut tracker = AssignmentTracker::default();
+        tracker.assign(2, 1_002, client);
+        tracker.assign(1_002, 2_002, client);
+
+        tracker.complete(1_002);
+
+        let outstanding = tracker.outstanding();
+        assert_eq!(outstanding.len(), 1);
+        assert_eq!(outstanding[0].start, 1_002);
+        assert_eq!(outstanding[0].end, 2_002);
+    }
+}
@@ -0,0 +1,186 @@
+use super::server_state::DEFAULT_STEP;
+use crate::utils::sieve::{primes_up_to, sieve_segment};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::time::{Duration, Instant};
+
+/// The time a client should spend sieving one range when none is configured, in milliseconds.
+pub const DEFAULT_TARGET_MS: u64 = 100;
+
+/// The largest step recommended when no bound is configured.
+pub const DEFAULT_MAX_STEP: u64 = 10_000_000;
+
+/// The fractions of `sample_end` timed by `autotune_step`, as divisors.
+const SAMPLE_DIVISORS: [u64; 3] = [64, 16, 4];
+
+/// Times the sieve on ranges of increasing size ending at `sample_end`.
+///
+/// # Arguments
+///
+/// * `sample_end` - The upper limit of the sampled ranges.
+///
+/// # Returns
+///
+/// The size of each sampled range and how long sieving it took.
+fn measure_samples(sample_end: u64) -> Vec<(u64, Duration)> {
+    let base_primes = primes_up_to((sample_end as f64).sqrt() as u64 + 1);
+    SAMPLE_DIVISORS
+        .iter()
+        .map(|&divisor| {
+            let size = (sample_end / divisor).max(1);
+            let start = sample_end.saturating_sub(size).max(2);
+            let began = Instant::now();
+            sieve_segment(start, sample_end, base_primes.clone());
+            (sample_end - start, began.elapsed())
+        })
+        .collect()
+}
+
+/// Recommends a step from timed samples.
+///
+/// The throughput of all samples together is scaled to the target duration, then
+/// rounded down to a power of two so that small timing jitter gives the same answer.
+///
+/// # Arguments
+///
+/// * `samples` - The size of each sampled range and how long sieving it took.
+/// * `target` - How long sieving one range should take.
+/// * `min_step` - The smallest step to recommend.
+/// * `max_step` - The largest step to recommend.
+///
+/// # Returns
+///
+/// A step in `[min_step, max_step]`.
+pub fn recommend_step(
+    samples: &[(u64, Duration)],
+    target: Duration,
+    min_step: u64,
+    max_step: u64,
+) -> u64 {
+    let numbers: u64 = samples.iter().map(|&(size, _)| size).sum();
+    let elapsed: Duration = samples.iter().map(|&(_, elapsed)| elapsed).sum();
+    let per_second = numbers as f64 / elapsed.as_secs_f64().max(1e-9);
+    let step = (per_second * target.as_secs_f64()) as u64;
+    let step = match step {
+        0 => 0,
+        step => 1 << step.ilog2(),
+    };
+    step.clamp(min_step, max_step)
+}
+
+/// Finds a good `step` for this host by timing the sieve locally.
+///
+/// A few ranges of increasing size below `sample_end` are sieved and timed, and the
+/// step is sized so that sieving one range takes about `target_ms`. The result can
+/// be passed to `start_server` as `assignment_unit`.
+///
+/// # Arguments
+///
+/// * `sample_end` - The upper limit of the sampled ranges; use a value close to the
+///   `end` of the planned run, since sieving gets slower as numbers grow.
+/// * `target_ms` - (Optional) How long a client should spend on one range, in
+///   milliseconds. Defaults to `100`.
+/// * `min_step` - (Optional) The smallest step to recommend. Defaults to `1000`.
+/// * `max_step` - (Optional) The largest step to recommend. Defaults to `10_000_000`.
+///
+/// # Returns
+///
+/// The recommended step, a power of two clamped to `[min_step, max_step]`.
+///
+/// # Errors
+///
+/// Returns a `PyValueError` if `sample_end` is below `1000`, if `target_ms` or
+/// `min_step` is `0`, or if `min_step` is above `max_step`.
+///
+/// # Example (Python)
+///
+/// ```python
+/// import primesocket_core
+/// step = primesocket_core.autotune_step(10**9)
+/// primesocket_core.start_server(port=5000, end=10**9, assignment_unit=step)
+/// ```
+#[pyfunction(signature = (sample_end, target_ms=None, min_step=None, max_step=None))]
+pub fn autotune_step(
+    py: Python<'_>,
+    sample_end: u64,
+    target_ms: Option<u64>,
+    min_step: Option<u64>,
+    max_step: Option<u64>,
+) -> PyResult<u64> {
+    let target_ms = target_ms.unwrap_or(DEFAULT_TARGET_MS);
+    let min_step = min_step.unwrap_or(DEFAULT_STEP);
+    let max_step = max_step.unwrap_or(DEFAULT_MAX_STEP.max(min_step));
+    if sample_end < 1_000 {
+        return Err(PyErr::new::<PyValueError, _>(format!(
+            "Invalid sample end {} (expected at least 1000)",
+            sample_end
+        )));
+    }
+    if target_ms == 0 || min_step == 0 || min_step > max_step {
+        return Err(PyErr::new::<PyValueError, _>(format!(
+            "Invalid bounds (target {} ms, step {}..={}); expected positive values with min <= max",
+            target_ms, min_step, max_step
+        )));
+    }
+
+    let samples = py.allow_threads(|| measure_samples(sample_end));
+    Ok(recommend_step(
+        &samples,
+        Duration::from_millis(target_ms),
+        min_step,
+        max_step,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that the recommendation scales the measured throughput to the target,
+    /// is the same for the same workload and stays within the bounds.
+    #[test]
+    fn test_recommend_step() {
+        let samples = [
+            (15_625, Duration::from_micros(200)),
+            (62_500, Duration::from_micros(800)),
+            (250_000, Duration::from_micros(3_200)),
+        ];
+        // 78.125M numbers per second, 7.8125M in 100 ms.
+        let target = Duration::from_millis(100);
+        assert_eq!(
+            recommend_step(&samples, target, 1_000, 100_000_000),
+            1 << 22
+        );
+        assert_eq!(
+            recommend_step(&samples, target, 1_000, 100_000_000),
+            recommend_step(&samples, target, 1_000, 100_000_000)
+        );
+        assert_eq!(
+            recommend_step(&samples, target, 1_000, 1_000_000),
+            1_000_000
+        );
+        assert_eq!(
+            recommend_step(&samples, target, 10_000_000, 20_000_000),
+            10_000_000
+        );
+        assert_eq!(
+            recommend_step(&[(1, Duration::from_secs(1))], target, 1_000, 2_000),
+            1_000
+        );
+    }
+
+    /// Tests that `autotune_step` returns a step within the configured bounds and
+    /// rejects invalid ones.
+    #[test]
+    fn test_autotune_step_within_bounds() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let step = autotune_step(py, 1_000_000, Some(10), Some(4_096), Some(65_536)).unwrap();
+            assert!((4_096..=65_536).contains(&step));
+            assert!(step.is_power_of_two());
+
+            assert!(autotune_step(py, 1_000_000, Some(10), Some(100), Some(10)).is_err());
+            assert!(autotune_step(py, 999, None, None, None).is_err());
+        });
+    }
+}
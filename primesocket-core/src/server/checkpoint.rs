@@ -0,0 +1,360 @@
+use super::assignment::Assignment;
+use super::client_stats::ClientStats;
+use super::server_state::ServerState;
+use crate::utils::varint::{read_varints, write_varints};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The name of the manifest file of a chunked checkpoint.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// An outstanding assignment as stored in a checkpoint.
+///
+/// # Fields
+///
+/// * `start` - The first number of the range.
+/// * `end` - The last number of the range.
+/// * `assigned_to` - The address of the client working on the range.
+/// * `lease_remaining_ms` - How long the lease still had to run, in milliseconds.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CheckpointAssignment {
+    pub start: u64,
+    pub end: u64,
+    pub assigned_to: SocketAddr,
+    pub lease_remaining_ms: u64,
+}
+
+/// The on-disk representation of a `ServerState`.
+///
+/// Besides the computation itself, it keeps the outstanding assignments and the
+/// per-client counters so that fairness guarantees survive a restart.
+///
+/// # Fields
+///
+/// * `start` - The lower limit of the number range.
+/// * `end` - The upper limit of the number range.
+/// * `step` - The size of the ranges handed out.
+/// * `last_checked` - The highest number saved so far.
+/// * `assigned_up_to` - The highest number handed out so far.
+/// * `primes` - The primes identified so far.
+/// * `completed` - The completed ranges, as `(start, end)` pairs.
+/// * `assignments` - The outstanding assignments.
+/// * `client_stats` - The work saved by each client address.
+/// * `ranges` - The disjoint ranges the computation is restricted to, if any.
+/// * `epoch` - The id of the run, kept so saves of resumed assignments are still accepted.
+/// * `reclaimed` - The ranges whose lease expired, waiting to be handed out again.
+/// * `sum_only` - Whether the run keeps the sum of the primes instead of the list.
+/// * `prime_sum` - The sum of the primes saved so far, in sum-only mode.
+/// * `prime_count` - The number of primes saved so far, in sum-only mode.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    pub start: u64,
+    pub end: u64,
+    pub step: u64,
+    pub last_checked: u64,
+    pub assigned_up_to: u64,
+    pub primes: Vec<u64>,
+    pub completed: Vec<(u64, u64)>,
+    pub assignments: Vec<CheckpointAssignment>,
+    pub client_stats: Vec<(SocketAddr, ClientStats)>,
+    #[serde(default)]
+    pub ranges: Vec<(u64, u64)>,
+    #[serde(default)]
+    pub epoch: Option<u64>,
+    #[serde(default)]
+    pub reclaimed: Vec<(u64, u64)>,
+    #[serde(default)]
+    pub sum_only: bool,
+    #[serde(default)]
+    pub prime_sum: u128,
+    #[serde(default)]
+    pub prime_count: u64,
+}
+
+impl Checkpoint {
+    /// Captures the persistent part of a `ServerState`.
+    ///
+    /// # Arguments
+    ///
+    /// * `server_state` - The state to capture.
+    ///
+    /// # Returns
+    ///
+    /// A `Checkpoint` holding the state's progress, assignments and client counters.
+    pub fn from_state(server_state: &ServerState) -> Checkpoint {
+        let now = Instant::now();
+        Checkpoint {
+            start: server_state.start,
+            end: server_state.end,
+            step: server_state.step,
+            last_checked: server_state.last_checked,
+            assigned_up_to: server_state.assigned_up_to,
+            primes: server_state.primes.clone(),
+            completed: server_state.completed.iter().collect(),
+            assignments: server_state
+                .assignments
+                .assignments
+                .values()
+                .map(|assignment| CheckpointAssignment {
+                    start: assignment.start,
+                    end: assignment.end,
+                    assigned_to: assignment.assigned_to,
+                    lease_remaining_ms: assignment
+                        .lease_expires
+                        .saturating_duration_since(now)
+                        .as_millis() as u64,
+                })
+                .collect(),
+            client_stats: server_state
+                .client_stats
+                .iter()
+                .map(|(client, stats)| (*client, stats.clone()))
+                .collect(),
+            ranges: server_state.ranges.clone(),
+            epoch: Some(server_state.epoch),
+            reclaimed: server_state
+                .assignments
+                .reclaimed
+                .iter()
+                .map(|(&start, &end)| (start, end))
+                .collect(),
+            sum_only: server_state.sum_only,
+            prime_sum: server_state.prime_sum,
+            prime_count: server_state.prime_count,
+        }
+    }
+
+    /// Rebuilds a `ServerState` from the checkpoint.
+    ///
+    /// Leases resume with the time they had left when the checkpoint was taken.
+    ///
+    /// # Returns
+    ///
+    /// A `ServerState` with default settings and the checkpointed progress.
+    pub fn into_state(self) -> ServerState {
+        let now = Instant::now();
+        let mut server_state = ServerState::new(self.start, self.end, self.step);
+        server_state.restrict_to_ranges(&self.ranges);
+        if let Some(epoch) = self.epoch {
+            server_state.epoch = epoch;
+        }
+        server_state.last_checked = self.last_checked;
+        server_state.assigned_up_to = self.assigned_up_to;
+        server_state.primes = self.primes;
+        for (start, end) in self.completed {
+            server_state.completed.insert(start, end);
+        }
+        for assignment in self.assignments {
+            server_state.assignments.assignments.insert(
+                assignment.start,
+                Assignment {
+                    start: assignment.start,
+                    end: assignment.end,
+                    assigned_to: assignment.assigned_to,
+                    lease_expires: now + Duration::from_millis(assignment.lease_remaining_ms),
+                },
+            );
+        }
+        server_state.assignments.reclaimed = self.reclaimed.into_iter().collect();
+        server_state.sum_only = self.sum_only;
+        server_state.prime_sum = self.prime_sum;
+        server_state.prime_count = self.prime_count;
+        server_state.client_stats = self.client_stats.into_iter().collect();
+        server_state
+    }
+}
+
+/// A file of a chunked checkpoint, holding the primes of a value range.
+///
+/// # Fields
+///
+/// * `start` - The first value the segment covers.
+/// * `end` - The last value the segment covers.
+/// * `file` - The name of the segment file, relative to the checkpoint directory.
+/// * `count` - The number of primes in the segment.
+/// * `checksum` - The FNV-1a hash of the segment file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CheckpointSegment {
+    pub start: u64,
+    pub end: u64,
+    pub file: String,
+    pub count: u64,
+    pub checksum: u64,
+}
+
+/// The manifest of a chunked checkpoint: the checkpoint without its primes, and
+/// the segments holding them.
+///
+/// # Fields
+///
+/// * `checkpoint` - The checkpoint, with `primes` left empty.
+/// * `segments` - The segments holding the primes, ordered by range.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CheckpointManifest {
+    pub checkpoint: Checkpoint,
+    pub segments: Vec<CheckpointSegment>,
+}
+
+/// The manifest as stored on disk, along with the checksum of its contents.
+#[derive(Serialize, Deserialize)]
+struct ManifestFile {
+    checksum: u64,
+    manifest: CheckpointManifest,
+}
+
+impl Checkpoint {
+    /// Writes the checkpoint as a directory of segment files plus a manifest.
+    ///
+    /// The primes are split by value into segments of `segment_size` numbers, each
+    /// stored as varint-encoded gaps in a file named after its range and checksum.
+    /// Writes are incremental: segments already on disk with the same contents are
+    /// kept. The manifest is written last, and stale segments removed after it, so
+    /// a crash midway leaves the previous checkpoint loadable.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The checkpoint directory, created if missing.
+    /// * `segment_size` - The range of values each segment covers.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` if a file could not be written.
+    pub fn write_chunked(mut self, dir: &Path, segment_size: u64) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        let segment_size = segment_size.max(1);
+
+        let mut groups: BTreeMap<u64, Vec<u64>> = BTreeMap::new();
+        for prime in std::mem::take(&mut self.primes) {
+            groups.entry(prime / segment_size).or_default().push(prime);
+        }
+
+        let mut segments = Vec::with_capacity(groups.len());
+        for (key, primes) in groups {
+            let bytes = write_varints(&primes);
+            let checksum = fnv1a(&bytes);
+            let start = key * segment_size;
+            let end = start.saturating_add(segment_size - 1);
+            let file = format!("segment_{}_{}_{:016x}.bin", start, end, checksum);
+            let path = dir.join(&file);
+            if !path.exists() {
+                write_atomically(&path, &bytes)?;
+            }
+            segments.push(CheckpointSegment {
+                start,
+                end,
+                file,
+                count: primes.len() as u64,
+                checksum,
+            });
+        }
+
+        let manifest = CheckpointManifest {
+            checkpoint: self,
+            segments,
+        };
+        let checksum = fnv1a(&serde_json::to_vec(&manifest)?);
+        let kept: HashSet<String> = manifest.segments.iter().map(|s| s.file.clone()).collect();
+        let json = serde_json::to_vec(&ManifestFile { checksum, manifest })?;
+        write_atomically(&dir.join(MANIFEST_FILE), &json)?;
+
+        for entry in fs::read_dir(dir)? {
+            let name = entry?.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with("segment_") && !kept.contains(name.as_ref() as &str) {
+                fs::remove_file(dir.join(name.as_ref()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a checkpoint written by `write_chunked`, loading its segments in parallel.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The checkpoint directory.
+    ///
+    /// # Returns
+    ///
+    /// The `Checkpoint`, with the primes of every segment.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` if a file can't be read, or one of kind `InvalidData` if
+    /// the manifest or a segment doesn't match its checksum.
+    pub fn read_chunked(dir: &Path) -> io::Result<Checkpoint> {
+        let file: ManifestFile = serde_json::from_slice(&fs::read(dir.join(MANIFEST_FILE))?)?;
+        if fnv1a(&serde_json::to_vec(&file.manifest)?) != file.checksum {
+            return Err(invalid_data("checkpoint manifest checksum mismatch"));
+        }
+
+        let segments = &file.manifest.segments;
+        let loaded: Vec<io::Result<Vec<u64>>> = thread::scope(|scope| {
+            let handles: Vec<_> = segments
+                .iter()
+                .map(|segment| scope.spawn(move || read_segment(dir, segment)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|_| Err(invalid_data("segment reader panicked")))
+                })
+                .collect()
+        });
+
+        let mut checkpoint = file.manifest.checkpoint;
+        for primes in loaded {
+            checkpoint.primes.extend(primes?);
+        }
+        Ok(checkpoint)
+    }
+}
+
+/// Reads and checks the primes of one segment of a chunked checkpoint.
+fn read_segment(dir: &Path, segment: &CheckpointSegment) -> io::Result<Vec<u64>> {
+    let bytes = fs::read(dir.join(&segment.file))?;
+    if fnv1a(&bytes) != segment.checksum {
+        return Err(invalid_data(&format!(
+            "checksum mismatch in {}",
+            segment.file
+        )));
+    }
+    let primes = read_varints(&bytes).ok_or_else(|| invalid_data("malformed segment"))?;
+    if primes.len() as u64 != segment.count
+        || primes
+            .iter()
+            .any(|p| !(segment.start..=segment.end).contains(p))
+    {
+        return Err(invalid_data(&format!(
+            "unexpected contents in {}",
+            segment.file
+        )));
+    }
+    Ok(primes)
+}
+
+/// Writes `bytes` to `path` through a staging file, so readers never see a partial file.
+fn write_atomically(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let staging = path.with_extension(format!("{}.tmp", std::process::id()));
+    fs::write(&staging, bytes)?;
+    fs::rename(staging, path)
+}
+
+/// Builds an `InvalidData` error.
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+/// Hashes `bytes` with 64-bit FNV-1a.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
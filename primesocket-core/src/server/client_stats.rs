@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+/// Tracks the work contributed by a single client.
+///
+/// # Fields
+///
+/// * `ranges` - The number of ranges the client saved.
+/// * `primes` - The number of primes the client submitted.
+/// * `expired_leases` - The number of assignments the client let expire.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ClientStats {
+    pub ranges: u32,
+    pub primes: u64,
+    #[serde(default)]
+    pub expired_leases: u32,
+}
+
+impl ClientStats {
+    /// Records a range saved by the client.
+    ///
+    /// # Arguments
+    ///
+    /// * `primes` - The number of primes submitted with the range.
+    pub fn record_save(&mut self, primes: usize) {
+        self.ranges += 1;
+        self.primes += primes as u64;
+    }
+}
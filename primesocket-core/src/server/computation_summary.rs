@@ -0,0 +1,133 @@
+use super::server_state::ServerState;
+use pyo3::prelude::*;
+use std::fmt;
+use std::time::Duration;
+
+/// Describes how a finished computation went, logged when the server stops.
+///
+/// # Fields
+///
+/// * `primes` - The number of primes found.
+/// * `start` - The start of the computed range.
+/// * `end` - The end of the computed range.
+/// * `duration` - The wall-clock duration of the run, in seconds.
+/// * `clients` - The number of distinct client addresses that saved a range.
+/// * `segments` - The number of saves accepted, including sub-blocks of a range.
+///
+/// # Example (Python)
+///
+/// ```python
+/// import primesocket_core
+/// summary = primesocket_core.start_server(8080, 1000).summary
+/// print(summary.primes, summary.clients, summary.segments)
+/// ```
+#[pyclass(get_all, frozen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ComputationSummary {
+    pub primes: u64,
+    pub start: u64,
+    pub end: u64,
+    pub duration: f64,
+    pub clients: usize,
+    pub segments: u64,
+}
+
+impl ComputationSummary {
+    /// Builds a `ComputationSummary` from the final server state.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The server state at the end of the run.
+    /// * `duration` - How long the run took.
+    ///
+    /// # Returns
+    ///
+    /// A `ComputationSummary` of the work recorded in `state`.
+    pub fn from_state(state: &ServerState, duration: Duration) -> ComputationSummary {
+        ComputationSummary {
+            primes: match state.sum_only {
+                true => state.prime_count,
+                false => state.primes.len() as u64,
+            },
+            start: state.start,
+            end: state.end,
+            duration: duration.as_secs_f64(),
+            clients: state.contributing_clients(),
+            segments: state.segments_saved,
+        }
+    }
+}
+
+impl fmt::Display for ComputationSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ComputationSummary(primes={}, range=[{}, {}], duration={:.3}, clients={}, segments={})",
+            self.primes, self.start, self.end, self.duration, self.clients, self.segments
+        )
+    }
+}
+
+#[pymethods]
+impl ComputationSummary {
+    fn __repr__(&self) -> String {
+        self.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::response_handler::handler;
+    use crate::utils::json::{Request, Task};
+    use std::net::SocketAddr;
+
+    /// Tests that the summary of a completed computation counts its primes, segments
+    /// and the clients that saved them.
+    #[test]
+    fn test_summary_after_completion() {
+        let mut server_state = ServerState::new(2, 100, 50);
+        let clients: [SocketAddr; 2] = [
+            "127.0.0.1:4000".parse().unwrap(),
+            "127.0.0.1:4001".parse().unwrap(),
+        ];
+        let primes = crate::utils::sieve::primes_up_to(100);
+        for client in clients {
+            let range = handler(
+                &mut server_state,
+                Request {
+                    task: Task::Start,
+                    ..Default::default()
+                },
+                client,
+            );
+            let (start, end) = (range.start.unwrap(), range.end.unwrap());
+            handler(
+                &mut server_state,
+                Request {
+                    task: Task::Save,
+                    start: Some(start),
+                    end: Some(end),
+                    primes: Some(
+                        primes
+                            .iter()
+                            .copied()
+                            .filter(|p| (start..=end).contains(p))
+                            .collect(),
+                    ),
+                    ..Default::default()
+                },
+                client,
+            );
+        }
+        assert_eq!(server_state.status, "completed");
+
+        let summary = ComputationSummary::from_state(&server_state, Duration::from_millis(1_500));
+
+        assert_eq!(summary.primes, 25);
+        assert_eq!((summary.start, summary.end), (2, 100));
+        assert_eq!(summary.clients, 2);
+        assert_eq!(summary.segments, 2);
+        assert_eq!(summary.duration, 1.5);
+    }
+}
@@ -0,0 +1,90 @@
+use super::client_stats::ClientStats;
+use super::server_state::ServerState;
+use crate::utils::json::OutstandingRange;
+use serde::Serialize;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How large a debug file may grow before it is rotated to `<path>.1`.
+pub const DEBUG_STATE_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+/// A snapshot of the assignments and clients of a run, for post-mortem debugging.
+///
+/// # Fields
+///
+/// * `timestamp` - When the snapshot was taken, as a UNIX timestamp in milliseconds.
+/// * `status` - The status of the computation.
+/// * `last_checked` - The highest number saved so far.
+/// * `completed_frontier` - The largest value below which every range is completed.
+/// * `outstanding` - The ranges assigned but not completed yet, with their leases.
+/// * `clients` - The work saved by each client address, ordered by address.
+#[derive(Serialize, Debug)]
+pub struct DebugSnapshot {
+    pub timestamp: u64,
+    pub status: String,
+    pub last_checked: u64,
+    pub completed_frontier: u64,
+    pub outstanding: Vec<OutstandingRange>,
+    pub clients: Vec<(String, ClientStats)>,
+}
+
+impl DebugSnapshot {
+    /// Captures the assignments and clients of a `ServerState`.
+    ///
+    /// # Arguments
+    ///
+    /// * `server_state` - The state to capture.
+    ///
+    /// # Returns
+    ///
+    /// A `DebugSnapshot` of the state as it is now.
+    pub fn from_state(server_state: &ServerState) -> DebugSnapshot {
+        let mut clients: Vec<(String, ClientStats)> = server_state
+            .client_stats
+            .iter()
+            .map(|(client, stats)| (client.to_string(), stats.clone()))
+            .collect();
+        clients.sort_by(|a, b| a.0.cmp(&b.0));
+
+        DebugSnapshot {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis() as u64),
+            status: server_state.status.clone(),
+            last_checked: server_state.last_checked,
+            completed_frontier: server_state.completed_frontier(),
+            outstanding: server_state.assignments.outstanding(),
+            clients,
+        }
+    }
+
+    /// Appends the snapshot as a JSON line to `path`.
+    ///
+    /// Once the file exceeds `DEBUG_STATE_MAX_BYTES`, it is first moved to
+    /// `<path>.1`, replacing the previous rotation.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The debug file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` if the snapshot can't be serialized or written.
+    pub fn append_to(&self, path: &Path) -> io::Result<()> {
+        if fs::metadata(path).is_ok_and(|metadata| metadata.len() > DEBUG_STATE_MAX_BYTES) {
+            let mut rotated = path.as_os_str().to_owned();
+            rotated.push(".1");
+            fs::rename(path, rotated)?;
+        }
+
+        let mut line = serde_json::to_string(self)?;
+        line.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?
+            .write_all(line.as_bytes())
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::BTreeMap;
+
+/// A set of inclusive `[start, end]` ranges, kept merged and ordered.
+///
+/// Overlapping or touching ranges are merged on insertion, so the set always
+/// holds the fewest disjoint ranges covering the inserted numbers.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IntervalSet {
+    ranges: BTreeMap<u64, u64>,
+}
+
+impl IntervalSet {
+    /// Creates an empty `IntervalSet`.
+    pub fn new() -> IntervalSet {
+        IntervalSet::default()
+    }
+
+    /// Adds `[start, end]` to the set, merging it with its neighbours.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The first number of the range.
+    /// * `end` - The last number of the range.
+    pub fn insert(&mut self, start: u64, end: u64) {
+        if start > end {
+            return;
+        }
+
+        let mut start = start;
+        let mut end = end;
+
+        // Absorb a range starting before `start` that overlaps or touches it.
+        if let Some((&prev_start, &prev_end)) = self.ranges.range(..=start).next_back() {
+            if prev_end.saturating_add(1) >= start {
+                start = prev_start;
+                end = end.max(prev_end);
+            }
+        }
+
+        // Absorb every range starting inside (or right after) the new one.
+        let following: Vec<(u64, u64)> = self
+            .ranges
+            .range(start..=end.saturating_add(1))
+            .map(|(&s, &e)| (s, e))
+            .collect();
+        for (s, e) in following {
+            self.ranges.remove(&s);
+            end = end.max(e);
+        }
+
+        self.ranges.insert(start, end);
+    }
+
+    /// Returns the end of the contiguous coverage starting at `origin`.
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - Where the coverage must start.
+    ///
+    /// # Returns
+    ///
+    /// The largest `x` such that every number in `[origin, x]` is in the set, or
+    /// `origin` itself if `origin` is not covered yet.
+    pub fn contiguous_end(&self, origin: u64) -> u64 {
+        match self.ranges.range(..=origin).next_back() {
+            Some((_, &end)) if end >= origin => end,
+            _ => origin,
+        }
+    }
+
+    /// Returns the start of the contiguous coverage ending at `origin`.
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - Where the coverage must end.
+    ///
+    /// # Returns
+    ///
+    /// The smallest `x` such that every number in `[x, origin]` is in the set, or
+    /// `origin` itself if `origin` is not covered yet.
+    pub fn contiguous_start(&self, origin: u64) -> u64 {
+        match self.ranges.range(..=origin).next_back() {
+            Some((&start, &end)) if end >= origin => start,
+            _ => origin,
+        }
+    }
+
+    /// Checks whether every number in `[start, end]` is in the set.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The first number of the range.
+    /// * `end` - The last number of the range.
+    pub fn covers(&self, start: u64, end: u64) -> bool {
+        self.ranges
+            .range(..=start)
+            .next_back()
+            .is_some_and(|(_, &covered_end)| covered_end >= end)
+    }
+
+    /// Checks whether any number in `[start, end]` is in the set.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The first number of the range.
+    /// * `end` - The last number of the range.
+    pub fn intersects(&self, start: u64, end: u64) -> bool {
+        start <= end
+            && self
+                .ranges
+                .range(..=end)
+                .next_back()
+                .is_some_and(|(_, &covered_end)| covered_end >= start)
+    }
+
+    /// Iterates over the disjoint ranges in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.ranges.iter().map(|(&start, &end)| (start, end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that overlapping, touching and nested ranges are merged.
+    #[test]
+    fn test_interval_set_merges() {
+        let mut set = IntervalSet::new();
+        set.insert(10, 20);
+        set.insert(30, 40);
+        set.insert(21, 25);
+        set.insert(35, 38);
+        set.insert(24, 31);
+
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![(10, 40)]);
+    }
+
+    /// Tests that the contiguous coverage stops at the first gap.
+    #[test]
+    fn test_interval_set_contiguous_end() {
+        let mut set = IntervalSet::new();
+        assert_eq!(set.contiguous_end(2), 2);
+
+        set.insert(2, 1_002);
+        set.insert(2_002, 3_002);
+        assert_eq!(set.contiguous_end(2), 1_002);
+
+        set.insert(1_002, 2_002);
+        assert_eq!(set.contiguous_end(2), 3_002);
+    }
+
+    /// Tests that a range is covered only when no number of it is missing.
+    #[test]
+    fn test_interval_set_covers() {
+        let mut set = IntervalSet::new();
+        assert!(!set.covers(5, 5));
+
+        set.insert(10, 20);
+        set.insert(30, 40);
+        assert!(set.covers(10, 20));
+        assert!(set.covers(35, 35));
+        assert!(!set.covers(15, 35));
+        assert!(!set.covers(25, 25));
+    }
+
+    /// Tests that the contiguous coverage below an origin stops at the first gap.
+    #[test]
+    fn test_interval_set_contiguous_start() {
+        let mut set = IntervalSet::new();
+        assert_eq!(set.contiguous_start(3_002), 3_002);
+
+        set.insert(2_002, 3_002);
+        set.insert(2, 1_002);
+        assert_eq!(set.contiguous_start(3_002), 2_002);
+
+        set.insert(1_002, 2_002);
+        assert_eq!(set.contiguous_start(3_002), 2);
+    }
+
+    /// Tests that a range intersects the set as soon as one of its numbers is covered.
+    #[test]
+    fn test_interval_set_intersects() {
+        let mut set = IntervalSet::new();
+        set.insert(10, 20);
+
+        assert!(set.intersects(20, 30));
+        assert!(set.intersects(0, 10));
+        assert!(set.intersects(12, 14));
+        assert!(!set.intersects(21, 30));
+        assert!(!set.intersects(0, 9));
+        assert!(!set.intersects(15, 12));
+    }
+}
@@ -0,0 +1,196 @@
+use super::response_handler::handler;
+use super::server_state::{ServerState, DEFAULT_STEP};
+use crate::client::request_handler::compute_save;
+use crate::client::worker::{SieveWorker, Worker};
+use crate::utils::json::{Request, Task};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Mutex;
+use std::thread;
+
+/// Runs the whole distributed computation in-process, without sockets.
+///
+/// A server state and `workers` client threads exchange requests and responses
+/// through direct calls to the server handler instead of UDP, so the behavior
+/// matches a networked run without any network setup.
+///
+/// # Arguments
+///
+/// * `end` - The upper limit of the computation (inclusive).
+/// * `workers` - (Optional) The number of client workers. Defaults to `1`.
+/// * `step` - (Optional) The size of the ranges handed to workers. Defaults to `1000`.
+///
+/// # Returns
+///
+/// The primes in `[2, end]`, in ascending order.
+///
+/// # Errors
+///
+/// Returns a `PyValueError` if `workers` or `step` is `0`.
+///
+/// # Example (Python)
+///
+/// ```python
+/// import primesocket_core
+/// primes = primesocket_core.run_local(1000, workers=2)
+/// ```
+#[pyfunction(signature = (end, workers=None, step=None))]
+pub fn run_local(
+    py: Python<'_>,
+    end: u64,
+    workers: Option<usize>,
+    step: Option<u64>,
+) -> PyResult<Vec<u64>> {
+    let workers = workers.unwrap_or(1);
+    if workers == 0 || step == Some(0) {
+        return Err(PyErr::new::<PyValueError, _>(
+            "Parameters 'workers' and 'step' must be positive",
+        ));
+    }
+
+    let server_state = ServerState::new(2, end, step.unwrap_or(DEFAULT_STEP));
+
+    let server_state = py.allow_threads(|| run_pipeline(server_state, workers));
+    Ok(server_state.primes)
+}
+
+/// Computes the primes in `[2, end]` in-process with a single client, without sockets.
+///
+/// A `ServerState` hands out ranges of `step` numbers through the same `handler` as a
+/// networked server, and an in-process loop stands in for the client, so this path
+/// exercises the distributed logic without any network setup. Only the tests use it;
+/// Python callers have `run_local`.
+///
+/// # Arguments
+///
+/// * `end` - The upper limit of the computation (inclusive).
+/// * `step` - The size of the ranges handed to the client.
+///
+/// # Returns
+///
+/// The primes in `[2, end]`, in ascending order and without duplicates.
+///
+/// # Errors
+///
+/// Returns a `PyValueError` if `step` is `0`, as `run_local` does.
+///
+/// # Example
+///
+/// ```ignore
+/// use crate::server::local::compute_primes_local;
+/// let primes = compute_primes_local(20, 5)?;
+/// assert_eq!(primes, vec![2, 3, 5, 7, 11, 13, 17, 19]);
+/// ```
+#[cfg(test)]
+pub(crate) fn compute_primes_local(end: u64, step: u32) -> PyResult<Vec<u64>> {
+    if step == 0 {
+        return Err(PyErr::new::<PyValueError, _>(
+            "Parameter 'step' must be positive",
+        ));
+    }
+    let server_state = ServerState::new(2, end, u64::from(step));
+    let mut server_state = run_pipeline(server_state, 1);
+    server_state.finalize_primes();
+    Ok(server_state.primes)
+}
+
+/// Serves `server_state` to `workers` in-process client threads until it completes.
+///
+/// # Arguments
+///
+/// * `server_state` - The computation to run.
+/// * `workers` - The number of client threads.
+///
+/// # Returns
+///
+/// The final `ServerState`.
+pub(crate) fn run_pipeline(server_state: ServerState, workers: usize) -> ServerState {
+    let server_state = Mutex::new(server_state);
+
+    thread::scope(|scope| {
+        for id in 0..workers {
+            let server_state = &server_state;
+            // Each worker gets its own address, so per-client features apply.
+            let client = SocketAddr::from((Ipv4Addr::LOCALHOST, id as u16 + 1));
+            scope.spawn(move || run_worker(server_state, client, &SieveWorker::default()));
+        }
+    });
+
+    server_state.into_inner().unwrap()
+}
+
+/// Runs one in-process client against the shared server state.
+///
+/// # Arguments
+///
+/// * `server_state` - The shared server state.
+/// * `client` - The address identifying the worker.
+/// * `worker` - The `Worker` computing the results of assigned ranges.
+fn run_worker(server_state: &Mutex<ServerState>, client: SocketAddr, worker: &dyn Worker) {
+    loop {
+        let start = Request {
+            task: Task::Start,
+            ..Default::default()
+        };
+        let range = handler(&mut server_state.lock().unwrap(), start, client);
+        if range.task != Task::Range {
+            return;
+        }
+
+        let save = compute_save(
+            worker,
+            range.start.unwrap_or_default(),
+            range.end.unwrap_or_default(),
+            range.primes.unwrap_or_default(),
+            range.residue,
+        );
+        let ack = handler(&mut server_state.lock().unwrap(), save, client);
+        if ack.task == Task::Done {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::types::PyDict;
+
+    /// Tests that a local run with two workers returns the 168 primes below 1000.
+    #[test]
+    fn test_run_local_two_workers() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let run_local = wrap_pyfunction!(run_local, py).unwrap();
+            let kwargs = PyDict::new(py);
+            kwargs.set_item("workers", 2).unwrap();
+            kwargs.set_item("step", 100).unwrap();
+
+            let primes: Vec<u64> = run_local
+                .call((1000,), Some(&kwargs))
+                .unwrap()
+                .extract()
+                .unwrap();
+
+            assert_eq!(primes.len(), 168);
+            assert_eq!(primes, crate::utils::sieve::primes_up_to(1000));
+        });
+    }
+
+    /// Tests that a local computation returns the known primes, whatever the step, and
+    /// that a step of `0` is rejected.
+    #[test]
+    fn test_compute_primes_local() {
+        let expected = crate::utils::sieve::primes_up_to(10_000);
+        for step in [1, 7, 1_000, 20_000] {
+            assert_eq!(
+                compute_primes_local(10_000, step).unwrap(),
+                expected,
+                "step {}",
+                step
+            );
+        }
+        assert!(compute_primes_local(10_000, 0).is_err());
+    }
+}
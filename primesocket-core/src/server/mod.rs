@@ -1,4 +1,19 @@
+mod assignment;
+mod checkpoint;
+mod client_stats;
+mod debug_state;
+mod intervals;
+mod progress;
+mod rate_limit;
 mod response_handler;
+mod server_config;
+mod server_options;
 mod server_state;
 
+pub mod autotune;
+pub mod computation_summary;
+pub mod local;
+pub mod run_result;
+#[allow(clippy::module_inception)]
 pub mod server;
+pub mod server_handle;
@@ -0,0 +1,190 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// How many progress events may wait for the callback before the oldest is dropped.
+pub const PROGRESS_QUEUE_CAPACITY: usize = 16;
+
+/// Reports the progress of the computation.
+///
+/// # Fields
+///
+/// * `last_checked` - The highest number saved so far.
+/// * `end` - The upper limit of the computation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProgressEvent {
+    pub last_checked: u64,
+    pub end: u64,
+}
+
+/// A bounded queue that drops its oldest event when full.
+struct ProgressQueue {
+    events: Mutex<(VecDeque<ProgressEvent>, bool)>,
+    ready: Condvar,
+    capacity: usize,
+    dropped: AtomicU64,
+}
+
+impl ProgressQueue {
+    /// Adds an event without ever blocking on the consumer.
+    fn push(&self, event: ProgressEvent) {
+        let mut guard = self.events.lock().unwrap();
+        if guard.0.len() >= self.capacity {
+            guard.0.pop_front();
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        guard.0.push_back(event);
+        self.ready.notify_one();
+    }
+
+    /// Waits for the next event, returning `None` once closed and drained.
+    fn pop(&self) -> Option<ProgressEvent> {
+        let mut guard = self.events.lock().unwrap();
+        loop {
+            if let Some(event) = guard.0.pop_front() {
+                return Some(event);
+            }
+            if guard.1 {
+                return None;
+            }
+            guard = self.ready.wait(guard).unwrap();
+        }
+    }
+
+    /// Stops waiting for new events; pending ones are still delivered.
+    fn close(&self) {
+        self.events.lock().unwrap().1 = true;
+        self.ready.notify_all();
+    }
+}
+
+/// Delivers progress events to a callback running on a dedicated thread.
+///
+/// The network loop only pushes events into a bounded queue, so a slow callback
+/// (e.g. Python code waiting for the GIL) never stalls it. When the callback
+/// can't keep up, the oldest pending events are dropped: only the most recent
+/// progress matters.
+pub struct ProgressReporter {
+    queue: Arc<ProgressQueue>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ProgressReporter {
+    /// Starts the reporter thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - How many events may be pending before the oldest is dropped.
+    /// * `callback` - Called on the reporter thread for each delivered event.
+    ///
+    /// # Returns
+    ///
+    /// A `ProgressReporter` feeding `callback`.
+    pub fn spawn<F>(capacity: usize, mut callback: F) -> ProgressReporter
+    where
+        F: FnMut(ProgressEvent) + Send + 'static,
+    {
+        let queue = Arc::new(ProgressQueue {
+            events: Mutex::new((VecDeque::with_capacity(capacity), false)),
+            ready: Condvar::new(),
+            capacity: capacity.max(1),
+            dropped: AtomicU64::new(0),
+        });
+
+        let consumer = queue.clone();
+        let thread = thread::spawn(move || {
+            while let Some(event) = consumer.pop() {
+                callback(event);
+            }
+        });
+
+        ProgressReporter {
+            queue,
+            thread: Some(thread),
+        }
+    }
+
+    /// Returns a cloneable handle reporting to this reporter.
+    pub fn handle(&self) -> ProgressHandle {
+        ProgressHandle {
+            queue: self.queue.clone(),
+        }
+    }
+
+    /// Returns how many events were dropped because the callback fell behind.
+    pub fn dropped(&self) -> u64 {
+        self.queue.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Delivers the pending events, then stops the reporter thread.
+    pub fn finish(mut self) {
+        self.queue.close();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// A cloneable handle used by tasks to report progress to a `ProgressReporter`.
+#[derive(Clone)]
+pub struct ProgressHandle {
+    queue: Arc<ProgressQueue>,
+}
+
+impl ProgressHandle {
+    /// Queues an event for the callback. Never blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The progress to report.
+    pub fn report(&self, event: ProgressEvent) {
+        self.queue.push(event);
+    }
+}
+
+impl Drop for ProgressReporter {
+    fn drop(&mut self) {
+        self.queue.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    /// Tests that a slow callback neither blocks reporting nor receives stale events.
+    ///
+    /// A hundred events are reported to a callback taking 20ms each. Reporting
+    /// must return immediately, older events must be dropped, and the last
+    /// delivered event must be the most recent one.
+    #[test]
+    fn test_progress_reporter_drops_oldest_events() {
+        let delivered = Arc::new(Mutex::new(Vec::new()));
+        let delivered_clone = delivered.clone();
+        let reporter = ProgressReporter::spawn(4, move |event| {
+            thread::sleep(Duration::from_millis(20));
+            delivered_clone.lock().unwrap().push(event.last_checked);
+        });
+
+        let progress = reporter.handle();
+        let started = Instant::now();
+        for last_checked in 1..=100 {
+            progress.report(ProgressEvent {
+                last_checked,
+                end: 100,
+            });
+        }
+        let reporting_time = started.elapsed();
+        let dropped = reporter.dropped();
+        reporter.finish();
+
+        let delivered = delivered.lock().unwrap();
+        assert!(reporting_time < Duration::from_millis(20));
+        assert!(dropped > 0);
+        assert_eq!(delivered.len() as u64 + dropped, 100);
+        assert_eq!(delivered.last(), Some(&100));
+        assert!(delivered.windows(2).all(|w| w[0] < w[1]));
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Instant;
+
+/// How many buckets are kept before the ones of idle clients are dropped.
+const MAX_IDLE_BUCKETS: usize = 4096;
+
+/// The requests a single client may still send right away.
+///
+/// # Fields
+///
+/// * `tokens` - The requests available, refilled continuously up to the capacity.
+/// * `refilled_at` - The moment `tokens` was last brought up to date.
+#[derive(Clone, Debug)]
+struct TokenBucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    /// Returns the tokens available at `now`, refilled at `rate` per second up to `rate`.
+    fn tokens_at(&self, now: Instant, rate: f64) -> f64 {
+        let elapsed = now
+            .saturating_duration_since(self.refilled_at)
+            .as_secs_f64();
+        (self.tokens + elapsed * rate).min(rate)
+    }
+}
+
+/// Limits the requests per second of each client with a token bucket per address.
+///
+/// A bucket holds up to one second worth of requests, so a client may burst up to
+/// `rate` requests, then sustain `rate` requests per second.
+///
+/// # Fields
+///
+/// * `rate` - The requests per second allowed to each client.
+/// * `buckets` - The bucket of each client seen recently.
+#[derive(Clone, Debug)]
+pub struct RateLimiter {
+    rate: f64,
+    buckets: HashMap<SocketAddr, TokenBucket>,
+}
+
+impl RateLimiter {
+    /// Creates a `RateLimiter` allowing `rate` requests per second to each client.
+    ///
+    /// # Arguments
+    ///
+    /// * `rate` - The requests per second allowed (at least `1`).
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `RateLimiter` with no client seen yet.
+    pub fn new(rate: u32) -> RateLimiter {
+        RateLimiter {
+            rate: f64::from(rate.max(1)),
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Takes a token from the bucket of `client`, if one is available.
+    ///
+    /// Once too many clients were seen, the buckets that refilled completely are
+    /// dropped: a new bucket starts full, so forgetting them changes nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The address the request was received from.
+    /// * `now` - The moment the request was received.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the request is allowed, `false` if the client exceeded its rate.
+    pub fn allow(&mut self, client: SocketAddr, now: Instant) -> bool {
+        let rate = self.rate;
+        if self.buckets.len() >= MAX_IDLE_BUCKETS && !self.buckets.contains_key(&client) {
+            self.buckets
+                .retain(|_, bucket| bucket.tokens_at(now, rate) < rate);
+        }
+
+        let bucket = self.buckets.entry(client).or_insert(TokenBucket {
+            tokens: rate,
+            refilled_at: now,
+        });
+        bucket.tokens = bucket.tokens_at(now, rate);
+        bucket.refilled_at = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Tests that a client may burst up to the rate, is then throttled until tokens
+    /// refill, and doesn't affect other clients.
+    #[test]
+    fn test_rate_limiter_bursts_then_refills() {
+        let mut limiter = RateLimiter::new(4);
+        let spammer: SocketAddr = "127.0.0.1:4000".parse().unwrap();
+        let other: SocketAddr = "127.0.0.1:4001".parse().unwrap();
+        let now = Instant::now();
+
+        let allowed = (0..10).filter(|_| limiter.allow(spammer, now)).count();
+        assert_eq!(allowed, 4);
+        assert!(limiter.allow(other, now));
+
+        assert!(!limiter.allow(spammer, now + Duration::from_millis(200)));
+        assert!(limiter.allow(spammer, now + Duration::from_millis(260)));
+        assert!(!limiter.allow(spammer, now + Duration::from_millis(260)));
+
+        let later = now + Duration::from_secs(10);
+        let allowed = (0..10).filter(|_| limiter.allow(spammer, later)).count();
+        assert_eq!(allowed, 4);
+    }
+
+    /// Tests that the buckets of idle clients are dropped once too many were seen.
+    #[test]
+    fn test_rate_limiter_drops_idle_buckets() {
+        let mut limiter = RateLimiter::new(2);
+        let now = Instant::now();
+        for port in 0..MAX_IDLE_BUCKETS as u16 {
+            limiter.allow(SocketAddr::from(([127, 0, 0, 1], port)), now);
+        }
+        assert_eq!(limiter.buckets.len(), MAX_IDLE_BUCKETS);
+
+        let later = now + Duration::from_secs(1);
+        assert!(limiter.allow("10.0.0.1:4000".parse().unwrap(), later));
+        assert_eq!(limiter.buckets.len(), 1);
+    }
+}
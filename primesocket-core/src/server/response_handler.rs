@@ -1,7 +1,20 @@
-use crate::server::server_state::ServerState;
-use crate::utils::json::{Request, Response};
-use std::cmp::{max, min};
-use std::collections::BTreeSet;
+use crate::server::server_state::{OverlapPolicy, ServerState, UnsortedPolicy};
+use crate::utils::json::{Request, Response, Task, PROTOCOL_VERSION};
+use log::warn;
+use std::cmp::max;
+use std::net::SocketAddr;
+
+/// Tasks that require the admin secret.
+const ADMIN_TASKS: [Task; 4] = [Task::Flush, Task::Outstanding, Task::Extend, Task::Shutdown];
+
+/// The most primes sent in a single `"download"` or `"fetch"` page.
+pub const MAX_DOWNLOAD_PAGE: u64 = 10_000;
+
+/// Tasks answered from the stored primes, including by read replicas.
+const READ_TASKS: [Task; 4] = [Task::IsPrime, Task::QueryRange, Task::Progress, Task::Fetch];
+
+/// Tasks answered from the list of primes, which isn't kept in sum-only mode.
+const LIST_TASKS: [Task; 4] = [Task::IsPrime, Task::QueryRange, Task::Fetch, Task::Download];
 
 /// Handles incoming requests and processes them based on the requested task.
 ///
@@ -12,6 +25,7 @@ use std::collections::BTreeSet;
 ///
 /// * `server_state` - A mutable reference to the server state.
 /// * `request` - A `Request` object containing the task and optional parameters.
+/// * `client` - The address the request was received from.
 ///
 /// # Returns
 ///
@@ -20,87 +34,568 @@ use std::collections::BTreeSet;
 ///
 /// # Task Handling
 ///
-/// - `"start"`: Returns the range of numbers to be processed.
-/// - `"save"`: Updates the state with the latest processed number and primes.
-/// - `"fetch"`: Returns the current list of identified prime numbers.
+/// - `"start"`: Returns the range of numbers to be processed, or `"done"` once the client
+///   saved `max_ranges_per_client` ranges or let `max_expired_leases` leases expire. Expired
+///   leases are reaped on every `"start"`.
+/// - `"save"`: Updates the state with the latest processed number and primes, and answers
+///   `"save_ack"` (or `"done"`) echoing the `end` recorded; saving a range again is harmless.
+///   Submissions holding more primes than their range can contain, or primes outside of it,
+///   are rejected.
+///   Primes out of ascending order are sorted, or rejected with `UnsortedPolicy::Reject`.
+///   Saves carrying the epoch of another run are rejected as `"stale_epoch"`. Saves partially
+///   overlapping saved ranges are counted, and merged or rejected following `OverlapPolicy`;
+///   in sum-only mode, where no union can be taken, they are always rejected.
+/// - `"fetch"`: Returns up to `limit` of the primes found so far from index `offset`, with
+///   their total, `last_checked` and `end`, leaving the state untouched and assigning no
+///   work, for monitoring clients.
+/// - `"stats"`: Returns the throughput: the saves accepted, the request bytes received, the
+///   primes saved per second and the time since the server started.
+/// - `"progress"`: Returns `last_checked`, the completed frontier, how far ranges were assigned
+///   and how many saves overlapped.
+/// - `"flush"`: (Admin) Writes the current primes to disk and replies with the bytes written.
+/// - `"outstanding"`: (Admin) Returns the ranges assigned to clients and not saved yet.
+/// - `"extend"`: (Admin) Raises the end of the computation to the larger `end` of the request.
+/// - `"shutdown"`: (Admin) Stops the server; the primes found so far are saved on the way out.
+/// - `"is_prime"`: Tells whether `start` is prime, if it is below the completed frontier.
+/// - `"query_range"`: Returns the primes in `[start, end]`, if it is below the completed frontier.
+/// - `"whoami"`: Returns the source address the request was received from.
+/// - `"download"`: Returns up to `limit` primes from index `offset`, once the computation is
+///   completed (or by a read replica), along with the total number of primes.
 /// - Any other task: Returns an error response.
-pub fn handler(server_state: &mut ServerState, request: Request) -> Response {
-    // If the computation is completed, return the final result.
-    if server_state.status == "completed" {
+///
+/// Requests speaking another protocol version than `PROTOCOL_VERSION` are answered with a
+/// `"version_mismatch"` error, whatever their task, since their fields can't be trusted.
+///
+/// Once the computation is completed or shut down every request is answered with `"done"`,
+/// but `"save"` requests arriving within the grace period are still validated and merged.
+/// A read replica (status `"read_only"`) only answers read tasks. In sum-only mode, the tasks
+/// answered from the list of primes (`"is_prime"`, `"query_range"`, `"fetch"` and
+/// `"download"`) are answered with a `"sum_only"` error, since only the seed primes are kept.
+pub fn handler(server_state: &mut ServerState, request: Request, client: SocketAddr) -> Response {
+    if request.version != PROTOCOL_VERSION {
+        warn!(
+            "⚠️ Rejected a request from {} speaking protocol version {} (expected {})",
+            client, request.version, PROTOCOL_VERSION
+        );
         return Response {
-            task: "done".to_string(),
-            status: server_state.status.clone(),
-            start: None,
-            end: None,
-            primes: None,
+            task: Task::Error,
+            status: "version_mismatch".to_string(),
+            ..Default::default()
         };
     }
 
-    match request.task.as_str() {
-        "start" => Response {
-            task: "range".to_string(),
-            status: server_state.status.clone(),
-            start: Some(server_state.last_checked),
-            end: Some(min(
-                server_state.last_checked + server_state.step,
-                server_state.end,
-            )),
-            primes: Some(
+    if server_state.sum_only && LIST_TASKS.contains(&request.task) {
+        return Response {
+            task: Task::Error,
+            status: "sum_only".to_string(),
+            ..Default::default()
+        };
+    }
+
+    match request.task {
+        Task::IsPrime => return is_prime_query(server_state, &request),
+        Task::QueryRange => return query_range(server_state, &request),
+        Task::Download => return download(server_state, &request),
+        Task::Whoami => {
+            return Response {
+                task: Task::Whoami,
+                status: server_state.status.clone(),
+                address: Some(client.to_string()),
+                ..Default::default()
+            }
+        }
+        _ => {}
+    }
+
+    if server_state.status == "read_only" && !READ_TASKS.contains(&request.task) {
+        return Response {
+            task: Task::Error,
+            status: "read_only".to_string(),
+            ..Default::default()
+        };
+    }
+
+    // If the computation is completed or shut down, return the final result.
+    if server_state.is_stopped() {
+        // Late submissions are still merged during the grace period, once they pass
+        // the same checks as any other save.
+        if request.task == Task::Save && server_state.in_grace_period() {
+            if let Some(rejection) = check_save(server_state, &request, client) {
+                return rejection;
+            }
+            if server_state.sum_only {
+                if let Some(rejection) = record_sum(server_state, &request) {
+                    return rejection;
+                }
+                let end = request.end.unwrap_or(0);
                 server_state
-                    .primes
-                    .iter()
-                    .take(5_000)
-                    .cloned()
-                    .collect::<Vec<u32>>(),
-            ),
+                    .completed
+                    .insert(request.start.unwrap_or(end), end);
+            } else {
+                server_state.merge_primes(request.primes.unwrap_or_default());
+            }
+        }
+
+        return Response {
+            task: Task::Done,
+            status: server_state.status.clone(),
+            ..Default::default()
+        };
+    }
+
+    if ADMIN_TASKS.contains(&request.task) && !server_state.is_authorized(request.secret.as_deref())
+    {
+        return Response {
+            task: Task::Error,
+            status: "unauthorized".to_string(),
+            ..Default::default()
+        };
+    }
+
+    if request.task == Task::Start {
+        server_state.reap_expired_leases();
+    }
+
+    match request.task {
+        Task::Start if server_state.client_quarantined(&client) => Response {
+            task: Task::Done,
+            status: "quarantined".to_string(),
+            ..Default::default()
+        },
+        Task::Start if server_state.client_limit_reached(&client) => Response {
+            task: Task::Done,
+            status: "client_limit_reached".to_string(),
+            ..Default::default()
         },
-        "save" => {
+        Task::Start => {
+            let (start, end) = server_state.next_range();
+            server_state.assignments.assign(start, end, client);
+            server_state.assigned_up_to = max(server_state.assigned_up_to, end);
+
+            Response {
+                task: Task::Range,
+                status: server_state.status.clone(),
+                start: Some(start),
+                end: Some(end),
+                primes: Some(server_state.sieving_primes()),
+                residue: server_state.residue,
+                epoch: Some(server_state.epoch),
+                sum_only: server_state.sum_only.then_some(true),
+                ..Default::default()
+            }
+        }
+        Task::Save => {
+            if let Some(rejection) = check_save(server_state, &request, client) {
+                return rejection;
+            }
             let last_checked = request.end.unwrap_or(0);
+            let count = saved_count(&request);
+            if server_state.sum_only {
+                if let Some(rejection) = record_sum(server_state, &request) {
+                    return rejection;
+                }
+            }
+            server_state.assignments.complete(last_checked);
+            if let Some(start) = request.start {
+                server_state.completed.insert(start, last_checked);
+            }
+            let mut primes = request.primes.unwrap_or_default();
+            if !is_ascending(&primes) {
+                primes.sort_unstable();
+                primes.dedup();
+            }
+            let saved_primes = if server_state.sum_only {
+                count as usize
+            } else {
+                primes.len()
+            };
             server_state
-                .primes
-                .extend(request.primes.unwrap_or_default());
-            server_state.primes = server_state
-                .primes
-                .iter()
-                .cloned()
-                .collect::<BTreeSet<_>>()
-                .into_iter()
-                .collect();
+                .client_stats
+                .entry(client)
+                .or_default()
+                .record_save(saved_primes);
+            if !server_state.sum_only {
+                server_state.merge_primes(primes);
+            }
             server_state.last_checked = max(last_checked, server_state.last_checked);
+            server_state.record_pi_checkpoints();
+            server_state.saves_since_checkpoint += 1;
+            server_state.segments_saved += 1;
+            server_state.primes_saved += saved_primes as u64;
 
-            // If the last checked number reaches the end, mark as completed.
-            if server_state.last_checked >= server_state.end {
-                server_state.status = "completed".to_string();
+            // If every range was saved, mark as completed.
+            if server_state.is_finished() {
+                server_state.mark_completed();
                 return Response {
-                    task: "done".to_string(),
+                    task: Task::Done,
                     status: server_state.status.clone(),
-                    start: None,
-                    end: None,
-                    primes: None,
+                    end: Some(last_checked),
+                    ..Default::default()
                 };
             }
 
             Response {
-                task: "continue".to_string(),
+                task: Task::SaveAck,
+                status: server_state.status.clone(),
+                end: Some(last_checked),
+                completed_frontier: Some(server_state.completed_frontier()),
+                ..Default::default()
+            }
+        }
+        Task::Progress => Response {
+            task: Task::Progress,
+            status: server_state.status.clone(),
+            start: Some(server_state.start),
+            end: Some(server_state.end),
+            last_checked: Some(server_state.last_checked),
+            completed_frontier: Some(server_state.completed_frontier()),
+            assigned_up_to: Some(server_state.assigned_up_to),
+            overlapping_saves: Some(server_state.overlapping_saves),
+            ..Default::default()
+        },
+        Task::Fetch => fetch(server_state, &request),
+        Task::Stats => Response {
+            task: Task::Stats,
+            status: server_state.status.clone(),
+            segments: Some(server_state.segments_saved),
+            bytes_received: Some(server_state.bytes_received),
+            primes_per_second: Some(server_state.primes_per_second()),
+            elapsed_ms: Some(server_state.started_at.elapsed().as_millis() as u64),
+            ..Default::default()
+        },
+        Task::Flush => match server_state.save_primes_to_file() {
+            Ok(bytes) => Response {
+                task: Task::Flushed,
+                status: server_state.status.clone(),
+                bytes: Some(bytes),
+                ..Default::default()
+            },
+            Err(_) => Response {
+                task: Task::Error,
+                status: "flush_failed".to_string(),
+                ..Default::default()
+            },
+        },
+        Task::Shutdown => {
+            server_state.mark_shutdown();
+            Response {
+                task: Task::Done,
                 status: server_state.status.clone(),
-                start: None,
-                end: None,
-                primes: None,
+                ..Default::default()
             }
         }
+        Task::Outstanding => Response {
+            task: Task::Outstanding,
+            status: server_state.status.clone(),
+            outstanding: Some(server_state.assignments.outstanding()),
+            ..Default::default()
+        },
+        Task::Extend => match request.end {
+            Some(end) if end > server_state.end => {
+                server_state.extend_end(end);
+                Response {
+                    task: Task::Extended,
+                    status: server_state.status.clone(),
+                    end: Some(end),
+                    ..Default::default()
+                }
+            }
+            _ => Response {
+                task: Task::Error,
+                status: "invalid_range".to_string(),
+                ..Default::default()
+            },
+        },
         _ => Response {
-            task: "error".to_string(),
+            task: Task::Error,
             status: "invalid_task".to_string(),
-            start: None,
-            end: None,
-            primes: None,
+            ..Default::default()
+        },
+    }
+}
+
+/// Validates a `"save"` before anything from it is recorded.
+///
+/// Saves carrying the epoch of another run, ending past the end of the run, holding more
+/// primes than their range can contain or primes outside of it, or unsorted primes under
+/// `UnsortedPolicy::Reject` are rejected. Saves partially overlapping saved ranges are counted, and rejected
+/// under `OverlapPolicy::Reject` or in sum-only mode.
+///
+/// # Arguments
+///
+/// * `server_state` - The server state.
+/// * `request` - The `"save"` request.
+/// * `client` - The address the request was received from.
+///
+/// # Returns
+///
+/// `Some(Response)` with the error rejecting the save, or `None` if it may be recorded.
+fn check_save(
+    server_state: &mut ServerState,
+    request: &Request,
+    client: SocketAddr,
+) -> Option<Response> {
+    let error = |status: &str| {
+        Some(Response {
+            task: Task::Error,
+            status: status.to_string(),
+            ..Default::default()
+        })
+    };
+    if request
+        .epoch
+        .is_some_and(|epoch| epoch != server_state.epoch)
+    {
+        return error("stale_epoch");
+    }
+    if request.end.is_none_or(|end| end > server_state.end) {
+        return error("out_of_range");
+    }
+    if !server_state.plausible_prime_count(
+        request.start.unwrap_or(server_state.start),
+        request.end.unwrap_or(0),
+        saved_count(request) as usize,
+    ) {
+        return error("implausible_prime_count");
+    }
+    if !primes_within_range(server_state, request) {
+        return error("out_of_range");
+    }
+    if server_state.unsorted_policy == UnsortedPolicy::Reject
+        && !is_ascending(request.primes.as_deref().unwrap_or_default())
+    {
+        return error("unsorted_primes");
+    }
+
+    let end = request.end.unwrap_or(0);
+    let start = request.start.unwrap_or(end);
+    if server_state.overlaps_saved(start, end) {
+        server_state.overlapping_saves += 1;
+        warn!(
+            "⚠️ Save of [{}, {}] from {} overlaps ranges saved before",
+            start, end, client
+        );
+        if server_state.sum_only || server_state.overlap_policy == OverlapPolicy::Reject {
+            return error("overlapping_range");
+        }
+    }
+    None
+}
+
+/// Adds the sum and count of a validated `"save"` to the totals of a sum-only run.
+///
+/// Saves carrying the list instead of the sum are added up here.
+///
+/// # Arguments
+///
+/// * `server_state` - The server state.
+/// * `request` - The `"save"` request.
+///
+/// # Returns
+///
+/// `Some(Response)` with a `"sum_overflow"` error if the total would overflow, or `None`.
+fn record_sum(server_state: &mut ServerState, request: &Request) -> Option<Response> {
+    let end = request.end.unwrap_or(0);
+    let start = request.start.unwrap_or(end);
+    let sum = request.sum.unwrap_or_else(|| {
+        let primes = request.primes.as_deref().unwrap_or_default();
+        primes.iter().map(|&p| u128::from(p)).sum()
+    });
+    if server_state.record_sum(start, end, sum, saved_count(request)) {
+        return None;
+    }
+    Some(Response {
+        task: Task::Error,
+        status: "sum_overflow".to_string(),
+        ..Default::default()
+    })
+}
+
+/// Checks that every submitted prime lies within the submitted range.
+///
+/// # Arguments
+///
+/// * `server_state` - The server state, whose `start` stands in for a missing range start.
+/// * `request` - The `"save"` request.
+///
+/// # Returns
+///
+/// `true` if every prime is within `[start, end]` of the request.
+fn primes_within_range(server_state: &ServerState, request: &Request) -> bool {
+    let start = request.start.unwrap_or(server_state.start);
+    let end = request.end.unwrap_or(0);
+    request
+        .primes
+        .as_deref()
+        .unwrap_or_default()
+        .iter()
+        .all(|prime| (start..=end).contains(prime))
+}
+
+/// Returns the number of primes a `"save"` reports: its `count` when it carries a
+/// sum instead of the list, otherwise the length of the list.
+fn saved_count(request: &Request) -> u64 {
+    request
+        .count
+        .unwrap_or_else(|| request.primes.as_ref().map_or(0, Vec::len) as u64)
+}
+
+/// Checks in a single pass that `primes` are strictly ascending.
+fn is_ascending(primes: &[u64]) -> bool {
+    primes.windows(2).all(|pair| pair[0] < pair[1])
+}
+
+/// Answers an `"is_prime"` query for `request.start`.
+///
+/// Only numbers up to the completed frontier can be answered: above it, a
+/// missing prime may simply not have been saved yet.
+fn is_prime_query(server_state: &ServerState, request: &Request) -> Response {
+    match request.start {
+        Some(n) if n <= server_state.completed_frontier() => Response {
+            task: Task::IsPrime,
+            status: server_state.status.clone(),
+            start: Some(n),
+            is_prime: Some(server_state.primes.binary_search(&n).is_ok()),
+            ..Default::default()
         },
+        Some(_) => Response {
+            task: Task::Error,
+            status: "out_of_range".to_string(),
+            ..Default::default()
+        },
+        None => Response {
+            task: Task::Error,
+            status: "invalid_range".to_string(),
+            ..Default::default()
+        },
+    }
+}
+
+/// Answers a `"query_range"` query with the primes in `[request.start, request.end]`.
+fn query_range(server_state: &ServerState, request: &Request) -> Response {
+    let (start, end) = match (request.start, request.end) {
+        (Some(start), Some(end)) if start <= end => (start, end),
+        _ => {
+            return Response {
+                task: Task::Error,
+                status: "invalid_range".to_string(),
+                ..Default::default()
+            }
+        }
+    };
+    if end > server_state.completed_frontier() {
+        return Response {
+            task: Task::Error,
+            status: "out_of_range".to_string(),
+            ..Default::default()
+        };
+    }
+
+    let primes = &server_state.primes;
+    let from = primes.partition_point(|&p| p < start);
+    let to = primes.partition_point(|&p| p <= end);
+    Response {
+        task: Task::QueryRange,
+        status: server_state.status.clone(),
+        start: Some(start),
+        end: Some(end),
+        primes: Some(primes[from..to].to_vec()),
+        ..Default::default()
+    }
+}
+
+/// Answers a `"download"` with a page of the primes found.
+///
+/// Only finished computations are served, so the pages of a download never shift
+/// and an interrupted download can resume from the offset it reached. Offsets past
+/// the number of primes are rejected; the offset equal to it answers an empty page.
+fn download(server_state: &ServerState, request: &Request) -> Response {
+    if !matches!(server_state.status.as_str(), "completed" | "read_only") {
+        return Response {
+            task: Task::Error,
+            status: "not_completed".to_string(),
+            ..Default::default()
+        };
+    }
+
+    let Some(page) = page(&server_state.primes, request) else {
+        return invalid_offset(server_state);
+    };
+    Response {
+        task: Task::Download,
+        status: server_state.status.clone(),
+        primes: Some(page.to_vec()),
+        offset: Some(request.offset.unwrap_or(0)),
+        total: Some(server_state.primes.len() as u64),
+        ..Default::default()
+    }
+}
+
+/// Answers a `"fetch"` with a page of the primes found so far and the progress.
+///
+/// Unlike `"download"`, the computation may still be running: primes saved in the
+/// meantime below the offset shift the next pages, so a client paging through a
+/// running computation may see a prime twice.
+fn fetch(server_state: &ServerState, request: &Request) -> Response {
+    let Some(page) = page(&server_state.primes, request) else {
+        return invalid_offset(server_state);
+    };
+    Response {
+        task: Task::Fetch,
+        status: server_state.status.clone(),
+        end: Some(server_state.end),
+        last_checked: Some(server_state.last_checked),
+        primes: Some(page.to_vec()),
+        offset: Some(request.offset.unwrap_or(0)),
+        total: Some(server_state.primes.len() as u64),
+        ..Default::default()
+    }
+}
+
+/// Returns the page of `primes` a request asks for: up to `limit` primes, capped at
+/// `MAX_DOWNLOAD_PAGE`, from index `offset`.
+///
+/// # Returns
+///
+/// The page, empty at the offset equal to the number of primes, or `None` past it.
+fn page<'a>(primes: &'a [u64], request: &Request) -> Option<&'a [u64]> {
+    let offset = request.offset.unwrap_or(0);
+    let limit = request
+        .limit
+        .unwrap_or(MAX_DOWNLOAD_PAGE)
+        .min(MAX_DOWNLOAD_PAGE);
+    if offset > primes.len() as u64 {
+        return None;
+    }
+    let from = offset as usize;
+    let to = from.saturating_add(limit as usize).min(primes.len());
+    Some(&primes[from..to])
+}
+
+/// Rejects a page request whose offset is past the number of primes, reporting it.
+fn invalid_offset(server_state: &ServerState) -> Response {
+    Response {
+        task: Task::Error,
+        status: "invalid_offset".to_string(),
+        total: Some(server_state.primes.len() as u64),
+        ..Default::default()
     }
 }
 
 #[cfg(test)]
 mod unit_tests {
     use super::*;
+    use crate::server::assignment::AssignmentOrder;
+    use crate::server::server_state::DEFAULT_STEP;
+    use crate::utils::primality::is_prime;
+    use crate::utils::sieve::{primes_up_to, sieve_segment};
+    use std::fs;
+    use std::time::Duration;
+
+    /// Returns the address used as the requesting client in tests.
+    fn client() -> SocketAddr {
+        "127.0.0.1:4000".parse().unwrap()
+    }
 
     /// Tests the `handler` function when a "start" request is sent.
     ///
@@ -113,20 +608,1230 @@ mod unit_tests {
         let start = 0;
         let end = 100;
 
-        let mut server_state = ServerState::new(start, end);
+        let mut server_state = ServerState::new(start, end, DEFAULT_STEP);
 
         let request = Request {
-            task: "start".to_string(),
-            end: None,
-            primes: None,
+            task: Task::Start,
+            ..Default::default()
         };
 
-        let response = handler(&mut server_state, request);
+        let response = handler(&mut server_state, request, client());
         println!("Handler response: {:?}", response);
 
-        assert_eq!(response.task, "range");
+        assert_eq!(response.task, Task::Range);
         assert_eq!(response.status, "processing");
         assert!(response.start.is_some());
         assert!(response.end.is_some());
     }
+
+    /// Tests a whole run over `[2, 1_000_000]`, far above the squares of the default seeds:
+    /// clients sieving with the base primes handed out never submit a composite.
+    #[test]
+    fn test_base_primes_cover_sqrt_end() {
+        let mut server_state = ServerState::new(2, 1_000_000, DEFAULT_STEP);
+        assert!(server_state
+            .seed_primes
+            .last()
+            .is_some_and(|&p| p * p < 1_000_000));
+        loop {
+            let range = handler(
+                &mut server_state,
+                Request {
+                    task: Task::Start,
+                    ..Default::default()
+                },
+                client(),
+            );
+            if range.task != Task::Range {
+                break;
+            }
+            let (start, end) = (range.start.unwrap(), range.end.unwrap());
+            let primes = sieve_segment(start, end, range.primes.unwrap());
+            assert!(
+                primes.iter().all(|&p| is_prime(p)),
+                "composite in [{}, {}]",
+                start,
+                end
+            );
+            handler(
+                &mut server_state,
+                Request {
+                    task: Task::Save,
+                    start: Some(start),
+                    end: Some(end),
+                    primes: Some(primes),
+                    ..Default::default()
+                },
+                client(),
+            );
+        }
+        assert_eq!(server_state.status, "completed");
+        assert_eq!(server_state.primes, primes_up_to(1_000_000));
+    }
+
+    /// Tests that custom seed primes start the list of primes and are the base primes of
+    /// the first `"range"`, those beyond `sqrt(end)` left out.
+    #[test]
+    fn test_handler_start_uses_seed_primes() {
+        let seeds = primes_up_to(2_000);
+        let mut server_state =
+            ServerState::with_seed_primes(2, 1_000_000, DEFAULT_STEP, Some(seeds.clone()));
+        assert_eq!(server_state.primes, seeds);
+
+        let response = handler(
+            &mut server_state,
+            Request {
+                task: Task::Start,
+                ..Default::default()
+            },
+            client(),
+        );
+        assert_eq!(response.task, Task::Range);
+        let base = response.primes.unwrap();
+        assert_eq!(base, seeds[..seeds.partition_point(|&p| p <= 1_001)]);
+
+        let server_state =
+            ServerState::with_seed_primes(2, 1_000_000, DEFAULT_STEP, Some(vec![2, 3, 5]));
+        assert_eq!(server_state.sieving_primes(), primes_up_to(1_001));
+        assert_eq!(server_state.primes, vec![2, 3, 5]);
+    }
+
+    /// Tests that the JSON the server emits parses on the client, and the other way around.
+    #[test]
+    fn test_handler_json_round_trips() {
+        let mut server_state = ServerState::new(2, 10_000, DEFAULT_STEP);
+        server_state.restrict_to_residue(1, 4);
+
+        let request = Request {
+            task: Task::Start,
+            ..Default::default()
+        };
+        let json = handler(&mut server_state, request, client())
+            .to_json()
+            .unwrap();
+        let range = Response::from_json(&json).unwrap();
+        assert_eq!(range.task, Task::Range);
+        assert_eq!((range.start, range.end), (Some(2), Some(1_002)));
+        assert_eq!(range.primes, Some(server_state.sieving_primes()));
+        assert_eq!(range.residue, Some((1, 4)));
+
+        let save = Request {
+            task: Task::Save,
+            start: range.start,
+            end: range.end,
+            primes: Some(vec![5, 13, 17]),
+            ..Default::default()
+        };
+        let save = Request::from_json(&save.to_json().unwrap()).unwrap();
+        assert_eq!(save.task, Task::Save);
+        assert_eq!(save.primes, Some(vec![5, 13, 17]));
+        let json = handler(&mut server_state, save, client())
+            .to_json()
+            .unwrap();
+        assert_eq!(Response::from_json(&json).unwrap().task, Task::SaveAck);
+    }
+
+    /// Tests that an authorized `"shutdown"` takes the state out of `"processing"`.
+    #[test]
+    fn test_handler_shutdown() {
+        let mut server_state = ServerState::new(2, 1_000, DEFAULT_STEP);
+        server_state.admin_secret = Some("s3cret".to_string());
+        let shutdown = |secret: Option<&str>| Request {
+            task: Task::Shutdown,
+            secret: secret.map(str::to_string),
+            ..Default::default()
+        };
+
+        let response = handler(&mut server_state, shutdown(None), client());
+        assert_eq!(response.status, "unauthorized");
+        assert_eq!(server_state.status, "processing");
+
+        let response = handler(&mut server_state, shutdown(Some("s3cret")), client());
+        assert_eq!(response.task, Task::Done);
+        assert_eq!(server_state.status, "shutdown");
+        assert!(server_state.is_stopped());
+
+        let start = Request {
+            task: Task::Start,
+            ..Default::default()
+        };
+        assert_eq!(handler(&mut server_state, start, client()).task, Task::Done);
+    }
+
+    /// Tests that `"download"` pages through the primes only once the computation is over.
+    #[test]
+    fn test_handler_download_pages() {
+        let mut server_state = ServerState::new(2, 100, DEFAULT_STEP);
+        let download = |offset, limit| Request {
+            task: Task::Download,
+            offset: Some(offset),
+            limit: Some(limit),
+            ..Default::default()
+        };
+
+        let response = handler(&mut server_state, download(0, 10), client());
+        assert_eq!(response.status, "not_completed");
+
+        server_state.mark_completed();
+        let response = handler(&mut server_state, download(20, 10), client());
+        assert_eq!(response.task, Task::Download);
+        assert_eq!(response.primes, Some(vec![73, 79, 83, 89, 97]));
+        assert_eq!((response.offset, response.total), (Some(20), Some(25)));
+
+        let response = handler(&mut server_state, download(25, 10), client());
+        assert_eq!(response.primes, Some(vec![]));
+
+        let response = handler(&mut server_state, download(30, 10), client());
+        assert_eq!(response.task, Task::Error);
+        assert_eq!(response.status, "invalid_offset");
+        assert_eq!(response.total, Some(25));
+    }
+
+    /// Tests that a client letting its leases expire is quarantined while others get work.
+    #[test]
+    fn test_handler_quarantines_expiring_client() {
+        let mut server_state = ServerState::new(2, 100_000, DEFAULT_STEP);
+        server_state.max_expired_leases = Some(2);
+        server_state.assignments.lease_duration = Duration::ZERO;
+        let flaky: SocketAddr = "127.0.0.1:4001".parse().unwrap();
+        let start = || Request {
+            task: Task::Start,
+            ..Default::default()
+        };
+
+        // Each new request reaps the lease the previous one let expire.
+        for _ in 0..2 {
+            assert_eq!(handler(&mut server_state, start(), flaky).task, Task::Range);
+        }
+        let range = handler(&mut server_state, start(), flaky);
+        assert_eq!(range.task, Task::Done);
+        assert_eq!(range.status, "quarantined");
+        assert_eq!(server_state.client_stats[&flaky].expired_leases, 2);
+        assert!(server_state.client_quarantined(&flaky));
+
+        assert_eq!(
+            handler(&mut server_state, start(), client()).task,
+            Task::Range
+        );
+        assert!(!server_state.client_quarantined(&client()));
+
+        let late_save = Request {
+            task: Task::Save,
+            start: Some(2),
+            end: Some(1_002),
+            primes: Some(primes_up_to(1_002)),
+            ..Default::default()
+        };
+        assert_eq!(
+            handler(&mut server_state, late_save, flaky).task,
+            Task::SaveAck
+        );
+        assert_eq!(server_state.last_checked, 1_002);
+    }
+
+    /// Tests that a request carrying an unknown protocol version is rejected without
+    /// touching the state, while one without a version is served.
+    #[test]
+    fn test_handler_rejects_unknown_version() {
+        let mut server_state = ServerState::new(2, 100, DEFAULT_STEP);
+        let request = Request::from_json(r#"{"version":9,"task":"start"}"#).unwrap();
+        let response = handler(&mut server_state, request, client());
+        assert_eq!(response.task, Task::Error);
+        assert_eq!(response.status, "version_mismatch");
+        assert_eq!(response.version, PROTOCOL_VERSION);
+        assert_eq!(
+            server_state.assigned_up_to,
+            ServerState::new(2, 100, DEFAULT_STEP).assigned_up_to
+        );
+
+        let request = Request::from_json(r#"{"task":"start"}"#).unwrap();
+        assert_eq!(
+            handler(&mut server_state, request, client()).task,
+            Task::Range
+        );
+    }
+
+    /// Tests that a save carrying the epoch of another run is rejected.
+    #[test]
+    fn test_handler_rejects_stale_epoch() {
+        let mut server_state = ServerState::new(2, 100, DEFAULT_STEP);
+        let stale = ServerState::new(2, 100, DEFAULT_STEP).epoch;
+        assert_ne!(stale, server_state.epoch);
+
+        let start = Request {
+            task: Task::Start,
+            ..Default::default()
+        };
+        let range = handler(&mut server_state, start, client());
+        assert_eq!(range.epoch, Some(server_state.epoch));
+
+        let save = |epoch| Request {
+            task: Task::Save,
+            start: Some(2),
+            end: Some(100),
+            primes: Some(vec![2, 3, 5, 7]),
+            epoch,
+            ..Default::default()
+        };
+        let response = handler(&mut server_state, save(Some(stale)), client());
+        assert_eq!(response.task, Task::Error);
+        assert_eq!(response.status, "stale_epoch");
+        assert_eq!(server_state.last_checked, 2);
+
+        let response = handler(&mut server_state, save(range.epoch), client());
+        assert_eq!(response.task, Task::Done);
+    }
+
+    /// Tests that a late `"save"` within the grace period reaches the final file.
+    ///
+    /// The computation is completed by a first chunk, then a chunk that was still
+    /// outstanding arrives late. Its primes must be merged and written to disk.
+    #[test]
+    fn test_handler_late_save_within_grace_period() {
+        let mut server_state = ServerState::new(2, 100, DEFAULT_STEP);
+        server_state.grace_period = Duration::from_secs(60);
+        server_state.output_path =
+            std::env::temp_dir().join(format!("primesocket_grace_{}.txt", std::process::id()));
+
+        let response = handler(
+            &mut server_state,
+            Request {
+                task: Task::Save,
+                end: Some(100),
+                primes: Some(vec![2, 3, 5, 7]),
+                ..Default::default()
+            },
+            client(),
+        );
+        assert_eq!(response.task, Task::Done);
+        assert_eq!(server_state.status, "completed");
+
+        let response = handler(
+            &mut server_state,
+            Request {
+                task: Task::Save,
+                end: Some(50),
+                primes: Some(vec![41, 43, 47]),
+                ..Default::default()
+            },
+            client(),
+        );
+        assert_eq!(response.task, Task::Done);
+
+        server_state.save_primes_to_file().unwrap();
+        let contents = fs::read_to_string(&server_state.output_path).unwrap();
+        fs::remove_file(&server_state.output_path).unwrap();
+
+        let saved: Vec<u64> = contents.lines().map(|l| l.parse().unwrap()).collect();
+        assert!(saved.contains(&47));
+        assert!(saved.contains(&97));
+    }
+
+    /// Tests that a late `"save"` within the grace period is validated like any other:
+    /// primes outside its range, or a range beyond the end, are rejected and not merged.
+    #[test]
+    fn test_handler_late_save_rejected_when_invalid() {
+        let mut server_state = ServerState::new(2, 100, DEFAULT_STEP);
+        server_state.grace_period = Duration::from_secs(60);
+        let save = |start, end, primes| Request {
+            task: Task::Save,
+            start: Some(start),
+            end: Some(end),
+            primes: Some(primes),
+            ..Default::default()
+        };
+        let all = primes_up_to(100);
+        handler(&mut server_state, save(2, 100, all.clone()), client());
+        assert!(server_state.in_grace_period());
+
+        let response = handler(
+            &mut server_state,
+            save(50, 100, vec![53, 1_000_003]),
+            client(),
+        );
+        assert_eq!(response.task, Task::Error);
+        assert_eq!(response.status, "out_of_range");
+        let response = handler(&mut server_state, save(100, 200, vec![101, 103]), client());
+        assert_eq!(response.task, Task::Error);
+        assert_eq!(response.status, "out_of_range");
+        assert_eq!(server_state.primes, all);
+    }
+
+    /// Tests that a `"save"` ending past the end of the run is rejected, so it can't move
+    /// `last_checked` past the end and mark the run completed.
+    #[test]
+    fn test_handler_save_past_end_rejected() {
+        let mut server_state = ServerState::new(2, 100, DEFAULT_STEP);
+        let last_checked = server_state.last_checked;
+        let response = handler(
+            &mut server_state,
+            Request {
+                task: Task::Save,
+                end: Some(u64::MAX),
+                primes: Some(Vec::new()),
+                ..Default::default()
+            },
+            client(),
+        );
+        assert_eq!(response.task, Task::Error);
+        assert_eq!(response.status, "out_of_range");
+        assert_eq!(server_state.last_checked, last_checked);
+        assert_ne!(server_state.status, "completed");
+    }
+
+    /// Tests that in sum-only mode, a late `"save"` within the grace period is added to
+    /// the totals rather than merged into the list of primes.
+    #[test]
+    fn test_handler_late_sum_save_within_grace_period() {
+        let mut server_state = ServerState::new(2, 100, DEFAULT_STEP);
+        server_state.sum_only = true;
+        server_state.grace_period = Duration::from_secs(60);
+        let seeds = server_state.primes.clone();
+        let save = |start, end| {
+            let primes = sieve_segment(start, end, primes_up_to(10));
+            Request {
+                task: Task::Save,
+                start: Some(start),
+                end: Some(end),
+                sum: Some(primes.iter().map(|&p| u128::from(p)).sum()),
+                count: Some(primes.len() as u64),
+                ..Default::default()
+            }
+        };
+
+        let response = handler(&mut server_state, save(50, 100), client());
+        assert_eq!(response.task, Task::Done);
+        assert!(server_state.in_grace_period());
+        let response = handler(&mut server_state, save(2, 50), client());
+        assert_eq!(response.task, Task::Done);
+
+        let all = primes_up_to(100);
+        assert_eq!(
+            server_state.prime_sum,
+            all.iter().map(|&p| u128::from(p)).sum::<u128>()
+        );
+        assert_eq!(server_state.prime_count, all.len() as u64);
+        assert_eq!(server_state.primes, seeds);
+
+        // The same range saved again is not counted twice.
+        handler(&mut server_state, save(2, 50), client());
+        assert_eq!(server_state.prime_count, all.len() as u64);
+    }
+
+    /// Tests that in sum-only mode, the tasks answered from the list of primes are refused
+    /// with `"sum_only"`, since the list only holds the seed primes.
+    #[test]
+    fn test_handler_sum_only_refuses_list_tasks() {
+        let mut server_state = ServerState::new(2, 100, DEFAULT_STEP);
+        server_state.sum_only = true;
+        server_state.completed.insert(2, 100);
+        server_state.mark_completed();
+
+        for task in [Task::IsPrime, Task::QueryRange, Task::Fetch, Task::Download] {
+            let request = Request {
+                task,
+                start: Some(2),
+                end: Some(100),
+                ..Default::default()
+            };
+            let response = handler(&mut server_state, request, client());
+            assert_eq!(response.task, Task::Error);
+            assert_eq!(response.status, "sum_only");
+        }
+    }
+
+    /// Tests that a late `"save"` after the grace period is not merged.
+    #[test]
+    fn test_handler_late_save_after_grace_period() {
+        let mut server_state = ServerState::new(2, 100, DEFAULT_STEP);
+        server_state.primes.clear();
+        server_state.mark_completed();
+
+        let response = handler(
+            &mut server_state,
+            Request {
+                task: Task::Save,
+                end: Some(50),
+                primes: Some(vec![41, 43, 47]),
+                ..Default::default()
+            },
+            client(),
+        );
+
+        assert_eq!(response.task, Task::Done);
+        assert!(server_state.primes.is_empty());
+    }
+
+    /// Tests that an authorized `"flush"` writes the current primes mid-run.
+    ///
+    /// The reply must carry the number of bytes written and the computation
+    /// must keep its `"processing"` status.
+    #[test]
+    fn test_handler_flush_mid_run() {
+        let mut server_state = ServerState::new(2, 10_000, DEFAULT_STEP);
+        server_state.admin_secret = Some("s3cret".to_string());
+        server_state.output_path =
+            std::env::temp_dir().join(format!("primesocket_flush_{}.txt", std::process::id()));
+
+        handler(
+            &mut server_state,
+            Request {
+                task: Task::Save,
+                end: Some(1_000),
+                primes: Some(vec![101, 103]),
+                ..Default::default()
+            },
+            client(),
+        );
+
+        let response = handler(
+            &mut server_state,
+            Request {
+                task: Task::Flush,
+                secret: Some("s3cret".to_string()),
+                ..Default::default()
+            },
+            client(),
+        );
+        let contents = fs::read_to_string(&server_state.output_path).unwrap();
+        fs::remove_file(&server_state.output_path).unwrap();
+
+        assert_eq!(response.task, Task::Flushed);
+        assert_eq!(response.status, "processing");
+        assert_eq!(response.bytes, Some(contents.len() as u64));
+        assert_eq!(server_state.status, "processing");
+        assert_eq!(contents.lines().last(), Some("103"));
+    }
+
+    /// Tests that `"flush"` is rejected without the right secret.
+    #[test]
+    fn test_handler_flush_unauthorized() {
+        let mut server_state = ServerState::new(2, 100, DEFAULT_STEP);
+        server_state.admin_secret = Some("s3cret".to_string());
+        server_state.output_path = std::env::temp_dir().join("primesocket_flush_denied.txt");
+
+        let response = handler(
+            &mut server_state,
+            Request {
+                task: Task::Flush,
+                secret: Some("guess".to_string()),
+                ..Default::default()
+            },
+            client(),
+        );
+
+        assert_eq!(response.task, Task::Error);
+        assert_eq!(response.status, "unauthorized");
+        assert!(!server_state.output_path.exists());
+    }
+
+    /// Tests that the admin `"outstanding"` task lists unsaved assignments.
+    ///
+    /// Two ranges are assigned to different clients without being saved; both
+    /// must be reported with their bounds, owner and a lease in the future.
+    #[test]
+    fn test_handler_outstanding_ranges() {
+        let mut server_state = ServerState::new(2, 10_000, DEFAULT_STEP);
+        server_state.admin_secret = Some("s3cret".to_string());
+        let other: SocketAddr = "127.0.0.1:4001".parse().unwrap();
+
+        handler(
+            &mut server_state,
+            Request {
+                task: Task::Start,
+                ..Default::default()
+            },
+            client(),
+        );
+        server_state.assignments.assign(1_002, 2_002, other);
+
+        let response = handler(
+            &mut server_state,
+            Request {
+                task: Task::Outstanding,
+                secret: Some("s3cret".to_string()),
+                ..Default::default()
+            },
+            client(),
+        );
+        let outstanding = response.outstanding.unwrap();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+
+        assert_eq!(response.task, Task::Outstanding);
+        assert_eq!(outstanding.len(), 2);
+        assert_eq!((outstanding[0].start, outstanding[0].end), (2, 1_002));
+        assert_eq!(outstanding[0].assigned_to, "127.0.0.1:4000");
+        assert_eq!((outstanding[1].start, outstanding[1].end), (1_002, 2_002));
+        assert_eq!(outstanding[1].assigned_to, "127.0.0.1:4001");
+        assert!(outstanding.iter().all(|range| range.lease_expires > now));
+    }
+
+    /// Tests that the save completing the computation and a `"shutdown"` notify the
+    /// stop signal.
+    #[tokio::test]
+    async fn test_handler_notifies_stop_signal() {
+        let mut server_state = ServerState::new(2, 100, DEFAULT_STEP);
+        let stop_signal = server_state.stop_signal.clone();
+        handler(
+            &mut server_state,
+            Request {
+                task: Task::Start,
+                ..Default::default()
+            },
+            client(),
+        );
+        handler(
+            &mut server_state,
+            Request {
+                task: Task::Save,
+                start: Some(2),
+                end: Some(100),
+                primes: Some(primes_up_to(100)),
+                ..Default::default()
+            },
+            client(),
+        );
+        assert_eq!(server_state.status, "completed");
+        tokio::time::timeout(Duration::ZERO, stop_signal.notified())
+            .await
+            .expect("completion was not signaled");
+
+        let mut server_state = ServerState::new(2, 100, DEFAULT_STEP);
+        server_state.admin_secret = Some("s3cret".to_string());
+        let stop_signal = server_state.stop_signal.clone();
+        handler(
+            &mut server_state,
+            Request {
+                task: Task::Shutdown,
+                secret: Some("s3cret".to_string()),
+                ..Default::default()
+            },
+            client(),
+        );
+        tokio::time::timeout(Duration::ZERO, stop_signal.notified())
+            .await
+            .expect("shutdown was not signaled");
+    }
+
+    /// Tests paging through 25,000 primes with `"fetch"`: pages are capped at
+    /// `MAX_DOWNLOAD_PAGE`, report the total, and reassemble into the full list.
+    #[test]
+    fn test_handler_fetch_pages() {
+        let mut server_state = ServerState::new(2, 1_000_000, DEFAULT_STEP);
+        server_state.primes = primes_up_to(300_000)[..25_000].to_vec();
+        let fetch = |server_state: &mut ServerState, offset: u64, limit: Option<u64>| {
+            handler(
+                server_state,
+                Request {
+                    task: Task::Fetch,
+                    offset: Some(offset),
+                    limit,
+                    ..Default::default()
+                },
+                client(),
+            )
+        };
+
+        let mut fetched = Vec::new();
+        let mut pages = 0;
+        loop {
+            let response = fetch(&mut server_state, fetched.len() as u64, Some(4_000));
+            assert_eq!(response.task, Task::Fetch);
+            assert_eq!(response.total, Some(25_000));
+            assert_eq!(response.offset, Some(fetched.len() as u64));
+            let page = response.primes.unwrap();
+            if page.is_empty() {
+                break;
+            }
+            fetched.extend(page);
+            pages += 1;
+        }
+        assert_eq!(pages, 7);
+        assert_eq!(fetched, server_state.primes);
+
+        let capped = fetch(&mut server_state, 0, None).primes.unwrap();
+        assert_eq!(capped.len() as u64, MAX_DOWNLOAD_PAGE);
+        let past_end = fetch(&mut server_state, 25_001, None);
+        assert_eq!(past_end.task, Task::Error);
+        assert_eq!(past_end.status, "invalid_offset");
+    }
+
+    /// Tests that `"stats"` reports the accepted saves and a positive rate once primes
+    /// were saved.
+    #[test]
+    fn test_handler_stats() {
+        let mut server_state = ServerState::new(2, 100_000, DEFAULT_STEP);
+        let stats = |server_state: &mut ServerState| {
+            handler(
+                server_state,
+                Request {
+                    task: Task::Stats,
+                    ..Default::default()
+                },
+                client(),
+            )
+        };
+        assert_eq!(stats(&mut server_state).primes_per_second, Some(0.0));
+
+        for (start, end) in [(2, 1_002), (1_002, 2_002)] {
+            handler(
+                &mut server_state,
+                Request {
+                    task: Task::Start,
+                    ..Default::default()
+                },
+                client(),
+            );
+            handler(
+                &mut server_state,
+                Request {
+                    task: Task::Save,
+                    start: Some(start),
+                    end: Some(end),
+                    primes: Some(
+                        primes_up_to(end)
+                            .into_iter()
+                            .filter(|&p| p > start)
+                            .collect(),
+                    ),
+                    ..Default::default()
+                },
+                client(),
+            );
+        }
+        std::thread::sleep(Duration::from_millis(5));
+
+        let response = stats(&mut server_state);
+        assert_eq!(response.task, Task::Stats);
+        assert_eq!(response.segments, Some(2));
+        assert_eq!(
+            server_state.primes_saved,
+            primes_up_to(2_002).len() as u64 - 1
+        );
+        assert!(response.primes_per_second.is_some_and(|rate| rate > 0.0));
+        assert!(response.elapsed_ms.is_some_and(|elapsed| elapsed >= 5));
+    }
+
+    /// Tests that `"fetch"` returns the primes and the progress without assigning
+    /// work or moving `last_checked`.
+    #[test]
+    fn test_handler_fetch() {
+        let mut server_state = ServerState::new(2, 100_000, DEFAULT_STEP);
+        handler(
+            &mut server_state,
+            Request {
+                task: Task::Start,
+                ..Default::default()
+            },
+            client(),
+        );
+        handler(
+            &mut server_state,
+            Request {
+                task: Task::Save,
+                start: Some(2),
+                end: Some(1_002),
+                primes: Some(vec![2, 3, 5, 7]),
+                ..Default::default()
+            },
+            client(),
+        );
+        let (last_checked, assigned_up_to) =
+            (server_state.last_checked, server_state.assigned_up_to);
+
+        let response = handler(
+            &mut server_state,
+            Request {
+                task: Task::Fetch,
+                ..Default::default()
+            },
+            client(),
+        );
+
+        assert_eq!(response.task, Task::Fetch);
+        assert!(response
+            .primes
+            .as_ref()
+            .is_some_and(|primes| primes.contains(&7)));
+        assert_eq!(response.primes, Some(server_state.primes.clone()));
+        assert_eq!(response.last_checked, Some(last_checked));
+        assert_eq!(response.end, Some(100_000));
+        assert_eq!(server_state.last_checked, last_checked);
+        assert_eq!(server_state.assigned_up_to, assigned_up_to);
+        assert!(server_state.assignments.assignments.is_empty());
+    }
+
+    /// Tests that the completed frontier stops at the first gap.
+    ///
+    /// Three ranges are assigned but only the first and the third are saved:
+    /// `last_checked` and `assigned_up_to` move past the gap, the frontier doesn't.
+    #[test]
+    fn test_handler_progress_completed_frontier() {
+        let mut server_state = ServerState::new(2, 100_000, DEFAULT_STEP);
+        for _ in 0..3 {
+            handler(
+                &mut server_state,
+                Request {
+                    task: Task::Start,
+                    ..Default::default()
+                },
+                client(),
+            );
+        }
+
+        for (start, end) in [(2, 1_002), (2_002, 3_002)] {
+            handler(
+                &mut server_state,
+                Request {
+                    task: Task::Save,
+                    start: Some(start),
+                    end: Some(end),
+                    primes: Some(vec![]),
+                    ..Default::default()
+                },
+                client(),
+            );
+        }
+
+        let response = handler(
+            &mut server_state,
+            Request {
+                task: Task::Progress,
+                ..Default::default()
+            },
+            client(),
+        );
+
+        assert_eq!(response.task, Task::Progress);
+        assert_eq!(response.completed_frontier, Some(1_002));
+        assert_eq!(response.last_checked, Some(3_002));
+        assert_eq!(response.assigned_up_to, Some(3_002));
+    }
+
+    /// Tests a read replica loaded from a completed run's output file.
+    ///
+    /// The replica must answer `"is_prime"` and `"query_range"` from the file,
+    /// reject queries beyond its range, and refuse to hand out work.
+    #[test]
+    fn test_handler_read_replica_is_prime() {
+        let mut primary = ServerState::new(2, 200, DEFAULT_STEP);
+        primary.output_path =
+            std::env::temp_dir().join(format!("primesocket_replica_{}.txt", std::process::id()));
+        primary.merge_primes(crate::utils::sieve::sieve_segment(
+            2,
+            200,
+            primary.primes.clone(),
+        ));
+        primary.save_primes_to_file().unwrap();
+
+        let mut replica =
+            ServerState::read_replica(primary.output_path.clone(), 10, Some(200)).unwrap();
+        fs::remove_file(&primary.output_path).unwrap();
+
+        let query = |n| Request {
+            task: Task::IsPrime,
+            start: Some(n),
+            ..Default::default()
+        };
+        let response = handler(&mut replica, query(199), client());
+        assert_eq!(response.task, Task::IsPrime);
+        assert_eq!(response.is_prime, Some(true));
+        assert_eq!(
+            handler(&mut replica, query(187), client()).is_prime,
+            Some(false)
+        );
+        assert_eq!(
+            handler(&mut replica, query(211), client()).status,
+            "out_of_range"
+        );
+
+        let response = handler(
+            &mut replica,
+            Request {
+                task: Task::QueryRange,
+                start: Some(180),
+                end: Some(200),
+                ..Default::default()
+            },
+            client(),
+        );
+        assert_eq!(response.primes, Some(vec![181, 191, 193, 197, 199]));
+
+        let response = handler(
+            &mut replica,
+            Request {
+                task: Task::Start,
+                ..Default::default()
+            },
+            client(),
+        );
+        assert_eq!(response.task, Task::Error);
+        assert_eq!(response.status, "read_only");
+    }
+
+    /// Tests that a client is cut off at the per-client cap while others keep working.
+    #[test]
+    fn test_handler_per_client_cap() {
+        let mut server_state = ServerState::new(2, 100_000, DEFAULT_STEP);
+        server_state.max_ranges_per_client = Some(2);
+        let capped = client();
+        let other: SocketAddr = "127.0.0.1:4001".parse().unwrap();
+
+        let work = |server_state: &mut ServerState, client| {
+            let range = handler(
+                server_state,
+                Request {
+                    task: Task::Start,
+                    ..Default::default()
+                },
+                client,
+            );
+            if range.task == Task::Range {
+                handler(
+                    server_state,
+                    Request {
+                        task: Task::Save,
+                        start: range.start,
+                        end: range.end,
+                        primes: Some(Vec::new()),
+                        ..Default::default()
+                    },
+                    client,
+                );
+            }
+            range
+        };
+
+        assert_eq!(work(&mut server_state, capped).task, Task::Range);
+        assert_eq!(work(&mut server_state, capped).task, Task::Range);
+        let response = work(&mut server_state, capped);
+        assert_eq!(response.task, Task::Done);
+        assert_eq!(response.status, "client_limit_reached");
+
+        assert_eq!(work(&mut server_state, other).task, Task::Range);
+        assert_eq!(work(&mut server_state, other).task, Task::Range);
+        assert_eq!(server_state.client_stats[&capped].ranges, 2);
+        assert_eq!(server_state.status, "processing");
+    }
+
+    /// Tests that descending assignment hands out the top of the range first.
+    #[test]
+    fn test_handler_descending_assignment_order() {
+        let mut server_state = ServerState::new(2, 2_500, DEFAULT_STEP);
+        server_state.set_assignment_order(AssignmentOrder::Descending);
+        let start = || Request {
+            task: Task::Start,
+            ..Default::default()
+        };
+
+        let first = handler(&mut server_state, start(), client());
+        assert_eq!((first.start, first.end), (Some(1_500), Some(2_500)));
+
+        let mut response = first;
+        while response.task == Task::Range {
+            let save = Request {
+                task: Task::Save,
+                start: response.start,
+                end: response.end,
+                primes: Some(Vec::new()),
+                ..Default::default()
+            };
+            assert_ne!(handler(&mut server_state, save, client()).task, Task::Error);
+            response = handler(&mut server_state, start(), client());
+        }
+
+        assert_eq!(server_state.status, "completed");
+        assert_eq!(server_state.completed_frontier(), 2_500);
+    }
+
+    /// Tests that the base primes sent with a range don't depend on how far the run got.
+    #[test]
+    fn test_handler_base_primes_stable_across_run() {
+        let mut server_state = ServerState::new(2, 1_000_000, DEFAULT_STEP);
+        let start = || Request {
+            task: Task::Start,
+            ..Default::default()
+        };
+
+        let first = handler(&mut server_state, start(), client());
+        let save = Request {
+            task: Task::Save,
+            start: first.start,
+            end: first.end,
+            primes: Some(sieve_segment(2, 1_002, primes_up_to(100))),
+            ..Default::default()
+        };
+        assert_eq!(
+            handler(&mut server_state, save, client()).task,
+            Task::SaveAck
+        );
+        let second = handler(&mut server_state, start(), client());
+
+        assert_ne!(first.start, second.start);
+        assert_eq!(first.primes, second.primes);
+        assert_eq!(first.primes, Some(primes_up_to(1_001)));
+    }
+
+    /// Tests that concurrent `"start"` requests get distinct ranges, in both orders,
+    /// and that a range whose lease expired is handed out again.
+    #[test]
+    fn test_handler_start_skips_ranges_in_flight() {
+        let other: SocketAddr = "127.0.0.1:4001".parse().unwrap();
+        let start = || Request {
+            task: Task::Start,
+            ..Default::default()
+        };
+
+        let mut server_state = ServerState::new(2, 2_500, DEFAULT_STEP);
+        let first = handler(&mut server_state, start(), client());
+        let second = handler(&mut server_state, start(), other);
+        assert_eq!((first.start, first.end), (Some(2), Some(1_002)));
+        assert_eq!((second.start, second.end), (Some(1_002), Some(2_002)));
+
+        server_state
+            .assignments
+            .assignments
+            .get_mut(&2)
+            .unwrap()
+            .lease_expires = std::time::Instant::now();
+        let third = handler(&mut server_state, start(), other);
+        assert_eq!((third.start, third.end), (Some(2), Some(1_002)));
+        assert_eq!(server_state.client_stats[&client()].expired_leases, 1);
+
+        let mut server_state = ServerState::new(2, 2_500, DEFAULT_STEP);
+        server_state.set_assignment_order(AssignmentOrder::Descending);
+        let first = handler(&mut server_state, start(), client());
+        let second = handler(&mut server_state, start(), other);
+        assert_eq!((first.start, first.end), (Some(1_500), Some(2_500)));
+        assert_eq!((second.start, second.end), (Some(500), Some(1_500)));
+    }
+
+    /// Tests that saving the last range doesn't complete the computation while an
+    /// earlier range is still in flight, which is handed out again instead.
+    #[test]
+    fn test_handler_waits_for_ranges_in_flight() {
+        let other: SocketAddr = "127.0.0.1:4001".parse().unwrap();
+        let start = || Request {
+            task: Task::Start,
+            ..Default::default()
+        };
+
+        let mut server_state = ServerState::new(2, 2_002, DEFAULT_STEP);
+        handler(&mut server_state, start(), client());
+        handler(&mut server_state, start(), other);
+        let save = Request {
+            task: Task::Save,
+            start: Some(1_002),
+            end: Some(2_002),
+            primes: Some(
+                primes_up_to(2_002)
+                    .into_iter()
+                    .filter(|&p| p > 1_002)
+                    .collect(),
+            ),
+            ..Default::default()
+        };
+        handler(&mut server_state, save, other);
+        assert!(!server_state.is_finished());
+        assert_eq!(server_state.status, "processing");
+
+        let again = handler(&mut server_state, start(), other);
+        assert_eq!((again.start, again.end), (Some(2), Some(1_002)));
+    }
+
+    /// Tests that extending the end mid-run makes the output cover the new end.
+    #[test]
+    fn test_handler_extend_mid_run() {
+        let mut server_state = ServerState::new(2, 1_000, 250);
+        server_state.admin_secret = Some("hunter2".to_string());
+
+        let work = |server_state: &mut ServerState| {
+            let range = handler(
+                server_state,
+                Request {
+                    task: Task::Start,
+                    ..Default::default()
+                },
+                client(),
+            );
+            if range.task != Task::Range {
+                return range.task;
+            }
+            let (start, end) = (range.start.unwrap(), range.end.unwrap());
+            let save = Request {
+                task: Task::Save,
+                start: Some(start),
+                end: Some(end),
+                primes: Some(sieve_segment(start, end, range.primes.unwrap())),
+                ..Default::default()
+            };
+            handler(server_state, save, client()).task
+        };
+
+        assert_eq!(work(&mut server_state), Task::SaveAck);
+        assert_eq!(work(&mut server_state), Task::SaveAck);
+
+        let extend = |end| Request {
+            task: Task::Extend,
+            end: Some(end),
+            secret: Some("hunter2".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            handler(&mut server_state, extend(500), client()).status,
+            "invalid_range"
+        );
+        assert_eq!(
+            handler(&mut server_state, extend(2_000), client()).task,
+            Task::Extended
+        );
+
+        while work(&mut server_state) == Task::SaveAck {}
+
+        assert_eq!(server_state.status, "completed");
+        assert_eq!(server_state.end, 2_000);
+        assert_eq!(server_state.primes, primes_up_to(2_000));
+    }
+
+    /// Tests that a step larger than the whole range completes after a single range.
+    #[test]
+    fn test_handler_step_larger_than_range() {
+        let mut server_state = ServerState::new(2, 50, DEFAULT_STEP);
+        assert_eq!(server_state.step, 1000);
+        let start = || Request {
+            task: Task::Start,
+            ..Default::default()
+        };
+
+        let range = handler(&mut server_state, start(), client());
+        assert_eq!((range.start, range.end), (Some(2), Some(50)));
+
+        let save = Request {
+            task: Task::Save,
+            start: range.start,
+            end: range.end,
+            primes: Some(sieve_segment(2, 50, range.primes.unwrap())),
+            ..Default::default()
+        };
+        assert_eq!(handler(&mut server_state, save, client()).task, Task::Done);
+        assert_eq!(
+            handler(&mut server_state, start(), client()).task,
+            Task::Done
+        );
+        assert_eq!(server_state.client_stats[&client()].ranges, 1);
+        assert_eq!(server_state.primes, primes_up_to(50));
+    }
+
+    /// Tests that a save holding more primes than its range can contain is rejected.
+    #[test]
+    fn test_handler_rejects_implausible_prime_count() {
+        let mut server_state = ServerState::new(2, 10_000, DEFAULT_STEP);
+        let save = |primes: Vec<u64>| Request {
+            task: Task::Save,
+            start: Some(1_000),
+            end: Some(1_100),
+            primes: Some(primes),
+            ..Default::default()
+        };
+
+        let response = handler(&mut server_state, save((1_000..1_100).collect()), client());
+        assert_eq!(response.task, Task::Error);
+        assert_eq!(response.status, "implausible_prime_count");
+        assert_eq!(server_state.last_checked, 2);
+        assert!(!server_state.primes.contains(&1_000));
+
+        let primes = sieve_segment(1_000, 1_100, primes_up_to(100));
+        assert_eq!(
+            handler(&mut server_state, save(primes), client()).task,
+            Task::SaveAck
+        );
+    }
+
+    /// Tests that out-of-order primes are sorted by default and rejected when configured.
+    ///
+    /// Primes outside the submitted range are always rejected.
+    #[test]
+    fn test_handler_unsorted_save() {
+        let save = |primes: Vec<u64>| Request {
+            task: Task::Save,
+            start: Some(100),
+            end: Some(130),
+            primes: Some(primes),
+            ..Default::default()
+        };
+        let shuffled = vec![113, 101, 127, 103, 109, 107, 101];
+
+        let mut server_state = ServerState::new(2, 10_000, DEFAULT_STEP);
+        let response = handler(&mut server_state, save(vec![101, 97]), client());
+        assert_eq!(response.status, "out_of_range");
+
+        let response = handler(&mut server_state, save(shuffled.clone()), client());
+        assert_eq!(response.task, Task::SaveAck);
+        assert!(server_state
+            .primes
+            .ends_with(&[101, 103, 107, 109, 113, 127]));
+        assert_eq!(server_state.client_stats[&client()].primes, 6);
+
+        let mut server_state = ServerState::new(2, 10_000, DEFAULT_STEP);
+        server_state.unsorted_policy = UnsortedPolicy::Reject;
+        let response = handler(&mut server_state, save(shuffled), client());
+        assert_eq!(response.task, Task::Error);
+        assert_eq!(response.status, "unsorted_primes");
+        assert!(!server_state.primes.contains(&101));
+        assert_eq!(server_state.last_checked, 2);
+    }
+
+    /// Tests that overlapping saves are merged into the union of their primes and counted,
+    /// and rejected when configured.
+    #[test]
+    fn test_handler_overlapping_saves() {
+        let save = |start: u64, end: u64| Request {
+            task: Task::Save,
+            start: Some(start),
+            end: Some(end),
+            primes: Some(sieve_segment(start, end, primes_up_to(40))),
+            ..Default::default()
+        };
+        let progress = Request {
+            task: Task::Progress,
+            ..Default::default()
+        };
+
+        let mut server_state = ServerState::new(2, 10_000, DEFAULT_STEP);
+        handler(&mut server_state, save(2, 1_000), client());
+        handler(&mut server_state, save(1_000, 1_200), client());
+        handler(&mut server_state, save(2, 1_000), client());
+        assert_eq!(server_state.overlapping_saves, 0);
+
+        let response = handler(&mut server_state, save(500, 1_500), client());
+        assert_eq!(response.task, Task::SaveAck);
+        assert_eq!(server_state.primes, primes_up_to(1_500));
+        let response = handler(&mut server_state, progress, client());
+        assert_eq!(response.overlapping_saves, Some(1));
+
+        let mut server_state = ServerState::new(2, 10_000, DEFAULT_STEP);
+        server_state.overlap_policy = OverlapPolicy::Reject;
+        handler(&mut server_state, save(2, 1_000), client());
+        let response = handler(&mut server_state, save(500, 1_500), client());
+        assert_eq!(response.task, Task::Error);
+        assert_eq!(response.status, "overlapping_range");
+        assert_eq!(server_state.overlapping_saves, 1);
+        assert_eq!(server_state.primes, primes_up_to(1_000));
+        assert_eq!(server_state.last_checked, 1_000);
+    }
+
+    /// Tests that an unknown task string is answered with an error.
+    #[test]
+    fn test_handler_unknown_task() {
+        let mut server_state = ServerState::new(2, 100, DEFAULT_STEP);
+        let request = Request::from_json(r#"{"task":"launch_missiles"}"#).unwrap();
+        assert_eq!(request.task, Task::Unknown);
+
+        let response = handler(&mut server_state, request, client());
+        assert_eq!(response.task, Task::Error);
+        assert_eq!(response.status, "invalid_task");
+    }
 }
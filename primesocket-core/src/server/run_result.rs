@@ -0,0 +1,84 @@
+use super::computation_summary::ComputationSummary;
+use super::server_state::ServerState;
+use pyo3::prelude::*;
+use std::time::Duration;
+
+/// Summarizes a finished server run for Python callers.
+///
+/// The `RunResult` is returned by `start_server` once the computation is over,
+/// giving typed access to the outcome instead of an opaque success value.
+///
+/// # Fields
+///
+/// * `count` - The number of primes identified.
+/// * `largest` - The largest prime identified, if any.
+/// * `duration` - The wall-clock duration of the run, in seconds.
+/// * `path` - The file where the primes were written.
+/// * `sum` - The sum of the primes, in sum-only mode; `largest` is unknown then.
+/// * `summary` - How the computation went: its range, segments and clients.
+///
+/// # Example (Python)
+///
+/// ```python
+/// import primesocket_core
+/// result = primesocket_core.start_server(8080, 1000)
+/// print(result.count, result.largest, result.duration, result.path)
+/// print(result.summary.clients, result.summary.segments)
+/// ```
+#[pyclass(get_all, frozen)]
+#[derive(Clone, Debug)]
+pub struct RunResult {
+    pub count: u64,
+    pub largest: Option<u64>,
+    pub duration: f64,
+    pub path: String,
+    pub sum: Option<u128>,
+    pub summary: ComputationSummary,
+}
+
+impl RunResult {
+    /// Builds a `RunResult` from the final server state.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The server state at the end of the run.
+    /// * `duration` - How long the run took.
+    ///
+    /// # Returns
+    ///
+    /// A `RunResult` describing the primes held by `state`.
+    pub fn from_state(state: &ServerState, duration: Duration) -> RunResult {
+        if state.sum_only {
+            return RunResult {
+                count: state.prime_count,
+                largest: None,
+                duration: duration.as_secs_f64(),
+                path: state.output_path.display().to_string(),
+                sum: Some(state.prime_sum),
+                summary: ComputationSummary::from_state(state, duration),
+            };
+        }
+        RunResult {
+            count: state.primes.len() as u64,
+            largest: state.primes.iter().max().cloned(),
+            duration: duration.as_secs_f64(),
+            path: state.output_path.display().to_string(),
+            sum: None,
+            summary: ComputationSummary::from_state(state, duration),
+        }
+    }
+}
+
+#[pymethods]
+impl RunResult {
+    fn __repr__(&self) -> String {
+        format!(
+            "RunResult(count={}, largest={}, duration={:.3}, path={:?}, sum={})",
+            self.count,
+            self.largest.map_or("None".to_string(), |p| p.to_string()),
+            self.duration,
+            self.path,
+            self.sum.map_or("None".to_string(), |s| s.to_string())
+        )
+    }
+}
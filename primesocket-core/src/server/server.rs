@@ -1,16 +1,49 @@
+use super::assignment::AssignmentOrder;
+use super::debug_state::DebugSnapshot;
+use super::progress::{ProgressEvent, ProgressReporter, PROGRESS_QUEUE_CAPACITY};
+use super::rate_limit::RateLimiter;
 use super::response_handler::handler;
-use super::server_state::ServerState;
-use crate::utils::json::{Request, Response};
+use super::run_result::RunResult;
+use super::server_config::ServerConfig;
+use super::server_handle::ServerHandle;
+use super::server_options::ServerOptions;
+use super::server_state::{OutputFormat, OverlapPolicy, ServerState, UnsortedPolicy, DEFAULT_STEP};
+use crate::utils::chunk::{
+    negotiate_datagram_size, recv_message, send_message_paced, Reassembler, MAX_DATAGRAM_SIZE,
+};
+use crate::utils::codec::WireFormat;
+use crate::utils::json::{Request, Response, Task};
+use crate::utils::logging;
+use crate::utils::primality::gcd;
+use log::{debug, error, info, log_enabled, trace, warn, Level};
 use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
-use std::collections::HashSet;
+use pyo3::types::PyDict;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
 use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Duration;
+use std::thread;
+use std::time::{Duration, Instant};
 use tokio::net::UdpSocket;
 use tokio::runtime::Builder;
-use tokio::sync::{mpsc, Mutex};
-use tokio::time::sleep;
+use tokio::sync::{mpsc, Mutex, MutexGuard};
+use tokio::time::{interval, sleep, sleep_until, timeout, MissedTickBehavior};
+
+/// How many times the response sender is restarted before the server gives up.
+const MAX_SENDER_RESTARTS: u32 = 5;
+
+/// The longest delay between two sweeps for expired leases.
+const LEASE_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long a spawned server waits for its runtime's tasks to finish once it returned.
+const RUNTIME_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The queue of responses waiting to be sent: payload, destination and datagram size.
+type ResponseQueue = Arc<Mutex<mpsc::Receiver<(Vec<u8>, SocketAddr, usize)>>>;
 
 /// Starts a UDP server for processing client requests.
 ///
@@ -22,19 +55,162 @@ use tokio::time::sleep;
 ///
 /// * `port` - The UDP port where the server will listen.
 /// * `end` - The ending value of the number range to be processed (mandatory).
-/// * `verbose` - (Optional) Verbosity level for logging.
+/// * `verbose` - (Optional) Verbosity level for logging: `0` (default) logs warnings and
+///   errors, `1` adds progress, `2` every message and `3` keepalives. Records go through the
+///   `log` crate, to stderr unless the embedding application installed a logger.
+/// * `grace_period_ms` - (Optional) How long, in milliseconds, late submissions are
+///   still merged after the computation completes. Defaults to `0`.
+/// * `admin_secret` - (Optional) Shared secret enabling admin tasks such as `"flush"`.
+/// * `bind_retries` - (Optional) How many times to retry binding the port if it is busy.
+///   Defaults to `0`.
+/// * `bind_retry_delay_ms` - (Optional) Delay between bind attempts, in milliseconds.
+///   Defaults to `500`.
+/// * `output_radix` - (Optional) Base of the primes in the output file: `10` (default) or `16`.
+///   JSON output is always decimal.
+/// * `progress_callback` - (Optional) Python callable invoked as `callback(last_checked, end)`
+///   each time `last_checked` advances. It runs on a dedicated thread fed by a bounded queue;
+///   when it falls behind, the oldest pending events are dropped.
+/// * `read_only_from` - (Optional) Path of an output file to serve as a read replica. The
+///   server then answers read tasks (`"is_prime"`, `"query_range"`, `"progress"`) over
+///   `[2, end]`, `end` defaulting to the largest prime in the file, and hands out no work.
+/// * `max_ranges_per_client` - (Optional) How many ranges a single client address may save;
+///   once reached, that client is told `"done"` while others continue. Unlimited by default.
+/// * `residue` - (Optional) A residue class `(a, m)` with `gcd(a, m) == 1`: only primes
+///   congruent to `a` modulo `m` are computed and saved.
+/// * `assignment_order` - (Optional) `"ascending"` (default) hands out ranges from the low
+///   end of `[2, end]`; `"descending"` starts from the high end so big numbers start early.
+/// * `prime_count_tolerance` - (Optional) Factor applied to the theoretical maximum number of
+///   primes in a range; saves holding more primes are rejected. Must be finite and at least
+///   `1.0`, its default.
+/// * `pi_interval` - (Optional) Records `π(x)` at every multiple `x` of this interval as the
+///   computation progresses, written as `x,pi_x` CSV rows when it finishes.
+/// * `pi_output_path` - (Optional) The CSV file for the `π(x)` values. Defaults to `pi.csv`.
+/// * `keepalive_interval_ms` - (Optional) When set, clients holding an assignment receive a
+///   tiny keepalive datagram at this interval, keeping NAT mappings open. Disabled by default.
+/// * `on_complete_cmd` - (Optional) Shell command run once the computation finishes and the
+///   results are saved. The output path is passed as `$1` and as `PRIMESOCKET_OUTPUT_PATH`.
+/// * `ranges` - (Optional) A list of `(start, end)` ranges to compute the primes of, in a single
+///   run; `end` is ignored then. The output holds the primes of their union, sorted.
+/// * `unsorted_primes` - (Optional) What to do with a `"save"` whose primes are out of
+///   ascending order: `"sort"` (default) sorts them before storing, `"reject"` rejects it.
+/// * `debug_state_path` - (Optional) File where snapshots of the outstanding ranges, leases
+///   and per-client stats are appended as JSON lines, for debugging stalled runs. The file
+///   is rotated to `<path>.1` past 10 MiB.
+/// * `debug_state_interval_ms` - (Optional) How often debug snapshots are taken, in
+///   milliseconds. Defaults to `10000`.
+/// * `send_rate` - (Optional) The most datagrams the server sends per second. Responses are
+///   paced to stay under it instead of sent in bursts. Unlimited by default.
+/// * `dual_stack` - (Optional) When `true`, listens on `[::]` for both IPv6 and IPv4 clients
+///   instead of on `0.0.0.0` only. IPv4 clients then show up as IPv4-mapped IPv6 addresses.
+///   Defaults to `false`.
+/// * `assignment_unit` - (Optional) The size of the ranges handed out to clients. Clients
+///   sieve them in buffers of their own (see `stream_block_size` in `start_client`), so a
+///   large unit cuts round trips without growing client memory. Defaults to `1000`.
+/// * `step` - (Optional) An alias of `assignment_unit`; only one of them may be given.
+/// * `max_expired_leases` - (Optional) How many leases a single client address may let expire
+///   before it is quarantined: it gets no more work, but its late saves are still accepted.
+///   Unlimited by default.
+/// * `output` - (Optional) The file the primes are written to. Defaults to `primes.txt` in
+///   the working directory; set it when several servers run side by side.
+/// * `resume` - (Optional) A checkpoint file to pick up an interrupted computation from; `end`
+///   and `ranges` are taken from the checkpoint then. Checkpoints keep being written there
+///   unless `checkpoint_path` says otherwise.
+/// * `checkpoint_path` - (Optional) Where to write checkpoints of the computation: every
+///   `checkpoint_every` accepted saves and when the server stops.
+/// * `checkpoint_every` - (Optional) How many accepted saves separate two checkpoints.
+///   Defaults to `10`.
+/// * `checkpoint_segment_size` - (Optional) Writes checkpoints as a directory of segment files,
+///   each covering this many values, plus a checksummed manifest. Only the segments that
+///   changed are rewritten, and a directory given to `resume` is loaded in parallel.
+/// * `bloom_output` - (Optional) A file to write a Bloom filter of the primes to, alongside
+///   the full list, for fast probabilistic membership queries. See `utils::bloom`.
+/// * `bloom_false_positive_rate` - (Optional) The false-positive rate the Bloom filter is
+///   sized for. Defaults to `0.01`.
+/// * `lease_timeout_seconds` - (Optional) How long a client may hold a range without saving
+///   it before the range is handed to another client. Defaults to `120`.
+/// * `sum_only` - (Optional) When `True`, clients send the sum and count of the primes of
+///   their ranges instead of the list; the output file and the result hold the total sum.
+///   Queries answered from the list (`"is_prime"`, `"query_range"`, `"fetch"` and
+///   `"download"`) are refused.
+/// * `overlapping_ranges` - (Optional) What to do with a `"save"` whose range partially
+///   overlaps ranges saved before: `"merge"` (default) keeps the union of the primes,
+///   `"reject"` rejects it. Overlaps are counted and reported by `"progress"` either way.
+/// * `format` - (Optional) The encoding of requests and responses: `"json"` (default), or
+///   `"bincode"` when built with the `binary` feature. Clients must use the same format.
+/// * `shard` - (Optional) A `(shard_index, shard_count)` pair to run one of several servers
+///   splitting the computation: this server only hands out its slice of the range, and
+///   writes to the output file with `.shard<index>` inserted before the extension. The
+///   slices of all indices cover the range with no gaps. A resumed checkpoint already
+///   holds its slice, so only the output name is derived from `shard` then.
+/// * `output_format` - (Optional) The layout of the output file: `"text"` (default) with
+///   one prime per line, `"json"` with a single array of numbers, or `"csv"` with a
+///   `prime` header line. Named apart from `format`, which selects the wire encoding.
+/// * `bind_addr` - (Optional) The IP address of the interface to listen on, such as
+///   `"127.0.0.1"` to only serve local clients. Defaults to `"0.0.0.0"`, or `"::"` with
+///   `dual_stack`; an explicit address with `dual_stack` must be IPv6.
+/// * `idle_timeout_seconds` - (Optional) When no message arrives for this long, the server
+///   saves the primes found so far and shuts down, as after a `"shutdown"`. Disabled by
+///   default, so a server left without clients waits for them indefinitely.
+/// * `max_requests_per_second` - (Optional) The most requests per second accepted from each
+///   client address, after a burst of as many. Requests over the limit are answered with a
+///   `"rate_limited"` error without reaching the state. Unlimited by default.
+/// * `seed_primes` - (Optional) Every prime up to some bound, in ascending order, known
+///   before the run (e.g. all primes below `sqrt(end)`). They start the output and the base
+///   primes sent to clients, which are only computed above them. Defaults to the primes
+///   below 100; ignored when resuming or serving a replica.
+/// * `verify` - (Optional) When `True`, every prime found is checked with a primality test
+///   once the computation completes, and any composite is logged as an error. The primes
+///   are saved either way. Defaults to `False`.
+/// * `checksum` - (Optional) When `True`, every datagram starts with a CRC-32 of its
+///   content, and received datagrams that don't match are dropped as corrupted. Clients
+///   must use the same setting. Defaults to `False`.
+///
+/// Every argument after `verbose` is keyword-only; they are collected into `ServerOptions`.
+///
+/// # Returns
+///
+/// A `RunResult` summarizing the primes found once the computation finishes.
 ///
 /// # Errors
 ///
-/// This function returns a `PyValueError` if the `end` parameter is not provided.
-#[pyfunction(signature = (port, end=None, verbose=None))]
-pub fn start_server(port: u16, end: Option<u32>, verbose: Option<u8>) -> PyResult<()> {
-    let verbose = verbose.unwrap_or(0);
-    let start = 2;
-    let end = match end {
-        Some(e) => e,
-        None => return Err(PyErr::new::<PyValueError, _>("Parameter 'end' is required")),
-    };
+/// This function returns a `PyValueError` if `port` is `0`, if neither `end` nor valid `ranges` are
+/// provided, if `seed_primes` are not every prime up to the largest of them, if `output_radix` is
+/// not supported, if `read_only_from` can't be loaded, if `residue` is not a valid residue class,
+/// if `assignment_order`, `unsorted_primes`, `overlapping_ranges`, `format` or `output_format` is
+/// unknown, if `bind_addr` is not an IP address, or is IPv4 along with `dual_stack`, if `shard` is
+/// not a non-empty shard of the range, if `step` and `assignment_unit` are both given, if
+/// `send_rate`, `assignment_unit`, `step`, `checkpoint_every`, `checkpoint_segment_size`,
+/// `lease_timeout_seconds`, `idle_timeout_seconds` or `max_requests_per_second` is `0`, if
+/// `bloom_false_positive_rate` is not strictly between `0` and `1`, if `prime_count_tolerance` is
+/// not a finite number of at least `1`, if `resume` can't be loaded, or if the server fails while
+/// running. An unknown keyword argument raises a `TypeError`.
+#[pyfunction(signature = (port, end=None, verbose=None, **options))]
+pub fn start_server(
+    py: Python<'_>,
+    port: u16,
+    end: Option<u64>,
+    verbose: Option<u8>,
+    options: Option<&Bound<'_, PyDict>>,
+) -> PyResult<RunResult> {
+    let options = ServerOptions::from_kwargs(py, options)?;
+    start_with_options(py, port, end, verbose, options)
+}
+
+/// Runs a server until the computation finishes, as `start_server` does, from options
+/// already collected.
+///
+/// # Errors
+///
+/// This function returns a `PyValueError` for the invalid arguments listed by
+/// `start_server`, or if the server fails while running.
+fn start_with_options(
+    py: Python<'_>,
+    port: u16,
+    end: Option<u64>,
+    verbose: Option<u8>,
+    options: ServerOptions,
+) -> PyResult<RunResult> {
+    let (config, server_state) = configure_server(port, end, verbose, options)?;
 
     // Create a multi-threaded runtime
     let rt = Builder::new_multi_thread()
@@ -44,15 +220,392 @@ pub fn start_server(port: u16, end: Option<u32>, verbose: Option<u8>) -> PyResul
             PyErr::new::<PyValueError, _>(format!("Failed to create Tokio runtime: {}", e))
         })?;
 
-    rt.block_on(async move {
-        if let Err(e) = run_server(port, start, end, verbose).await {
-            if verbose > 0 {
-                eprintln!("❌ Server encountered an error: {:?}", e);
+    // Release the GIL while serving so the progress callback can run.
+    py.allow_threads(|| {
+        rt.block_on(async move {
+            run_server(config, server_state).await.inspect_err(|e| {
+                error!("❌ Server encountered an error: {:?}", e);
+            })
+        })
+    })
+}
+
+/// Starts a UDP server on a background thread and returns a handle to control it.
+///
+/// The server runs exactly as with `start_server`, on a dedicated thread owning its
+/// Tokio runtime, so the call returns right away. The handle stops the server, reports
+/// whether it is still running, and gives its `RunResult` once it ended; the runtime is
+/// shut down when the server returns.
+///
+/// # Arguments
+///
+/// Takes the same arguments as `start_server`.
+///
+/// # Returns
+///
+/// A `ServerHandle` for the running server; used as a context manager, the server is
+/// stopped when the `with` block exits.
+///
+/// # Errors
+///
+/// This function returns a `PyValueError` for the invalid arguments listed by
+/// `start_server`, or if the runtime or the thread can't be created. Errors of the
+/// running server, such as a failed bind, are returned by `ServerHandle.result()`.
+#[pyfunction(signature = (port, end=None, verbose=None, **options))]
+pub fn spawn_server(
+    py: Python<'_>,
+    port: u16,
+    end: Option<u64>,
+    verbose: Option<u8>,
+    options: Option<&Bound<'_, PyDict>>,
+) -> PyResult<ServerHandle> {
+    let options = ServerOptions::from_kwargs(py, options)?;
+    spawn_with_options(port, end, verbose, options)
+}
+
+/// Starts a server on a background thread, as `spawn_server` does, from options
+/// already collected.
+///
+/// # Errors
+///
+/// This function returns a `PyValueError` for the invalid arguments listed by
+/// `start_server`, or if the runtime or the thread can't be created.
+fn spawn_with_options(
+    port: u16,
+    end: Option<u64>,
+    verbose: Option<u8>,
+    options: ServerOptions,
+) -> PyResult<ServerHandle> {
+    let (config, server_state) = configure_server(port, end, verbose, options)?;
+    let stop_requested = config.stop_requested.clone();
+
+    let rt = Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| {
+            PyErr::new::<PyValueError, _>(format!("Failed to create Tokio runtime: {}", e))
+        })?;
+    let thread = thread::Builder::new()
+        .name("primesocket-server".to_string())
+        .spawn(move || {
+            let result = rt.block_on(async move {
+                run_server(config, server_state).await.inspect_err(|e| {
+                    error!("❌ Server encountered an error: {:?}", e);
+                })
+            });
+            // Tasks left behind, like the response sender, are dropped with the runtime.
+            rt.shutdown_timeout(RUNTIME_SHUTDOWN_TIMEOUT);
+            result
+        })
+        .map_err(|e| {
+            PyErr::new::<PyValueError, _>(format!("Failed to spawn server thread: {}", e))
+        })?;
+    Ok(ServerHandle::new(stop_requested, thread))
+}
+
+/// Builds the settings and the initial state of a server from the arguments of
+/// `start_server`, validating them.
+///
+/// # Returns
+///
+/// The `ServerConfig` and `ServerState` to hand to `run_server`.
+///
+/// # Errors
+///
+/// This function returns a `PyValueError` for the invalid arguments listed by `start_server`.
+fn configure_server(
+    port: u16,
+    end: Option<u64>,
+    verbose: Option<u8>,
+    options: ServerOptions,
+) -> PyResult<(ServerConfig, ServerState)> {
+    let ServerOptions {
+        grace_period_ms,
+        admin_secret,
+        bind_retries,
+        bind_retry_delay_ms,
+        output_radix,
+        progress_callback,
+        read_only_from,
+        max_ranges_per_client,
+        residue,
+        assignment_order,
+        prime_count_tolerance,
+        pi_interval,
+        pi_output_path,
+        keepalive_interval_ms,
+        on_complete_cmd,
+        ranges,
+        unsorted_primes,
+        debug_state_path,
+        debug_state_interval_ms,
+        send_rate,
+        dual_stack,
+        assignment_unit,
+        output,
+        max_expired_leases,
+        resume,
+        checkpoint_path,
+        checkpoint_every,
+        checkpoint_segment_size,
+        bloom_output,
+        bloom_false_positive_rate,
+        lease_timeout_seconds,
+        sum_only,
+        overlapping_ranges,
+        format,
+        shard,
+        output_format,
+        bind_addr,
+        idle_timeout_seconds,
+        max_requests_per_second,
+        seed_primes,
+        verify,
+        checksum,
+        step,
+    } = options;
+    if port == 0 {
+        return Err(PyErr::new::<PyValueError, _>(
+            "port must be between 1 and 65535",
+        ));
+    }
+    let verbose = verbose.unwrap_or(0);
+    let mut config = ServerConfig::new(port, verbose);
+    if let Some(callback) = progress_callback {
+        config.progress_callback = Some(Arc::new(move |event: ProgressEvent| {
+            Python::with_gil(|py| {
+                if let Err(e) = callback.call1(py, (event.last_checked, event.end)) {
+                    e.print(py);
+                }
+            });
+        }));
+    }
+    if let Some(retries) = bind_retries {
+        config.bind_retries = retries;
+    }
+    if let Some(delay) = bind_retry_delay_ms {
+        config.bind_retry_delay = Duration::from_millis(delay);
+    }
+    if let Some(format) = format {
+        config.format = WireFormat::from_name(&format).ok_or_else(|| {
+            PyErr::new::<PyValueError, _>(format!("Unknown wire format '{}'", format))
+        })?;
+    }
+    config.keepalive_interval = keepalive_interval_ms.map(Duration::from_millis);
+    config.on_complete_cmd = on_complete_cmd;
+    config.verify = verify.unwrap_or(false);
+    config.checksum = checksum.unwrap_or(false);
+    config.debug_state_path = debug_state_path.map(PathBuf::from);
+    if let Some(interval) = debug_state_interval_ms {
+        config.debug_state_interval = Duration::from_millis(interval);
+    }
+    if send_rate == Some(0) {
+        return Err(PyErr::new::<PyValueError, _>(
+            "Invalid send rate 0 (expected at least 1 datagram per second)",
+        ));
+    }
+    config.send_rate = send_rate;
+    config.dual_stack = dual_stack.unwrap_or(false);
+    if let Some(addr) = bind_addr {
+        let ip = addr.parse::<IpAddr>().map_err(|_| {
+            PyErr::new::<PyValueError, _>(format!("Invalid bind address '{}'", addr))
+        })?;
+        if config.dual_stack && ip.is_ipv4() {
+            return Err(PyErr::new::<PyValueError, _>(format!(
+                "Invalid bind address '{}' (dual_stack requires an IPv6 address)",
+                addr
+            )));
+        }
+        config.bind_addr = Some(ip);
+    }
+    if idle_timeout_seconds == Some(0) {
+        return Err(PyErr::new::<PyValueError, _>(
+            "Invalid idle timeout 0 (expected at least 1 second)",
+        ));
+    }
+    config.idle_timeout = idle_timeout_seconds.map(Duration::from_secs);
+    if max_requests_per_second == Some(0) {
+        return Err(PyErr::new::<PyValueError, _>(
+            "Invalid request rate 0 (expected at least 1 request per second)",
+        ));
+    }
+    config.max_requests_per_second = max_requests_per_second;
+
+    let output_radix = match output_radix.unwrap_or(10) {
+        radix @ (10 | 16) => radix,
+        radix => {
+            return Err(PyErr::new::<PyValueError, _>(format!(
+                "Unsupported output radix {} (expected 10 or 16)",
+                radix
+            )))
+        }
+    };
+
+    let seed_primes = seed_primes
+        .map(|seeds| {
+            ServerState::check_seed_primes(&seeds)
+                .map(|_| seeds)
+                .map_err(|e| PyErr::new::<PyValueError, _>(format!("Invalid seed primes: {}", e)))
+        })
+        .transpose()?;
+
+    let start = 2;
+    let mut server_state = match (read_only_from, end) {
+        (Some(path), end) => ServerState::read_replica(PathBuf::from(&path), output_radix, end)
+            .map_err(|e| {
+                PyErr::new::<PyValueError, _>(format!("Failed to load '{}': {}", path, e))
+            })?,
+        (None, _) if resume.is_some() => {
+            let path = Path::new(resume.as_deref().unwrap());
+            let loaded = if path.is_dir() {
+                ServerState::load_chunked_checkpoint(path)
+            } else {
+                ServerState::load_checkpoint(path)
+            };
+            loaded.map_err(|e| {
+                PyErr::new::<PyValueError, _>(format!(
+                    "Failed to resume from '{}': {}",
+                    path.display(),
+                    e
+                ))
+            })?
+        }
+        (None, _) if ranges.is_some() => {
+            let ranges = ranges.unwrap();
+            if ranges.is_empty() || ranges.iter().any(|&(low, high)| low < 2 || low > high) {
+                return Err(PyErr::new::<PyValueError, _>(format!(
+                    "Invalid ranges {:?} (expected non-empty (start, end) pairs with 2 <= start <= end)",
+                    ranges
+                )));
             }
+            let mut server_state =
+                ServerState::with_seed_primes(start, start, DEFAULT_STEP, seed_primes);
+            server_state.restrict_to_ranges(&ranges);
+            server_state
         }
-    });
+        (None, Some(end)) => ServerState::with_seed_primes(start, end, DEFAULT_STEP, seed_primes),
+        (None, None) => {
+            return Err(PyErr::new::<PyValueError, _>(
+                "Parameter 'end' or 'ranges' is required",
+            ))
+        }
+    };
+    server_state.grace_period = Duration::from_millis(grace_period_ms.unwrap_or(0));
+    server_state.admin_secret = admin_secret;
+    server_state.output_radix = output_radix;
+    if let Some(name) = output_format {
+        server_state.output_format = OutputFormat::from_name(&name).ok_or_else(|| {
+            PyErr::new::<PyValueError, _>(format!("Unknown output format '{}'", name))
+        })?;
+    }
+    if let Some(path) = output {
+        server_state.output_path = PathBuf::from(path);
+    }
+    if let Some((index, count)) = shard {
+        if index >= count || (resume.is_none() && !server_state.restrict_to_shard(index, count)) {
+            return Err(PyErr::new::<PyValueError, _>(format!(
+                "Invalid shard ({}, {}) (expected an index below the count, holding some numbers)",
+                index, count
+            )));
+        }
+        server_state.output_path = shard_output_path(&server_state.output_path, index);
+    }
+    server_state.max_ranges_per_client = max_ranges_per_client;
+    server_state.max_expired_leases = max_expired_leases;
+    server_state.checkpoint_path = checkpoint_path.or(resume).map(PathBuf::from);
+    match checkpoint_every {
+        Some(0) => {
+            return Err(PyErr::new::<PyValueError, _>(
+                "Invalid checkpoint interval 0 (expected at least 1 save)",
+            ))
+        }
+        Some(every) => server_state.checkpoint_every = every,
+        None => {}
+    }
+    if checkpoint_segment_size == Some(0) {
+        return Err(PyErr::new::<PyValueError, _>(
+            "Invalid checkpoint segment size 0 (expected a positive range size)",
+        ));
+    }
+    server_state.checkpoint_segment_size = checkpoint_segment_size;
+    server_state.sum_only = server_state.sum_only || sum_only.unwrap_or(false);
+    match lease_timeout_seconds {
+        Some(0) => {
+            return Err(PyErr::new::<PyValueError, _>(
+                "Invalid lease timeout 0 (expected at least 1 second)",
+            ))
+        }
+        Some(seconds) => server_state.assignments.lease_duration = Duration::from_secs(seconds),
+        None => {}
+    }
+    let assignment_unit = match (assignment_unit, step) {
+        (Some(_), Some(_)) => {
+            return Err(PyErr::new::<PyValueError, _>(
+                "Parameters 'step' and 'assignment_unit' are aliases (expected only one)",
+            ))
+        }
+        (unit, step) => unit.or(step.map(u64::from)),
+    };
+    match assignment_unit {
+        Some(0) => {
+            return Err(PyErr::new::<PyValueError, _>(
+                "Invalid assignment unit 0 (expected a positive range size)",
+            ))
+        }
+        Some(unit) => server_state.step = unit,
+        None => {}
+    }
+    server_state.pi_interval = pi_interval;
+    if let Some(path) = pi_output_path {
+        server_state.pi_output_path = PathBuf::from(path);
+    }
+    if let Some(tolerance) = prime_count_tolerance {
+        // Below 1, even a correct save is implausible and the run could never finish.
+        if !(tolerance.is_finite() && tolerance >= 1.0) {
+            return Err(PyErr::new::<PyValueError, _>(format!(
+                "Invalid prime count tolerance {} (expected a finite factor of at least 1)",
+                tolerance
+            )));
+        }
+        server_state.prime_count_tolerance = tolerance;
+    }
+    if let Some(order) = assignment_order {
+        let order = AssignmentOrder::from_name(&order).ok_or_else(|| {
+            PyErr::new::<PyValueError, _>(format!("Unknown assignment order '{}'", order))
+        })?;
+        server_state.set_assignment_order(order);
+    }
+    if let Some(policy) = unsorted_primes {
+        server_state.unsorted_policy = UnsortedPolicy::from_name(&policy).ok_or_else(|| {
+            PyErr::new::<PyValueError, _>(format!("Unknown unsorted primes policy '{}'", policy))
+        })?;
+    }
+    if let Some(policy) = overlapping_ranges {
+        server_state.overlap_policy = OverlapPolicy::from_name(&policy).ok_or_else(|| {
+            PyErr::new::<PyValueError, _>(format!("Unknown overlapping ranges policy '{}'", policy))
+        })?;
+    }
+    if let Some((a, m)) = residue {
+        if m == 0 || gcd(a, m) != 1 {
+            return Err(PyErr::new::<PyValueError, _>(format!(
+                "Invalid residue class {} mod {} (expected gcd(a, m) == 1)",
+                a, m
+            )));
+        }
+        server_state.restrict_to_residue(a % m, m);
+    }
+    if let Some(path) = bloom_output {
+        let rate = bloom_false_positive_rate.unwrap_or(0.01);
+        if !(rate > 0.0 && rate < 1.0) {
+            return Err(PyErr::new::<PyValueError, _>(format!(
+                "Invalid Bloom filter false-positive rate {} (expected 0 < rate < 1)",
+                rate
+            )));
+        }
+        server_state.enable_bloom(PathBuf::from(path), rate);
+    }
 
-    Ok(())
+    Ok((config, server_state))
 }
 
 /// Runs the UDP server and processes client requests.
@@ -61,138 +614,2463 @@ pub fn start_server(port: u16, end: Option<u32>, verbose: Option<u8>) -> PyResul
 /// messages. It processes requests using a shared `ServerState` and enqueues the responses
 /// to a dedicated task for sending.
 ///
+/// Clients may announce the largest datagram they accept with `max_payload`; responses
+/// to them are then split into chunks that fit, and chunked requests are reassembled.
+///
+/// The server also stops early on an admin `"shutdown"` request or on Ctrl-C, saving
+/// the primes found so far; the completion hook only runs for completed computations.
+///
 /// # Arguments
 ///
-/// * `port` - The UDP port to bind the socket.
-/// * `start` - The start of the number range.
-/// * `end` - The end of the number range.
-/// * `verbose` - Verbosity level for logging.
+/// * `config` - The network settings of the server.
+/// * `server_state` - The initial state of the computation.
+///
+/// # Returns
+///
+/// A `RunResult` built from the final state once the computation is completed or shut down.
 ///
 /// # Errors
 ///
-/// This function returns a `PyValueError` if it fails to bind the UDP socket.
-async fn run_server(port: u16, start: u32, end: u32, verbose: u8) -> PyResult<()> {
+/// This function returns a `PyValueError` if it fails to bind the UDP socket, or if the
+/// response sender keeps failing (the primes are saved before returning).
+async fn run_server(config: ServerConfig, server_state: ServerState) -> PyResult<RunResult> {
+    let started_at = Instant::now();
+    let port = config.port;
+    logging::init(config.verbose);
+    let lock_warn_threshold = config.lock_warn_threshold;
+
     // Bind the UDP socket and wrap it in an Arc for thread-safe sharing
-    let socket = match UdpSocket::bind(format!("0.0.0.0:{}", port)).await {
+    let socket = match bind_socket(&config).await {
         Ok(sock) => {
-            if verbose > 0 {
-                println!("🚀 Server started on port {}", port);
-            }
+            info!("🚀 Server started on port {}", port);
             Arc::new(sock)
         }
         Err(e) => {
+            // Ports below 1024 are reserved to privileged processes on Unix.
+            let hint = if port < 1024 && e.kind() == ErrorKind::PermissionDenied {
+                " (ports below 1024 need elevated privileges; pick a higher port)"
+            } else {
+                ""
+            };
             return Err(PyErr::new::<PyValueError, _>(format!(
-                "Failed to bind UDP socket: {}",
-                e
+                "Failed to bind UDP socket: {}{}",
+                e, hint
             )));
         }
     };
 
-    let (response_tx, mut response_rx) = mpsc::channel::<(String, std::net::SocketAddr)>(100);
+    let (response_tx, response_rx) = mpsc::channel::<(Vec<u8>, SocketAddr, usize)>(100);
+    let response_queue: ResponseQueue = Arc::new(Mutex::new(response_rx));
 
     let socket_for_sender = socket.clone();
-    tokio::spawn(async move {
-        while let Some((response_json, addr)) = response_rx.recv().await {
-            if let Err(e) = socket_for_sender
-                .send_to(response_json.as_bytes(), addr)
-                .await
-            {
-                eprintln!("❌ Error sending response to {}: {:?}", addr, e);
-            }
-        }
-    });
+    let send_rate = config.send_rate;
+    let checksum = config.checksum;
+    tokio::spawn(supervise_sender(
+        move || {
+            send_responses(
+                socket_for_sender.clone(),
+                response_queue.clone(),
+                send_rate,
+                checksum,
+            )
+        },
+        MAX_SENDER_RESTARTS,
+    ));
 
-    let server_state = Arc::new(Mutex::new(ServerState::new(start, end)));
+    let server_state = Arc::new(Mutex::new(server_state));
+    if let Some(interval) = config.keepalive_interval {
+        tokio::spawn(send_keepalives(
+            server_state.clone(),
+            response_tx.clone(),
+            interval,
+            config.format,
+        ));
+    }
+    tokio::spawn(reclaim_expired_leases(server_state.clone()));
+    if let Some(path) = config.debug_state_path.clone() {
+        tokio::spawn(dump_debug_state(
+            server_state.clone(),
+            path,
+            config.debug_state_interval,
+        ));
+    }
     let clients: Arc<Mutex<HashSet<String>>> = Arc::new(Mutex::new(HashSet::new()));
+    let payload_limits: Arc<Mutex<HashMap<SocketAddr, usize>>> =
+        Arc::new(Mutex::new(HashMap::new()));
+    let mut reassembler = Reassembler::with_max_message_size(config.max_message_size);
+    let mut progress_reporter = config.progress_callback.clone().map(|callback| {
+        ProgressReporter::spawn(PROGRESS_QUEUE_CAPACITY, move |event| callback(event))
+    });
+    let mut last_activity = Instant::now();
+    // Only the loop reads requests, so the limiter needs no lock.
+    let mut rate_limiter = config.max_requests_per_second.map(RateLimiter::new);
+    let stop_signal = server_state.lock().await.stop_signal.clone();
 
     loop {
-        {
-            let state = server_state.lock().await;
-            if state.status == "completed" {
-                if verbose > 0 {
-                    println!("✅ Computation finished. Shutting down server...");
+        // The loop sleeps until a message arrives, the state is marked stopped, or one
+        // of these deadlines passes: the end of the grace period or the idle timeout.
+        let wake_at = {
+            let mut state = lock_state(&server_state, lock_warn_threshold, |waited| {
+                warn!("⚠️ Server loop waiting {:?} for the state lock", waited);
+            })
+            .await;
+
+            // The supervisor gave up on the sender: nobody would get replies anymore.
+            if response_tx.is_closed() {
+                error!("❌ Response sender stopped. Saving results and shutting down...");
+                save_results(&mut state);
+                return Err(PyErr::new::<PyValueError, _>(
+                    "Response sender stopped unexpectedly",
+                ));
+            }
+
+            let idle = !state.is_stopped()
+                && config
+                    .idle_timeout
+                    .is_some_and(|idle_timeout| last_activity.elapsed() >= idle_timeout);
+            if idle {
+                state.mark_shutdown();
+            }
+
+            let shutdown = state.status == "shutdown";
+            if shutdown || (state.status == "completed" && !state.in_grace_period()) {
+                if idle {
+                    info!(
+                        "💤 No message received for {:?}. Saving results...",
+                        last_activity.elapsed()
+                    );
+                } else if shutdown {
+                    info!("🛑 Shutdown requested. Saving results...");
+                } else {
+                    info!("✅ Computation finished. Saving results...");
+                }
+                if config.verify && !shutdown {
+                    verify_results(&state);
                 }
-                break;
+                save_results(&mut state);
+                if let Some(command) = config.on_complete_cmd.as_ref().filter(|_| !shutdown) {
+                    run_completion_hook(command, &state.output_path).await;
+                }
+                info!("✅ Shutting down server...");
+                // Deliver the final progress before handing control back to Python.
+                if let Some(reporter) = progress_reporter.take() {
+                    if reporter.dropped() > 0 {
+                        debug!("⚠️ Dropped {} progress events", reporter.dropped());
+                    }
+                    reporter.finish();
+                }
+                let result = RunResult::from_state(&state, started_at.elapsed());
+                info!("📊 {}", result.summary);
+                return Ok(result);
             }
-        }
 
-        let mut buffer = vec![0; 65535];
+            let idle_deadline = config
+                .idle_timeout
+                .map(|idle_timeout| last_activity + idle_timeout);
+            match (state.grace_period_end(), idle_deadline) {
+                (Some(grace), Some(idle)) => Some(grace.min(idle)),
+                (grace, idle) => grace.or(idle),
+            }
+        };
 
         tokio::select! {
-            result = socket.recv_from(&mut buffer) => {
+            result = recv_message(&socket, &mut reassembler, config.checksum) => {
                 match result {
-                    Ok((size, src)) => {
-                        buffer.truncate(size);
-                        let request = String::from_utf8_lossy(&buffer[..size]).to_string();
+                    Ok((message, src)) => {
+                        last_activity = Instant::now();
+                        let format = config.format;
                         let client_addr = src.to_string();
 
+                        if let Some(limiter) = rate_limiter.as_mut() {
+                            if !limiter.allow(src, last_activity) {
+                                debug!("🚦 Request from {} over the rate limit", src);
+                                let response = Response {
+                                    task: Task::Error,
+                                    status: "rate_limited".to_string(),
+                                    ..Default::default()
+                                };
+                                let response_bytes = format.encode(&response).unwrap_or_else(|_| format.serialization_failed());
+                                // Dropped when the queue is full, so a flood never stalls the loop.
+                                if response_tx.try_send((response_bytes, src, MAX_DATAGRAM_SIZE)).is_err() {
+                                    debug!("⚠️ Response queue full, dropped rate limit reply to {}", src);
+                                }
+                                continue;
+                            }
+                        }
+
                         {
                             let mut clients_lock = clients.lock().await;
-                            if verbose > 0 && !clients_lock.contains(&client_addr) {
+                            if log_enabled!(Level::Info) && !clients_lock.contains(&client_addr) {
                                 clients_lock.insert(client_addr.clone());
-                                println!("🔗 New client connected: {}", client_addr);
+                                info!("🔗 New client connected: {}", client_addr);
                             }
                         }
 
                         let response_tx_clone = response_tx.clone();
                         let server_state_clone = server_state.clone();
+                        let payload_limits_clone = payload_limits.clone();
                         let src_clone = src;
+                        let max_message_size = config.max_message_size;
+                        let base_primes_path = config.base_primes_path.clone();
+                        let progress = progress_reporter.as_ref().map(ProgressReporter::handle);
 
                         tokio::spawn(async move {
-                            let response_json = {
-                                let mut state = server_state_clone.lock().await;
-                                if state.status == "completed" {
-                                    if verbose > 0 {
-                                        println!("✅ Computation finished. Saving results...");
-                                    }
-                                    if let Err(e) = state.save_primes_to_file() {
-                                        eprintln!("❌ Error saving primes: {:?}", e);
+                            let mut max_payload = MAX_DATAGRAM_SIZE;
+                            let response = {
+                                let mut state = lock_state(&server_state_clone, lock_warn_threshold, |waited| {
+                                    warn!("⚠️ Request from {} waiting {:?} for the state lock", src_clone, waited);
+                                })
+                                .await;
+                                state.bytes_received += message.len() as u64;
+                                match format.decode::<Request>(&message) {
+                                    Ok(request_data) => {
+                                        let mut limits = payload_limits_clone.lock().await;
+                                        let requested = request_data.max_payload;
+                                        if let Some(requested) = requested {
+                                            limits.insert(src_clone, negotiate_datagram_size(requested));
+                                        }
+                                        if let Some(&limit) = limits.get(&src_clone) {
+                                            max_payload = limit;
+                                        }
+
+                                        let previously_checked = state.last_checked;
+                                        let mut response = handler(&mut state, request_data, src_clone);
+                                        match state.checkpoint_if_due() {
+                                            Ok(true) => debug!("💾 Checkpoint written"),
+                                            Ok(false) => {}
+                                            Err(e) => error!("❌ Error writing checkpoint: {:?}", e),
+                                        }
+                                        if let Some(progress) = progress.as_ref().filter(|_| state.last_checked > previously_checked) {
+                                            progress.report(ProgressEvent {
+                                                last_checked: state.last_checked,
+                                                end: state.end,
+                                            });
+                                        }
+                                        if requested.is_some() {
+                                            response.max_payload = Some(max_payload as u32);
+                                        }
+                                        response
                                     }
-                                    return;
-                                }
-                                if let Some(request_data) = Request::from_json(&request) {
-                                    let response = handler(&mut state, request_data);
-                                    response.to_json()
-                                } else {
-                                    if verbose > 1 {
-                                        println!("⚠️ Invalid request format!");
+                                    Err(e) => {
+                                        debug!("⚠️ Invalid request format: {}", e);
+                                        Response {
+                                            task: Task::Error,
+                                            status: "invalid_request".to_string(),
+                                            ..Default::default()
+                                        }
                                     }
-                                    let error_response = Response {
-                                        task: "error".to_string(),
-                                        status: "invalid_request".to_string(),
-                                        start: None,
-                                        end: None,
-                                        primes: None,
-                                    };
-                                    error_response.to_json()
                                 }
                             };
-                            if verbose > 1 {
-                                println!("📤 Response being enqueued: {:?}", response_json);
+                            let mut response_bytes = format.encode(&response).unwrap_or_else(|e| {
+                                error!("❌ Failed to serialize response {:?}: {}", response, e);
+                                format.serialization_failed()
+                            });
+                            if response_bytes.len() > max_message_size && response.task == Task::Range {
+                                response_bytes = offload_base_primes(response, &base_primes_path, max_message_size, format);
                             }
-                            if let Err(e) = response_tx_clone.send((response_json, src_clone)).await {
-                                eprintln!("❌ Failed to enqueue response: {:?}", e);
+                            debug!("📤 Response being enqueued: {:?}", String::from_utf8_lossy(&response_bytes));
+                            if let Err(e) = response_tx_clone.send((response_bytes, src_clone, max_payload)).await {
+                                error!("❌ Failed to enqueue response: {:?}", e);
                             }
                         });
                     }
                     Err(e) => {
                         if e.kind() == ErrorKind::ConnectionReset {
-                            if verbose > 1 {
-                                eprintln!("⚠️ Connection reset by peer. Ignoring...");
-                            }
+                            debug!("⚠️ Connection reset by peer. Ignoring...");
                             continue;
                         } else {
-                            if verbose > 0 {
-                                eprintln!("❌ Failed to receive data: {:?}", e);
-                            }
+                            error!("❌ Failed to receive data: {:?}", e);
                         }
                     }
                 }
             },
-            _ = sleep(Duration::from_millis(10)) => {
+            _ = tokio::signal::ctrl_c() => {
+                lock_state(&server_state, lock_warn_threshold, |waited| {
+                    warn!("⚠️ Shutdown waiting {:?} for the state lock", waited);
+                })
+                .await
+                .mark_shutdown();
+            },
+            _ = config.stop_requested.notified() => {
+                lock_state(&server_state, lock_warn_threshold, |waited| {
+                    warn!("⚠️ Stop waiting {:?} for the state lock", waited);
+                })
+                .await
+                .mark_shutdown();
+            },
+            _ = stop_signal.notified() => {
+                continue;
+            },
+            _ = response_tx.closed() => {
+                continue;
+            },
+            _ = sleep_until_deadline(wake_at) => {
                 continue;
             }
         }
     }
-    Ok(())
+}
+
+/// Sleeps until `deadline`, or forever without one.
+async fn sleep_until_deadline(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => sleep_until(deadline.into()).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Replaces the inline base primes of an oversized `"range"` with a file reference.
+///
+/// The base primes are written to `path`, one per line, and the client is told to
+/// read them from there instead. Clients that can't read the file sieve their own
+/// base primes. Should even the slimmed response exceed `max_message_size`, an
+/// `"error"` is returned so that nothing unbounded is ever sent.
+///
+/// # Arguments
+///
+/// * `response` - The `"range"` response carrying the base primes inline.
+/// * `path` - Where the base primes are written.
+/// * `max_message_size` - The largest message sent, in bytes.
+/// * `format` - The encoding of the response.
+///
+/// # Returns
+///
+/// The serialized response to send.
+fn offload_base_primes(
+    mut response: Response,
+    path: &Path,
+    max_message_size: usize,
+    format: WireFormat,
+) -> Vec<u8> {
+    let error = |status: &str| {
+        format
+            .encode(&Response {
+                task: Task::Error,
+                status: status.to_string(),
+                ..Default::default()
+            })
+            .unwrap_or_else(|_| format.serialization_failed())
+    };
+
+    let primes = response.primes.take().unwrap_or_default();
+    if let Err(e) = write_base_primes(path, &primes) {
+        error!(
+            "❌ Failed to write base primes to {}: {:?}",
+            path.display(),
+            e
+        );
+        return error("message_too_large");
+    }
+    warn!(
+        "⚠️ Range response exceeds {} bytes, base primes offloaded to {}",
+        max_message_size,
+        path.display()
+    );
+
+    response.primes_path = Some(path.display().to_string());
+    match format.encode(&response) {
+        Ok(bytes) if bytes.len() <= max_message_size => bytes,
+        _ => error("message_too_large"),
+    }
+}
+
+/// Writes `primes` to `path`, one per line, replacing the file atomically.
+///
+/// # Arguments
+///
+/// * `path` - The destination file.
+/// * `primes` - The primes to write.
+///
+/// # Errors
+///
+/// Returns an `io::Error` if the file can't be written.
+fn write_base_primes(path: &Path, primes: &[u64]) -> std::io::Result<()> {
+    let contents: String = primes.iter().map(|prime| format!("{}\n", prime)).collect();
+    let staging = path.with_extension(format!("{}.tmp", std::process::id()));
+    std::fs::write(&staging, contents)?;
+    std::fs::rename(&staging, path)
+}
+
+/// Periodically appends a `DebugSnapshot` of the state to `path`.
+///
+/// Failures are logged and the next snapshot is still attempted. Stops once the
+/// computation is completed or shut down, after a final snapshot.
+///
+/// # Arguments
+///
+/// * `server_state` - The shared server state.
+/// * `path` - The debug file.
+/// * `interval` - The delay between two snapshots.
+async fn dump_debug_state(
+    server_state: Arc<Mutex<ServerState>>,
+    path: PathBuf,
+    interval: Duration,
+) {
+    loop {
+        sleep(interval).await;
+        let (snapshot, stopped) = {
+            let state = server_state.lock().await;
+            (DebugSnapshot::from_state(&state), state.is_stopped())
+        };
+
+        if let Err(e) = snapshot.append_to(&path) {
+            error!(
+                "❌ Failed to write debug snapshot to {}: {:?}",
+                path.display(),
+                e
+            );
+        }
+        if stopped {
+            return;
+        }
+    }
+}
+
+/// Periodically sends a keepalive to every client holding an assignment.
+///
+/// Clients behind symmetric NATs may lose their mapping while computing a long
+/// range in silence; the keepalives keep it open until their save arrives.
+/// Stops once the computation is completed or the response queue is closed.
+///
+/// # Arguments
+///
+/// * `server_state` - The shared server state.
+/// * `response_tx` - The queue of responses to send.
+/// * `interval` - The delay between two keepalives to the same client.
+/// * `format` - The encoding of the keepalives.
+async fn send_keepalives(
+    server_state: Arc<Mutex<ServerState>>,
+    response_tx: mpsc::Sender<(Vec<u8>, SocketAddr, usize)>,
+    interval: Duration,
+    format: WireFormat,
+) {
+    loop {
+        sleep(interval).await;
+        let holders = {
+            let state = server_state.lock().await;
+            if state.is_stopped() {
+                return;
+            }
+            state.assignments.holders()
+        };
+
+        for client in holders {
+            trace!("💓 Sending keepalive to {}", client);
+            let keepalive = (format.keepalive(), client, MAX_DATAGRAM_SIZE);
+            if response_tx.send(keepalive).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Periodically returns the ranges whose lease expired to the pool.
+///
+/// A client that crashes holding a range never saves it; without the sweep the
+/// range would only come back on the next `"start"`. Sweeps run every lease
+/// duration, from 10 ms to `LEASE_SWEEP_INTERVAL` apart, and stop once the server stopped.
+///
+/// # Arguments
+///
+/// * `server_state` - The shared server state.
+async fn reclaim_expired_leases(server_state: Arc<Mutex<ServerState>>) {
+    loop {
+        let period = {
+            let state = server_state.lock().await;
+            state
+                .assignments
+                .lease_duration
+                .clamp(Duration::from_millis(10), LEASE_SWEEP_INTERVAL)
+        };
+        sleep(period).await;
+        let mut state = server_state.lock().await;
+        if state.is_stopped() {
+            return;
+        }
+        let reclaimed = state.reap_expired_leases();
+        if reclaimed > 0 {
+            info!("♻️ Reclaimed {} range(s) with an expired lease", reclaimed);
+        }
+    }
+}
+
+/// Inserts `.shard<index>` before the extension of an output path.
+///
+/// # Arguments
+///
+/// * `path` - The output path shared by all shards.
+/// * `index` - The index of the shard.
+fn shard_output_path(path: &Path, index: u32) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}.shard{}.{}", stem, index, extension.to_string_lossy()),
+        None => format!("{}.shard{}", stem, index),
+    };
+    path.with_file_name(name)
+}
+
+/// Checks the primes found with a primality test, logging any composite as an error.
+///
+/// The primes are saved either way, so the output can be inspected.
+///
+/// # Arguments
+///
+/// * `server_state` - The state holding the results.
+fn verify_results(server_state: &ServerState) {
+    info!("🔍 Verifying {} primes...", server_state.primes.len());
+    match server_state.verify() {
+        Ok(()) => info!("✅ Every prime verified"),
+        Err(composites) => {
+            error!(
+                "❌ Verification failed: {} saved values are not prime: {:?}{}",
+                composites.len(),
+                &composites[..composites.len().min(20)],
+                if composites.len() > 20 { " ..." } else { "" }
+            );
+        }
+    }
+}
+
+/// Writes the primes, sorted and free of duplicates, and, when recorded, the `π(x)`
+/// values to their files.
+///
+/// Failures are logged rather than returned, so shutdown always proceeds.
+///
+/// # Arguments
+///
+/// * `server_state` - The state holding the results.
+fn save_results(server_state: &mut ServerState) {
+    server_state.finalize_primes();
+    if let Err(e) = server_state.save_primes_to_file() {
+        error!("❌ Error saving primes: {:?}", e);
+    }
+    if let Some(path) = &server_state.checkpoint_path {
+        if let Err(e) = server_state.write_checkpoint(path) {
+            error!("❌ Error writing checkpoint: {:?}", e);
+        }
+    }
+    if server_state.pi_interval.is_some() {
+        if let Err(e) = server_state.save_pi_checkpoints() {
+            error!("❌ Error saving π(x) values: {:?}", e);
+        }
+    }
+    if let Err(e) = server_state.save_bloom() {
+        error!("❌ Error saving Bloom filter: {:?}", e);
+    }
+}
+
+/// Runs the completion hook through `sh -c` and logs its exit status.
+///
+/// The output path is passed both as the first positional argument (`$1`) and in the
+/// `PRIMESOCKET_OUTPUT_PATH` environment variable. Failures are logged rather than
+/// returned, so shutdown always proceeds.
+///
+/// # Arguments
+///
+/// * `command` - The shell command to run.
+/// * `output_path` - The file holding the saved primes.
+async fn run_completion_hook(command: &str, output_path: &Path) {
+    info!("🪝 Running completion hook: {}", command);
+    let status = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .arg("sh")
+        .arg(output_path)
+        .env("PRIMESOCKET_OUTPUT_PATH", output_path)
+        .status()
+        .await;
+
+    match status {
+        Ok(status) if status.success() => {
+            info!("✅ Completion hook exited with {}", status);
+        }
+        Ok(status) => error!("❌ Completion hook exited with {}", status),
+        Err(e) => error!("❌ Failed to run completion hook: {:?}", e),
+    }
+}
+
+/// Acquires a lock, warning each time the wait exceeds `threshold`.
+///
+/// Waiting never gives up: the warnings only make long holds (e.g. a big merge)
+/// visible when diagnosing contention.
+///
+/// # Arguments
+///
+/// * `mutex` - The lock to acquire.
+/// * `threshold` - How long to wait between warnings.
+/// * `on_slow` - Called with the total time waited so far each time `threshold` elapses.
+///
+/// # Returns
+///
+/// The guard of the acquired lock.
+async fn lock_state<'a, T, F>(
+    mutex: &'a Mutex<T>,
+    threshold: Duration,
+    on_slow: F,
+) -> MutexGuard<'a, T>
+where
+    F: Fn(Duration),
+{
+    let started = Instant::now();
+    let lock = mutex.lock();
+    tokio::pin!(lock);
+    loop {
+        match timeout(threshold, &mut lock).await {
+            Ok(guard) => return guard,
+            Err(_) => on_slow(started.elapsed()),
+        }
+    }
+}
+
+/// Sends the queued responses until the queue is closed.
+///
+/// With a `send_rate`, each datagram waits for its turn on a pacing timer. The
+/// timer is only awaited here, never while holding the state lock, so a full
+/// queue just makes the request tasks wait for room.
+///
+/// # Arguments
+///
+/// * `socket` - The server socket.
+/// * `queue` - The responses waiting to be sent.
+/// * `send_rate` - The most datagrams sent per second, if limited.
+/// * `checksum` - Whether each datagram is sealed with its CRC-32.
+async fn send_responses(
+    socket: Arc<UdpSocket>,
+    queue: ResponseQueue,
+    send_rate: Option<u32>,
+    checksum: bool,
+) {
+    let mut pacer = send_rate.map(|rate| {
+        // Delaying missed ticks keeps an idle sender from bursting afterwards.
+        let mut pacer = interval(Duration::from_secs(1) / rate.max(1));
+        pacer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        pacer
+    });
+    let mut queue = queue.lock().await;
+    while let Some((bytes, addr, max_payload)) = queue.recv().await {
+        if let Err(e) =
+            send_message_paced(&socket, &bytes, addr, max_payload, checksum, pacer.as_mut()).await
+        {
+            error!("❌ Error sending response to {}: {:?}", addr, e);
+        }
+    }
+}
+
+/// Runs the response sender, restarting it if it panics.
+///
+/// The sender returns normally only once its queue is closed. If it panics,
+/// a new one is spawned on the same queue, up to `max_restarts` times; after
+/// that the supervisor stops, which drops the queue so the server notices
+/// and shuts down instead of queueing replies nobody sends.
+///
+/// Note that the release profile sets `panic = "abort"`, where a panic ends
+/// the whole process instead of reaching the supervisor.
+///
+/// # Arguments
+///
+/// * `spawn_sender` - Creates a new sender future.
+/// * `max_restarts` - How many restarts are allowed.
+///
+/// # Returns
+///
+/// `true` if the sender finished normally, `false` if the supervisor gave up.
+async fn supervise_sender<F, Fut>(spawn_sender: F, max_restarts: u32) -> bool
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let mut restarts = 0;
+    loop {
+        match tokio::spawn(spawn_sender()).await {
+            Ok(()) => return true,
+            Err(e) if restarts < max_restarts => {
+                restarts += 1;
+                warn!(
+                    "⚠️ Response sender failed ({}). Restarting {}/{}...",
+                    e, restarts, max_restarts
+                );
+            }
+            Err(e) => {
+                error!("❌ Response sender failed ({}). Giving up.", e);
+                return false;
+            }
+        }
+    }
+}
+
+/// Binds the server socket, retrying while the port is unavailable.
+///
+/// Each failed attempt is logged, then binding is retried after
+/// `bind_retry_delay`, up to `bind_retries` times.
+///
+/// # Arguments
+///
+/// * `config` - The network settings of the server.
+///
+/// # Errors
+///
+/// Returns the error of the last attempt if every attempt failed.
+async fn bind_socket(config: &ServerConfig) -> std::io::Result<UdpSocket> {
+    let addr: SocketAddr = match config.bind_addr {
+        Some(ip) => (ip, config.port).into(),
+        None if config.dual_stack => (Ipv6Addr::UNSPECIFIED, config.port).into(),
+        None => (Ipv4Addr::UNSPECIFIED, config.port).into(),
+    };
+    let mut attempt = 0;
+    loop {
+        match bind_udp(addr, config.dual_stack).await {
+            Ok(socket) => return Ok(socket),
+            Err(e) if attempt < config.bind_retries => {
+                attempt += 1;
+                warn!(
+                    "⚠️ Failed to bind {} ({}). Retrying {}/{} in {:?}...",
+                    addr, e, attempt, config.bind_retries, config.bind_retry_delay
+                );
+                sleep(config.bind_retry_delay).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Binds a UDP socket to `addr`.
+///
+/// # Arguments
+///
+/// * `addr` - The address to bind.
+/// * `dual_stack` - Whether an IPv6 socket also accepts IPv4 traffic.
+///
+/// # Errors
+///
+/// Returns an `io::Error` if the socket can't be created, configured or bound,
+/// including on platforms where dual-stack sockets are not supported.
+async fn bind_udp(addr: SocketAddr, dual_stack: bool) -> std::io::Result<UdpSocket> {
+    if !dual_stack {
+        return UdpSocket::bind(addr).await;
+    }
+
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_only_v6(false)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    UdpSocket::from_std(socket.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::client::{download_with_options, start_client_with_options};
+    use crate::client::client_options::ClientOptions;
+    use crate::server::local::compute_primes_local;
+    use crate::utils::chunk::Chunk;
+    use crate::utils::json::KEEPALIVE_JSON;
+    use crate::utils::sieve::{primes_up_to, sieve_segment};
+    use std::process::Command;
+    use std::thread;
+
+    /// Reserves a free UDP port on the loopback interface.
+    fn free_port() -> u16 {
+        std::net::UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    /// Runs a server over `[2, end]` served by a single client on loopback.
+    ///
+    /// Returns the server result along with the contents of the output file.
+    fn run_small(end: u64, verbose: u8) -> (RunResult, String) {
+        run_state(ServerState::new(2, end, DEFAULT_STEP), verbose)
+    }
+
+    /// Runs a server from `server_state` served by a single client on loopback.
+    ///
+    /// Returns the server result along with the contents of the output file.
+    fn run_state(server_state: ServerState, verbose: u8) -> (RunResult, String) {
+        run_configured(server_state, verbose, |_| {})
+    }
+
+    /// Runs a server from `server_state`, with its config adjusted by `configure`,
+    /// served by a single client on loopback.
+    ///
+    /// Returns the server result along with the contents of the output file.
+    fn run_configured<F>(
+        server_state: ServerState,
+        verbose: u8,
+        configure: F,
+    ) -> (RunResult, String)
+    where
+        F: FnOnce(&mut ServerConfig),
+    {
+        run_served_from(server_state, verbose, "127.0.0.1", configure)
+    }
+
+    /// Runs a server from `server_state`, with its config adjusted by `configure`,
+    /// served by a single client reaching it at `ip`.
+    ///
+    /// Returns the server result along with the contents of the output file.
+    fn run_served_from<F>(
+        mut server_state: ServerState,
+        verbose: u8,
+        ip: &str,
+        configure: F,
+    ) -> (RunResult, String)
+    where
+        F: FnOnce(&mut ServerConfig),
+    {
+        pyo3::prepare_freethreaded_python();
+        let port = free_port();
+        let mut config = ServerConfig::new(port, verbose);
+        configure(&mut config);
+        let checksum = config.checksum;
+        server_state.output_path =
+            std::env::temp_dir().join(format!("primesocket_run_{}.txt", port));
+        let output_path = server_state.output_path.clone();
+
+        let server = thread::spawn(move || {
+            Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(run_server(config, server_state))
+        });
+        thread::sleep(Duration::from_millis(200));
+        Python::with_gil(|py| {
+            start_client_with_options(
+                py,
+                ip,
+                port,
+                Some(verbose),
+                &ClientOptions {
+                    timeout_seconds: Some(5),
+                    checksum: Some(checksum),
+                    ..Default::default()
+                },
+            )
+        })
+        .unwrap();
+
+        let result = server.join().unwrap().unwrap();
+        let saved = std::fs::read_to_string(&output_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        (result, saved)
+    }
+
+    /// Tests that the `RunResult` returned by a small run matches its actual results.
+    ///
+    /// A server over `[2, 100]` is served by a single client, then the result is
+    /// inspected from Python to make sure the exposed fields are correct.
+    #[test]
+    fn test_run_result_matches_small_run() {
+        let (result, saved) = run_small(100, 0);
+        let expected_path = result.path.clone();
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let result = Py::new(py, result).unwrap();
+            let count: u64 = result.getattr(py, "count").unwrap().extract(py).unwrap();
+            let largest: Option<u64> = result.getattr(py, "largest").unwrap().extract(py).unwrap();
+            let duration: f64 = result.getattr(py, "duration").unwrap().extract(py).unwrap();
+            let path: String = result.getattr(py, "path").unwrap().extract(py).unwrap();
+
+            assert_eq!(count, 25);
+            assert_eq!(count as usize, saved.lines().count());
+            assert_eq!(largest, Some(97));
+            assert!(duration > 0.0);
+            assert_eq!(path, expected_path);
+        });
+    }
+
+    /// Tests that the summary returned by `start_client` reports its contribution.
+    ///
+    /// A single client computes `[2, 10000]` over two ranges, so it must account for
+    /// every prime and for the traffic of the run, as seen from Python.
+    #[test]
+    fn test_client_summary_reports_contribution() {
+        pyo3::prepare_freethreaded_python();
+        let port = free_port();
+        let mut server_state = ServerState::new(2, 10_000, 5_000);
+        server_state.output_path =
+            std::env::temp_dir().join(format!("primesocket_summary_{}.txt", port));
+        let output_path = server_state.output_path.clone();
+        let server = thread::spawn(move || {
+            Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(run_server(ServerConfig::new(port, 0), server_state))
+        });
+        thread::sleep(Duration::from_millis(200));
+
+        let summary = Python::with_gil(|py| {
+            start_client_with_options(
+                py,
+                "127.0.0.1",
+                port,
+                None,
+                &ClientOptions {
+                    timeout_seconds: Some(5),
+                    ..Default::default()
+                },
+            )
+        })
+        .unwrap();
+        server.join().unwrap().unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let summary = Py::new(py, summary).unwrap();
+            let get =
+                |name: &str| -> u64 { summary.getattr(py, name).unwrap().extract(py).unwrap() };
+
+            assert_eq!(get("ranges"), 2);
+            assert_eq!(get("primes"), 1_229);
+            assert!(get("bytes_sent") > 0);
+            assert!(get("bytes_received") > 0);
+        });
+    }
+
+    /// Tests a large assignment unit sieved by the client in small buffers.
+    ///
+    /// The two assigned ranges must be saved in sub-blocks of at most 20_000 values (21
+    /// in all), so the client never holds more than a buffer's worth of results, and the
+    /// output stays exact.
+    #[test]
+    fn test_large_assignment_unit_small_buffer() {
+        pyo3::prepare_freethreaded_python();
+        let port = free_port();
+        let mut server_state = ServerState::new(2, 400_000, 200_000);
+        server_state.output_path =
+            std::env::temp_dir().join(format!("primesocket_unit_{}.txt", port));
+        let output_path = server_state.output_path.clone();
+        let server = thread::spawn(move || {
+            Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(run_server(ServerConfig::new(port, 0), server_state))
+        });
+        thread::sleep(Duration::from_millis(200));
+
+        let summary = Python::with_gil(|py| {
+            start_client_with_options(
+                py,
+                "127.0.0.1",
+                port,
+                None,
+                &ClientOptions {
+                    timeout_seconds: Some(5),
+                    sieve_block_size: Some(4_096),
+                    stream_block_size: Some(20_000),
+                    ..Default::default()
+                },
+            )
+        })
+        .unwrap();
+        let result = server.join().unwrap().unwrap();
+        let saved = std::fs::read_to_string(&output_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+
+        assert_eq!(summary.ranges, 21);
+        assert_eq!(summary.primes, 33_860);
+        assert_eq!(result.count, 33_860);
+        assert_eq!(saved.lines().count(), 33_860);
+    }
+
+    /// Tests that a sum-only run adds up the primes of every range without listing them.
+    #[test]
+    fn test_sum_only_run() {
+        for (end, step) in [(10, 1_000), (100_000, 997)] {
+            let mut server_state = ServerState::new(2, end, DEFAULT_STEP);
+            server_state.sum_only = true;
+            server_state.step = step;
+            let (result, saved) = run_state(server_state, 0);
+
+            let primes = primes_up_to(end);
+            let expected: u128 = primes.iter().map(|&p| u128::from(p)).sum();
+            assert_eq!(result.sum, Some(expected));
+            assert_eq!(result.count, primes.len() as u64);
+            assert_eq!(saved, format!("{}\n", expected));
+        }
+        assert_eq!(primes_up_to(10).iter().sum::<u64>(), 17);
+    }
+
+    /// Tests that a `"shutdown"` request stops the server and saves the primes found so far.
+    #[tokio::test]
+    async fn test_shutdown_request_stops_server() {
+        let port = free_port();
+        let mut server_state = ServerState::new(2, 1_000_000, DEFAULT_STEP);
+        server_state.admin_secret = Some("s3cret".to_string());
+        server_state.output_path =
+            std::env::temp_dir().join(format!("primesocket_shutdown_{}.txt", port));
+        let output_path = server_state.output_path.clone();
+        let server = tokio::spawn(run_server(ServerConfig::new(port, 0), server_state));
+        sleep(Duration::from_millis(100)).await;
+
+        let request = Request {
+            task: Task::Shutdown,
+            secret: Some("s3cret".to_string()),
+            ..Default::default()
+        };
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        socket
+            .send_to(request.to_json().unwrap().as_bytes(), ("127.0.0.1", port))
+            .await
+            .unwrap();
+
+        let result = timeout(Duration::from_secs(2), server)
+            .await
+            .unwrap()
+            .unwrap();
+        let saved = std::fs::read_to_string(&output_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        assert_eq!(result.unwrap().count, 25);
+        assert_eq!(saved.lines().count(), 25);
+    }
+
+    /// Tests that the save completing the computation stops the server right away.
+    ///
+    /// The save is handled on a spawned task, after the loop went back to waiting for
+    /// messages; only the stop signal can wake it, as no other message follows.
+    #[tokio::test]
+    async fn test_completion_wakes_server_loop() {
+        let port = free_port();
+        let mut server_state = ServerState::new(2, 100, DEFAULT_STEP);
+        server_state.output_path =
+            std::env::temp_dir().join(format!("primesocket_wake_{}.txt", port));
+        let output_path = server_state.output_path.clone();
+        let server = tokio::spawn(run_server(ServerConfig::new(port, 0), server_state));
+        sleep(Duration::from_millis(100)).await;
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut buffer = vec![0; 65535];
+        for request in [
+            Request {
+                task: Task::Start,
+                ..Default::default()
+            },
+            Request {
+                task: Task::Save,
+                start: Some(2),
+                end: Some(100),
+                primes: Some(primes_up_to(100)),
+                ..Default::default()
+            },
+        ] {
+            socket
+                .send_to(request.to_json().unwrap().as_bytes(), ("127.0.0.1", port))
+                .await
+                .unwrap();
+            timeout(Duration::from_secs(2), socket.recv_from(&mut buffer))
+                .await
+                .unwrap()
+                .unwrap();
+        }
+
+        let result = timeout(Duration::from_millis(500), server)
+            .await
+            .expect("completed server kept waiting")
+            .unwrap()
+            .unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        assert_eq!(result.count, 25);
+    }
+
+    /// Tests downloading the primes of a completed server in pages.
+    ///
+    /// The server stays up during its grace period, while the primes are fetched in
+    /// pages far smaller than the result and reassembled by the client.
+    #[test]
+    fn test_download_completed_primes_in_pages() {
+        pyo3::prepare_freethreaded_python();
+        let port = free_port();
+        let mut server_state = ServerState::new(2, 100_000, DEFAULT_STEP);
+        server_state.primes = crate::utils::sieve::primes_up_to(100_000);
+        server_state.grace_period = Duration::from_secs(2);
+        server_state.mark_completed();
+        server_state.output_path =
+            std::env::temp_dir().join(format!("primesocket_download_{}.txt", port));
+        let output_path = server_state.output_path.clone();
+        let server = thread::spawn(move || {
+            Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(run_server(ServerConfig::new(port, 0), server_state))
+        });
+        thread::sleep(Duration::from_millis(200));
+
+        let primes = Python::with_gil(|py| {
+            download_with_options(
+                py,
+                "127.0.0.1",
+                port,
+                Some(1_000),
+                None,
+                None,
+                &ClientOptions {
+                    timeout_seconds: Some(5),
+                    ..Default::default()
+                },
+            )
+        })
+        .unwrap();
+
+        server.join().unwrap().unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        assert_eq!(primes.len(), 9_592);
+        assert_eq!(primes, crate::utils::sieve::primes_up_to(100_000));
+    }
+
+    /// Tests resuming a download interrupted after its first page.
+    ///
+    /// The resumed download starts at the number of primes already received, so the
+    /// assembled result has neither gaps nor duplicates; offsets past the end are refused.
+    #[test]
+    fn test_download_resumes_from_offset() {
+        pyo3::prepare_freethreaded_python();
+        let port = free_port();
+        let mut server_state = ServerState::new(2, 100_000, DEFAULT_STEP);
+        server_state.primes = primes_up_to(100_000);
+        server_state.grace_period = Duration::from_secs(2);
+        server_state.mark_completed();
+        server_state.output_path =
+            std::env::temp_dir().join(format!("primesocket_resume_dl_{}.txt", port));
+        let output_path = server_state.output_path.clone();
+        let server = thread::spawn(move || {
+            Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(run_server(ServerConfig::new(port, 0), server_state))
+        });
+        thread::sleep(Duration::from_millis(200));
+
+        // The first page arrives, then the link drops.
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let request = Request {
+            task: Task::Download,
+            offset: Some(0),
+            limit: Some(100),
+            ..Default::default()
+        };
+        socket
+            .send_to(request.to_json().unwrap().as_bytes(), ("127.0.0.1", port))
+            .unwrap();
+        let mut buffer = [0u8; MAX_DATAGRAM_SIZE];
+        let size = socket.recv(&mut buffer).unwrap();
+        let page = Response::from_json(&String::from_utf8_lossy(&buffer[..size])).unwrap();
+        let mut primes = page.primes.unwrap();
+        assert_eq!(primes.len(), 100);
+
+        let offset = Some(primes.len() as u64);
+        primes.extend(
+            Python::with_gil(|py| {
+                download_with_options(
+                    py,
+                    "127.0.0.1",
+                    port,
+                    Some(1_000),
+                    None,
+                    offset,
+                    &ClientOptions {
+                        timeout_seconds: Some(5),
+                        ..Default::default()
+                    },
+                )
+            })
+            .unwrap(),
+        );
+        let past_end = Python::with_gil(|py| {
+            download_with_options(
+                py,
+                "127.0.0.1",
+                port,
+                None,
+                None,
+                Some(9_593),
+                &ClientOptions {
+                    timeout_seconds: Some(5),
+                    ..Default::default()
+                },
+            )
+        });
+
+        server.join().unwrap().unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        assert_eq!(primes, primes_up_to(100_000));
+        assert!(past_end.unwrap_err().to_string().contains("invalid_offset"));
+    }
+
+    /// Tests that the completion hook runs after the results are saved.
+    ///
+    /// The hook copies its arguments and the output file next to a marker, so the
+    /// test can check it received the output path once the primes were written.
+    #[test]
+    fn test_completion_hook_runs_with_output_path() {
+        let marker = std::env::temp_dir().join(format!("primesocket_hook_{}", std::process::id()));
+        let command = format!(
+            "echo \"$1 $PRIMESOCKET_OUTPUT_PATH\" > {0}.args && cp \"$1\" {0}.primes",
+            marker.display()
+        );
+
+        let (result, saved) = run_configured(ServerState::new(2, 100, DEFAULT_STEP), 0, |config| {
+            config.on_complete_cmd = Some(command);
+        });
+
+        let args = std::fs::read_to_string(marker.with_extension("args")).unwrap();
+        let copied = std::fs::read_to_string(marker.with_extension("primes")).unwrap();
+        std::fs::remove_file(marker.with_extension("args")).unwrap();
+        std::fs::remove_file(marker.with_extension("primes")).unwrap();
+
+        assert_eq!(args.trim(), format!("{0} {0}", result.path));
+        assert_eq!(copied, saved);
+    }
+
+    /// Tests that a range too large for the message cap falls back to a base primes file.
+    ///
+    /// With a cap far below the size of the inline base primes, the client must be
+    /// pointed at the file instead, and still compute the right primes.
+    #[test]
+    fn test_oversized_range_offloads_base_primes() {
+        let base_primes_path =
+            std::env::temp_dir().join(format!("primesocket_base_{}.txt", std::process::id()));
+        let path = base_primes_path.clone();
+
+        let server_state = ServerState::new(2, 1_000_000, 10_000);
+        let (result, saved) = run_configured(server_state, 0, |config| {
+            config.max_message_size = 512;
+            config.base_primes_path = path;
+        });
+
+        let offloaded = std::fs::read_to_string(&base_primes_path).unwrap();
+        std::fs::remove_file(&base_primes_path).unwrap();
+        assert!(offloaded.len() > 512);
+        assert_eq!(result.count, 78_498);
+        assert_eq!(saved.lines().count(), 78_498);
+    }
+
+    /// Tests that debug snapshots accumulate at the configured interval and reflect the state.
+    #[tokio::test]
+    async fn test_debug_state_snapshots() {
+        let port = free_port();
+        let debug_state_path =
+            std::env::temp_dir().join(format!("primesocket_debug_{}.jsonl", port));
+        let mut config = ServerConfig::new(port, 0);
+        config.debug_state_path = Some(debug_state_path.clone());
+        config.debug_state_interval = Duration::from_millis(50);
+        tokio::spawn(run_server(
+            config,
+            ServerState::new(2, 1_000_000, DEFAULT_STEP),
+        ));
+        sleep(Duration::from_millis(100)).await;
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let request = Request {
+            task: Task::Start,
+            ..Default::default()
+        };
+        socket
+            .send_to(request.to_json().unwrap().as_bytes(), ("127.0.0.1", port))
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(400)).await;
+
+        let contents = std::fs::read_to_string(&debug_state_path).unwrap();
+        std::fs::remove_file(&debug_state_path).unwrap();
+        let snapshots: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert!(snapshots.len() >= 5, "{} snapshots", snapshots.len());
+        assert_eq!(snapshots[0]["outstanding"].as_array().unwrap().len(), 0);
+
+        let last = snapshots.last().unwrap();
+        let outstanding = last["outstanding"].as_array().unwrap();
+        assert_eq!(outstanding.len(), 1);
+        assert_eq!(
+            outstanding[0]["assigned_to"],
+            socket.local_addr().unwrap().to_string()
+        );
+        assert_eq!(last["status"], "processing");
+        assert!(snapshots
+            .windows(2)
+            .all(|pair| pair[0]["timestamp"].as_u64() <= pair[1]["timestamp"].as_u64()));
+    }
+
+    /// Tests that a client holding an assignment receives keepalives until it saves.
+    #[tokio::test]
+    async fn test_keepalives_sent_to_assignment_holder() {
+        let port = free_port();
+        let mut config = ServerConfig::new(port, 0);
+        config.keepalive_interval = Some(Duration::from_millis(30));
+        tokio::spawn(run_server(
+            config,
+            ServerState::new(2, 1_000_000, DEFAULT_STEP),
+        ));
+        sleep(Duration::from_millis(100)).await;
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let request = Request {
+            task: Task::Start,
+            ..Default::default()
+        };
+        socket
+            .send_to(request.to_json().unwrap().as_bytes(), ("127.0.0.1", port))
+            .await
+            .unwrap();
+
+        let mut buffer = vec![0; 65535];
+        let mut keepalives = 0;
+        while keepalives < 3 {
+            let (size, _) =
+                tokio::time::timeout(Duration::from_secs(1), socket.recv_from(&mut buffer))
+                    .await
+                    .expect("no keepalive received")
+                    .unwrap();
+            if &buffer[..size] == KEEPALIVE_JSON.as_bytes() {
+                keepalives += 1;
+            }
+        }
+    }
+
+    /// Tests that `"whoami"` echoes the address the request was sent from.
+    #[tokio::test]
+    async fn test_whoami_echoes_source_address() {
+        let port = free_port();
+        tokio::spawn(run_server(
+            ServerConfig::new(port, 0),
+            ServerState::new(2, 1_000, DEFAULT_STEP),
+        ));
+        sleep(Duration::from_millis(100)).await;
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let request = Request {
+            task: Task::Whoami,
+            ..Default::default()
+        };
+        socket
+            .send_to(request.to_json().unwrap().as_bytes(), ("127.0.0.1", port))
+            .await
+            .unwrap();
+
+        let mut buffer = vec![0; 65535];
+        let (size, _) = socket.recv_from(&mut buffer).await.unwrap();
+        let response = Response::from_json(&String::from_utf8_lossy(&buffer[..size])).unwrap();
+        assert_eq!(response.task, Task::Whoami);
+        assert_eq!(
+            response.address,
+            Some(socket.local_addr().unwrap().to_string())
+        );
+    }
+
+    /// Tests that a server bound to the loopback interface serves a client on loopback.
+    #[test]
+    fn test_bind_addr_loopback() {
+        let (result, saved) =
+            run_configured(ServerState::new(2, 1_000, DEFAULT_STEP), 0, |config| {
+                config.bind_addr = Some(IpAddr::V4(Ipv4Addr::LOCALHOST));
+            });
+        assert_eq!(result.count, 168);
+        assert_eq!(saved.lines().count(), 168);
+    }
+
+    /// Tests that a server bound to `[::]` serves a client given the bracketed IPv6
+    /// literal `[::1]`.
+    #[test]
+    fn test_ipv6_client_and_server() {
+        let server_state = ServerState::new(2, 1_000, DEFAULT_STEP);
+        let (result, saved) = run_served_from(server_state, 0, "[::1]", |config| {
+            config.bind_addr = Some(IpAddr::V6(Ipv6Addr::UNSPECIFIED));
+        });
+        assert_eq!(result.count, 168);
+        assert_eq!(saved.lines().count(), 168);
+    }
+
+    /// Tests that a server without clients saves its results and exits once idle,
+    /// while messages keep an active server alive.
+    #[tokio::test]
+    async fn test_idle_timeout_shuts_down() {
+        let port = free_port();
+        let mut config = ServerConfig::new(port, 0);
+        config.idle_timeout = Some(Duration::from_millis(300));
+        let mut server_state = ServerState::new(2, 1_000_000, DEFAULT_STEP);
+        server_state.output_path =
+            std::env::temp_dir().join(format!("primesocket_idle_{}.txt", port));
+        let output_path = server_state.output_path.clone();
+        let started = Instant::now();
+        let server = tokio::spawn(run_server(config, server_state));
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let progress = Request {
+            task: Task::Progress,
+            ..Default::default()
+        };
+        for _ in 0..4 {
+            sleep(Duration::from_millis(150)).await;
+            socket
+                .send_to(progress.to_json().unwrap().as_bytes(), ("127.0.0.1", port))
+                .await
+                .unwrap();
+        }
+        assert!(!server.is_finished());
+
+        let result = timeout(Duration::from_secs(2), server)
+            .await
+            .expect("idle server kept running")
+            .unwrap()
+            .unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(900));
+        assert_eq!(result.summary.end, 1_000_000);
+        assert!(output_path.exists());
+        std::fs::remove_file(&output_path).unwrap();
+    }
+
+    /// Tests that a run with checksums on both ends completes with the same primes.
+    #[test]
+    fn test_checksum_run() {
+        let (result, saved) = run_configured(ServerState::new(2, 100_000, 5_000), 0, |config| {
+            config.checksum = true;
+        });
+        assert_eq!(result.count, 9_592);
+        assert_eq!(saved.lines().count(), 9_592);
+    }
+
+    /// Tests that a spawned server keeps running until stopped, then saves its results,
+    /// shuts its runtime down so the port is free again, and reports its result.
+    #[test]
+    fn test_spawn_server_stop_lifecycle() {
+        pyo3::prepare_freethreaded_python();
+        let port = free_port();
+        let output_path = std::env::temp_dir().join(format!("primesocket_spawn_{}.txt", port));
+        let mut handle = spawn_with_options(
+            port,
+            Some(1_000_000),
+            None,
+            ServerOptions {
+                output: Some(output_path.display().to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        thread::sleep(Duration::from_millis(200));
+        assert!(handle.is_running());
+
+        let result = Python::with_gil(|py| {
+            handle.stop(py);
+            assert!(!handle.is_running());
+            // Stopping again is harmless, and the result is kept.
+            handle.stop(py);
+            handle.result(py).unwrap()
+        });
+        assert_eq!(result.summary.end, 1_000_000);
+        assert!(output_path.exists());
+        std::net::UdpSocket::bind(("0.0.0.0", port)).expect("runtime still holds the port");
+        std::fs::remove_file(&output_path).unwrap();
+    }
+
+    /// Tests that a client bursting past the rate limit is throttled with
+    /// `"rate_limited"`, while another client is still served.
+    #[tokio::test]
+    async fn test_rate_limit_throttles_bursts() {
+        let port = free_port();
+        let mut config = ServerConfig::new(port, 0);
+        config.max_requests_per_second = Some(5);
+        tokio::spawn(run_server(
+            config,
+            ServerState::new(2, 1_000_000, DEFAULT_STEP),
+        ));
+        sleep(Duration::from_millis(100)).await;
+
+        let request = Request {
+            task: Task::Progress,
+            ..Default::default()
+        };
+        let request = request.to_json().unwrap();
+        let spammer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        for _ in 0..20 {
+            spammer
+                .send_to(request.as_bytes(), ("127.0.0.1", port))
+                .await
+                .unwrap();
+        }
+        let mut buffer = vec![0; 65535];
+        let mut statuses = Vec::new();
+        while let Ok(received) =
+            timeout(Duration::from_millis(300), spammer.recv_from(&mut buffer)).await
+        {
+            let (size, _) = received.unwrap();
+            let response = Response::from_json(&String::from_utf8_lossy(&buffer[..size])).unwrap();
+            statuses.push((response.task, response.status));
+        }
+        let limited = statuses
+            .iter()
+            .filter(|(task, status)| *task == Task::Error && status == "rate_limited")
+            .count();
+        assert_eq!(statuses.len(), 20);
+        assert!((13..=15).contains(&limited), "{} rate limited", limited);
+
+        let other = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        other
+            .send_to(request.as_bytes(), ("127.0.0.1", port))
+            .await
+            .unwrap();
+        let (size, _) = timeout(Duration::from_secs(2), other.recv_from(&mut buffer))
+            .await
+            .unwrap()
+            .unwrap();
+        let response = Response::from_json(&String::from_utf8_lossy(&buffer[..size])).unwrap();
+        assert_eq!(response.task, Task::Progress);
+    }
+
+    /// Tests that port `0` is rejected before any socket is bound, even with an otherwise
+    /// runnable configuration.
+    #[test]
+    fn test_start_server_rejects_port_zero() {
+        pyo3::prepare_freethreaded_python();
+        let error = Python::with_gil(|py| {
+            start_with_options(py, 0, Some(100), None, ServerOptions::default())
+                .unwrap_err()
+                .to_string()
+        });
+        assert!(
+            error.contains("port must be between 1 and 65535"),
+            "{}",
+            error
+        );
+
+        let error = Python::with_gil(|py| {
+            start_client_with_options(
+                py,
+                "127.0.0.1",
+                0,
+                None,
+                &ClientOptions {
+                    timeout_seconds: Some(1),
+                    ..Default::default()
+                },
+            )
+        })
+        .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("port must be between 1 and 65535"));
+        let error = Python::with_gil(|py| {
+            download_with_options(
+                py,
+                "127.0.0.1",
+                0,
+                None,
+                None,
+                None,
+                &ClientOptions {
+                    timeout_seconds: Some(1),
+                    ..Default::default()
+                },
+            )
+        })
+        .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("port must be between 1 and 65535"));
+    }
+
+    /// Tests that a bind address that is not an IP address is rejected before the server starts.
+    #[test]
+    fn test_start_server_rejects_bind_addr() {
+        pyo3::prepare_freethreaded_python();
+        for addr in ["localhost", "127.0.0.1:4000", "256.0.0.1"] {
+            let result = Python::with_gil(|py| {
+                start_with_options(
+                    py,
+                    free_port(),
+                    Some(100),
+                    None,
+                    ServerOptions {
+                        bind_addr: Some(addr.to_string()),
+                        ..Default::default()
+                    },
+                )
+            });
+            assert!(result.is_err(), "{}", addr);
+        }
+    }
+
+    /// Tests that a dual-stack server serves an IPv4 and an IPv6 client.
+    #[tokio::test]
+    async fn test_dual_stack_serves_both_families() {
+        let port = free_port();
+        let mut config = ServerConfig::new(port, 0);
+        config.dual_stack = true;
+        tokio::spawn(run_server(config, ServerState::new(2, 1_000, DEFAULT_STEP)));
+        sleep(Duration::from_millis(100)).await;
+
+        let request = Request {
+            task: Task::Whoami,
+            ..Default::default()
+        };
+        let request = request.to_json().unwrap();
+        let v4 = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let v6 = UdpSocket::bind("[::1]:0").await.unwrap();
+        v4.send_to(request.as_bytes(), ("127.0.0.1", port))
+            .await
+            .unwrap();
+        v6.send_to(request.as_bytes(), ("::1", port)).await.unwrap();
+
+        let mut buffer = vec![0; 65535];
+        for (socket, expected) in [
+            (
+                &v4,
+                format!("[::ffff:127.0.0.1]:{}", v4.local_addr().unwrap().port()),
+            ),
+            (&v6, v6.local_addr().unwrap().to_string()),
+        ] {
+            let (size, _) = timeout(Duration::from_secs(2), socket.recv_from(&mut buffer))
+                .await
+                .unwrap()
+                .unwrap();
+            let response = Response::from_json(&String::from_utf8_lossy(&buffer[..size])).unwrap();
+            assert_eq!(response.task, Task::Whoami);
+            assert_eq!(response.address, Some(expected));
+        }
+    }
+
+    /// Tests that responses are split to fit a negotiated datagram size.
+    ///
+    /// The client announces a 256-byte limit; the `"range"` response carrying
+    /// the base primes must arrive as several chunks, none larger than the limit,
+    /// that reassemble into a valid response.
+    #[tokio::test]
+    async fn test_negotiated_payload_splits_responses() {
+        let port = free_port();
+        let mut server_state = ServerState::new(2, 100_000_000, DEFAULT_STEP);
+        server_state.base_primes = sieve_segment(2, 10_000, server_state.primes.clone());
+        tokio::spawn(run_server(ServerConfig::new(port, 0), server_state));
+        sleep(Duration::from_millis(100)).await;
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let request = Request {
+            task: Task::Start,
+            max_payload: Some(256),
+            ..Default::default()
+        };
+        socket
+            .send_to(request.to_json().unwrap().as_bytes(), ("127.0.0.1", port))
+            .await
+            .unwrap();
+
+        let mut reassembler = Reassembler::new();
+        let mut datagrams = 0;
+        let mut buffer = vec![0; 65535];
+        let message = loop {
+            let (size, src) = socket.recv_from(&mut buffer).await.unwrap();
+            datagrams += 1;
+            assert!(size <= 256);
+
+            let chunk = Chunk::from_bytes(&buffer[..size]).unwrap();
+            if let Some(message) = reassembler.push(src, chunk).unwrap() {
+                break message;
+            }
+        };
+
+        let response = Response::from_json(&String::from_utf8(message).unwrap()).unwrap();
+        assert!(datagrams > 1);
+        assert_eq!(response.task, Task::Range);
+        assert_eq!(response.max_payload, Some(256));
+        assert_eq!(response.primes.unwrap().len(), 1_229);
+    }
+
+    /// Tests that a client connecting is logged at info level through the `log` crate.
+    ///
+    /// The test re-runs itself in a child process, where a capturing logger is
+    /// installed before the run, so that no other test sets the level meanwhile.
+    #[test]
+    fn test_connect_logged_at_info() {
+        struct CapturingLogger(std::sync::Mutex<Vec<(log::Level, String)>>);
+
+        impl log::Log for CapturingLogger {
+            fn enabled(&self, _: &log::Metadata) -> bool {
+                true
+            }
+
+            fn log(&self, record: &log::Record) {
+                let entry = (record.level(), record.args().to_string());
+                self.0.lock().unwrap().push(entry);
+            }
+
+            fn flush(&self) {}
+        }
+
+        static LOGGER: CapturingLogger = CapturingLogger(std::sync::Mutex::new(Vec::new()));
+
+        if std::env::var_os("PRIMESOCKET_CAPTURE_CHILD").is_some() {
+            log::set_logger(&LOGGER).unwrap();
+            run_small(100, 1);
+            let records = LOGGER.0.lock().unwrap();
+            assert!(records.iter().any(|(level, message)| {
+                *level == log::Level::Info && message.starts_with("🔗 New client connected")
+            }));
+            assert!(records.iter().all(|(level, _)| *level <= log::Level::Info));
+            return;
+        }
+
+        let output = Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "server::server::tests::test_connect_logged_at_info",
+            ])
+            .env("PRIMESOCKET_CAPTURE_CHILD", "1")
+            .output()
+            .unwrap();
+
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+
+    /// Tests that verbose logs never reach stdout.
+    ///
+    /// The test re-runs itself in a child process performing a verbose run, then
+    /// checks the captured streams: log lines must all be on stderr, leaving
+    /// stdout free for data output.
+    #[test]
+    fn test_verbose_logs_go_to_stderr() {
+        if std::env::var_os("PRIMESOCKET_LOG_CHILD").is_some() {
+            run_small(100, 2);
+            return;
+        }
+
+        let output = Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "server::server::tests::test_verbose_logs_go_to_stderr",
+                "--nocapture",
+            ])
+            .env("PRIMESOCKET_LOG_CHILD", "1")
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        assert!(output.status.success());
+        for marker in ["🚀", "🔗", "📤", "📩", "✅", "⚠️", "❌"] {
+            assert!(!stdout.contains(marker), "log line on stdout: {}", stdout);
+        }
+        assert!(stderr.contains("🚀 Server started"));
+        assert!(stderr.contains("🔗 New client connected"));
+        assert!(stderr.contains("📩 Sending request"));
+    }
+
+    /// Tests that the server binds once a briefly held port is released.
+    #[tokio::test]
+    async fn test_bind_retries_until_port_is_released() {
+        let holder = std::net::UdpSocket::bind("0.0.0.0:0").unwrap();
+        let port = holder.local_addr().unwrap().port();
+
+        let mut config = ServerConfig::new(port, 0);
+        config.bind_retries = 20;
+        config.bind_retry_delay = Duration::from_millis(50);
+        let bind = tokio::spawn(async move { bind_socket(&config).await });
+
+        sleep(Duration::from_millis(200)).await;
+        drop(holder);
+
+        let socket = bind.await.unwrap().unwrap();
+        assert_eq!(socket.local_addr().unwrap().port(), port);
+    }
+
+    /// Tests that binding gives up once the retry budget is exhausted.
+    #[tokio::test]
+    async fn test_bind_retries_exhausted() {
+        let holder = std::net::UdpSocket::bind("0.0.0.0:0").unwrap();
+        let port = holder.local_addr().unwrap().port();
+
+        let mut config = ServerConfig::new(port, 0);
+        config.bind_retries = 2;
+        config.bind_retry_delay = Duration::from_millis(10);
+
+        assert!(bind_socket(&config).await.is_err());
+        drop(holder);
+    }
+
+    /// Tests that waiting on a long-held lock emits warnings once past the threshold.
+    #[tokio::test]
+    async fn test_lock_state_warns_on_long_hold() {
+        let mutex = Arc::new(Mutex::new(0u32));
+        let warnings = std::sync::Mutex::new(Vec::new());
+
+        let guard = mutex.clone().lock_owned().await;
+        let release = tokio::spawn(async move {
+            sleep(Duration::from_millis(120)).await;
+            drop(guard);
+        });
+
+        let value = *lock_state(&mutex, Duration::from_millis(50), |waited| {
+            warnings.lock().unwrap().push(waited)
+        })
+        .await;
+        assert_eq!(value, 0);
+        release.await.unwrap();
+
+        let warnings = warnings.into_inner().unwrap();
+        assert!(!warnings.is_empty());
+        assert!(warnings[0] >= Duration::from_millis(50));
+        assert!(warnings.windows(2).all(|w| w[0] < w[1]));
+
+        let quiet = std::sync::atomic::AtomicBool::new(true);
+        let _guard = lock_state(&mutex, Duration::from_millis(50), |_| {
+            quiet.store(false, std::sync::atomic::Ordering::SeqCst)
+        })
+        .await;
+        assert!(quiet.into_inner());
+    }
+
+    /// Tests that the sender spaces datagrams by the interval of its send rate.
+    #[tokio::test]
+    async fn test_sender_respects_send_rate() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let target = receiver.local_addr().unwrap();
+        let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let (tx, rx) = mpsc::channel::<(Vec<u8>, SocketAddr, usize)>(10);
+        for n in 0..4 {
+            tx.send((n.to_string().into_bytes(), target, MAX_DATAGRAM_SIZE))
+                .await
+                .unwrap();
+        }
+        drop(tx);
+
+        tokio::spawn(send_responses(
+            socket,
+            Arc::new(Mutex::new(rx)),
+            Some(20),
+            false,
+        ));
+
+        let mut buffer = vec![0; 64];
+        let mut arrivals = Vec::new();
+        for _ in 0..4 {
+            receiver.recv_from(&mut buffer).await.unwrap();
+            arrivals.push(Instant::now());
+        }
+        for pair in arrivals.windows(2) {
+            assert!(pair[1] - pair[0] >= Duration::from_millis(40));
+        }
+    }
+
+    /// Tests that a panicking sender is restarted and keeps draining the queue.
+    #[tokio::test]
+    async fn test_supervised_sender_restarts_after_panic() {
+        let (tx, rx) = mpsc::channel::<u32>(10);
+        let queue = Arc::new(Mutex::new(rx));
+        let delivered = Arc::new(Mutex::new(Vec::new()));
+        let attempts = Arc::new(std::sync::atomic::AtomicU32::new(0));
+
+        for n in 0..3 {
+            tx.send(n).await.unwrap();
+        }
+        drop(tx);
+
+        let (queue_clone, delivered_clone, attempts_clone) =
+            (queue.clone(), delivered.clone(), attempts.clone());
+        let finished = supervise_sender(
+            move || {
+                let queue = queue_clone.clone();
+                let delivered = delivered_clone.clone();
+                let attempt = attempts_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                async move {
+                    let mut queue = queue.lock().await;
+                    while let Some(n) = queue.recv().await {
+                        if attempt == 0 {
+                            panic!("simulated sender failure");
+                        }
+                        delivered.lock().await.push(n);
+                    }
+                }
+            },
+            3,
+        )
+        .await;
+
+        assert!(finished);
+        assert_eq!(attempts.load(std::sync::atomic::Ordering::SeqCst), 2);
+        assert_eq!(*delivered.lock().await, vec![1, 2]);
+    }
+
+    /// Tests that the supervisor gives up and closes the queue when the sender keeps failing.
+    #[tokio::test]
+    async fn test_supervised_sender_gives_up() {
+        let (tx, rx) = mpsc::channel::<u32>(10);
+        let queue = Arc::new(Mutex::new(rx));
+
+        let finished = supervise_sender(
+            move || {
+                let queue = queue.clone();
+                async move {
+                    let _queue = queue.lock().await;
+                    panic!("simulated sender failure");
+                }
+            },
+            2,
+        )
+        .await;
+
+        assert!(!finished);
+        assert!(tx.is_closed());
+    }
+
+    /// Tests that `π(x)` is recorded at every interval of a small run.
+    #[test]
+    fn test_pi_checkpoints_small_run() {
+        let mut server_state = ServerState::new(2, 1_000, 250);
+        server_state.pi_interval = Some(100);
+        server_state.pi_output_path =
+            std::env::temp_dir().join(format!("primesocket_pi_{}.csv", free_port()));
+        let pi_output_path = server_state.pi_output_path.clone();
+
+        run_state(server_state, 0);
+        let csv = std::fs::read_to_string(&pi_output_path).unwrap();
+        std::fs::remove_file(&pi_output_path).unwrap();
+
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows.len(), 11);
+        assert_eq!(rows[0], "x,pi_x");
+        assert_eq!(rows[1], "100,25");
+        assert_eq!(rows[5], "500,95");
+        assert_eq!(rows[10], "1000,168");
+    }
+
+    /// Tests that a run whose step exceeds the whole range finishes after one range.
+    #[test]
+    fn test_single_range_run() {
+        let (result, saved) = run_small(50, 0);
+
+        let primes: Vec<u64> = saved.lines().map(|line| line.parse().unwrap()).collect();
+        assert_eq!(primes, crate::utils::sieve::primes_up_to(50));
+        assert_eq!(result.largest, Some(47));
+    }
+
+    /// Tests that a run restricted to `1 mod 4` only saves primes of that class.
+    #[test]
+    fn test_residue_class_run_saves_only_class_primes() {
+        let mut server_state = ServerState::new(2, 5_000, DEFAULT_STEP);
+        server_state.restrict_to_residue(1, 4);
+
+        let (result, saved) = run_state(server_state, 0);
+        let primes: Vec<u64> = saved.lines().map(|line| line.parse().unwrap()).collect();
+
+        let expected: Vec<u64> = crate::utils::sieve::primes_up_to(5_000)
+            .into_iter()
+            .filter(|p| p % 4 == 1)
+            .collect();
+        assert_eq!(primes, expected);
+        assert_eq!(result.count, expected.len() as u64);
+    }
+
+    /// Tests that a run over two disjoint ranges saves exactly the primes of their union.
+    #[test]
+    fn test_disjoint_ranges_run() {
+        let mut server_state = ServerState::new(2, 2, DEFAULT_STEP);
+        server_state.restrict_to_ranges(&[(5_000, 12_000), (100, 1_500)]);
+
+        let (result, saved) = run_state(server_state, 0);
+
+        let primes: Vec<u64> = saved.lines().map(|line| line.parse().unwrap()).collect();
+        let expected: Vec<u64> = crate::utils::sieve::primes_up_to(12_000)
+            .into_iter()
+            .filter(|p| (100..=1_500).contains(p) || (5_000..=12_000).contains(p))
+            .collect();
+        assert_eq!(primes, expected);
+        assert_eq!(result.count, expected.len() as u64);
+    }
+
+    /// Tests that two shards of `[2, 1000]` are contiguous and together save exactly
+    /// its primes, each to its own output file.
+    #[test]
+    fn test_shards_cover_range() {
+        let mut primes = Vec::new();
+        let mut bounds = Vec::new();
+        for index in 0..2 {
+            let mut server_state = ServerState::new(2, 1_000, DEFAULT_STEP);
+            assert!(server_state.restrict_to_shard(index, 2));
+            bounds.push((server_state.start, server_state.end));
+
+            let (result, saved) = run_state(server_state, 0);
+            let shard: Vec<u64> = saved.lines().map(|line| line.parse().unwrap()).collect();
+            assert_eq!(result.count, shard.len() as u64);
+            primes.extend(shard);
+        }
+
+        assert_eq!(bounds, vec![(2, 500), (501, 1_000)]);
+        assert_eq!(primes, crate::utils::sieve::primes_up_to(1_000));
+        assert!(!ServerState::new(2, 3, DEFAULT_STEP).restrict_to_shard(0, 3));
+        assert_eq!(
+            shard_output_path(Path::new("out/primes.txt"), 1),
+            PathBuf::from("out/primes.shard1.txt")
+        );
+        assert_eq!(
+            shard_output_path(Path::new("primes"), 0),
+            PathBuf::from("primes.shard0")
+        );
+    }
+
+    /// Tests that the in-process computation and a run over loopback UDP, both driving
+    /// the same handler, find identical primes.
+    #[test]
+    fn test_local_matches_network_run() {
+        let (result, saved) = run_state(ServerState::new(2, 10_000, 1_000), 0);
+        let networked: Vec<u64> = saved.lines().map(|line| line.parse().unwrap()).collect();
+
+        let local = compute_primes_local(10_000, 1_000).unwrap();
+        assert_eq!(local, networked);
+        assert_eq!(result.count, local.len() as u64);
+    }
+
+    /// Tests that the primes saved at completion are sorted and free of duplicates
+    /// when saves arrive out of order, overlap, and repeat the seed primes.
+    #[test]
+    fn test_saved_primes_sorted_and_unique() {
+        let mut server_state = ServerState::new(2, 3_000, DEFAULT_STEP);
+        server_state.output_path =
+            std::env::temp_dir().join(format!("primesocket_finalize_{}.txt", std::process::id()));
+        let client: SocketAddr = "127.0.0.1:4000".parse().unwrap();
+        for _ in 0..3 {
+            handler(
+                &mut server_state,
+                Request {
+                    task: Task::Start,
+                    ..Default::default()
+                },
+                client,
+            );
+        }
+        let primes = crate::utils::sieve::primes_up_to(3_000);
+        let within = |start: u64, end: u64| -> Vec<u64> {
+            primes
+                .iter()
+                .copied()
+                .filter(|p| (start..=end).contains(p))
+                .rev()
+                .collect()
+        };
+        for (start, end) in [(2_002, 3_000), (2, 1_002), (1_002, 2_002), (900, 1_500)] {
+            handler(
+                &mut server_state,
+                Request {
+                    task: Task::Save,
+                    start: Some(start),
+                    end: Some(end),
+                    primes: Some(within(start, end)),
+                    ..Default::default()
+                },
+                client,
+            );
+        }
+        server_state.primes.extend([97, 2, 3]);
+        server_state.mark_completed();
+
+        save_results(&mut server_state);
+
+        let saved: Vec<u64> = std::fs::read_to_string(&server_state.output_path)
+            .unwrap()
+            .lines()
+            .map(|line| line.parse().unwrap())
+            .collect();
+        std::fs::remove_file(&server_state.output_path).ok();
+        assert_eq!(saved, primes);
+    }
+
+    /// Tests a run over a range beyond `u32::MAX`, up to 5_000_000_000.
+    #[test]
+    fn test_run_beyond_u32() {
+        let mut server_state = ServerState::new(2, 2, DEFAULT_STEP);
+        server_state.restrict_to_ranges(&[(4_999_990_000, 5_000_000_000)]);
+
+        let (result, saved) = run_state(server_state, 0);
+
+        let primes: Vec<u64> = saved.lines().map(|line| line.parse().unwrap()).collect();
+        let expected: Vec<u64> = (4_999_990_000..=5_000_000_000)
+            .filter(|&n| crate::utils::primality::is_prime(n))
+            .collect();
+        assert_eq!(primes, expected);
+        assert_eq!(result.count, expected.len() as u64);
+        assert!(primes.iter().all(|&p| p > u32::MAX as u64));
+    }
+
+    /// Tests that `start_server` writes the primes to the `output` path it is given.
+    #[test]
+    fn test_start_server_writes_to_output_path() {
+        let port = free_port();
+        let output = std::env::temp_dir().join(format!("primesocket_output_{}.txt", port));
+        let output_arg = output.to_string_lossy().into_owned();
+
+        pyo3::prepare_freethreaded_python();
+        let server = thread::spawn(move || {
+            Python::with_gil(|py| {
+                start_with_options(
+                    py,
+                    port,
+                    Some(100),
+                    None,
+                    ServerOptions {
+                        output: Some(output_arg),
+                        ..Default::default()
+                    },
+                )
+                .map(|result| result.count)
+            })
+        });
+        thread::sleep(Duration::from_millis(200));
+        Python::with_gil(|py| {
+            start_client_with_options(
+                py,
+                "127.0.0.1",
+                port,
+                None,
+                &ClientOptions {
+                    timeout_seconds: Some(5),
+                    ..Default::default()
+                },
+            )
+        })
+        .unwrap();
+
+        assert_eq!(server.join().unwrap().unwrap(), 25);
+        let saved = std::fs::read_to_string(&output).unwrap();
+        std::fs::remove_file(&output).unwrap();
+        let lines: Vec<&str> = saved.lines().collect();
+        assert_eq!(lines.len(), 25);
+        assert_eq!(lines.first(), Some(&"2"));
+        assert_eq!(lines.last(), Some(&"97"));
+    }
+
+    /// Tests that the ranges handed out by `start_server` span `assignment_unit`, the
+    /// step of the computation.
+    #[test]
+    fn test_start_server_assignment_unit_sets_step() {
+        let port = free_port();
+        let output = std::env::temp_dir().join(format!("primesocket_step_{}.txt", port));
+        let output_arg = output.to_string_lossy().into_owned();
+
+        pyo3::prepare_freethreaded_python();
+        let server = thread::spawn(move || {
+            Python::with_gil(|py| {
+                start_with_options(
+                    py,
+                    port,
+                    Some(10_000),
+                    None,
+                    ServerOptions {
+                        assignment_unit: Some(250),
+                        output: Some(output_arg),
+                        ..Default::default()
+                    },
+                )
+                .map(|result| result.count)
+            })
+        });
+        thread::sleep(Duration::from_millis(200));
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let request = Request {
+            task: Task::Start,
+            ..Default::default()
+        };
+        socket
+            .send_to(request.to_json().unwrap().as_bytes(), ("127.0.0.1", port))
+            .unwrap();
+        let mut buffer = [0u8; MAX_DATAGRAM_SIZE];
+        let size = socket.recv(&mut buffer).unwrap();
+        let range = Response::from_json(&String::from_utf8_lossy(&buffer[..size])).unwrap();
+        assert_eq!((range.start, range.end), (Some(2), Some(252)));
+
+        Python::with_gil(|py| {
+            start_client_with_options(
+                py,
+                "127.0.0.1",
+                port,
+                None,
+                &ClientOptions {
+                    timeout_seconds: Some(5),
+                    ..Default::default()
+                },
+            )
+        })
+        .unwrap();
+
+        assert_eq!(server.join().unwrap().unwrap(), 1_229);
+        std::fs::remove_file(&output).unwrap();
+    }
+
+    /// Tests that `step` sets the span of the ranges handed out, as its alias
+    /// `assignment_unit` does, and that it must be positive and not given along with it.
+    #[test]
+    fn test_configure_server_step() {
+        pyo3::prepare_freethreaded_python();
+        let configure = |assignment_unit: Option<u64>, step: Option<u32>| {
+            configure_server(
+                free_port(),
+                Some(10_000),
+                None,
+                ServerOptions {
+                    assignment_unit,
+                    step,
+                    ..Default::default()
+                },
+            )
+        };
+
+        let (_, mut server_state) = configure(None, Some(500)).unwrap();
+        let request = Request {
+            task: Task::Start,
+            ..Default::default()
+        };
+        let client = "127.0.0.1:4000".parse().unwrap();
+        let range = handler(&mut server_state, request, client);
+        assert_eq!((range.start, range.end), (Some(2), Some(502)));
+
+        let Err(error) = configure(None, Some(0)) else {
+            panic!("a step of 0 was accepted");
+        };
+        assert!(
+            error.to_string().contains("Invalid assignment unit 0"),
+            "{}",
+            error
+        );
+        let Err(error) = configure(Some(500), Some(500)) else {
+            panic!("both aliases was accepted");
+        };
+        assert!(error.to_string().contains("aliases"), "{}", error);
+    }
+
+    /// Tests that `prime_count_tolerance` is rejected unless it is a finite factor of at
+    /// least 1, which would otherwise refuse every save.
+    #[test]
+    fn test_configure_server_prime_count_tolerance() {
+        pyo3::prepare_freethreaded_python();
+        let configure = |tolerance: f64| {
+            configure_server(
+                free_port(),
+                Some(10_000),
+                None,
+                ServerOptions {
+                    prime_count_tolerance: Some(tolerance),
+                    ..Default::default()
+                },
+            )
+        };
+
+        let (_, server_state) = configure(1.5).unwrap();
+        assert_eq!(server_state.prime_count_tolerance, 1.5);
+        for tolerance in [f64::NAN, f64::INFINITY, 0.0, -1.0, 0.99] {
+            let Err(error) = configure(tolerance) else {
+                panic!("a tolerance of {} was accepted", tolerance);
+            };
+            assert!(
+                error.to_string().contains("Invalid prime count tolerance"),
+                "{}",
+                error
+            );
+        }
+    }
+
+    /// Tests that a Python progress callback passed to `start_server` sees `last_checked`
+    /// advance up to `end`.
+    #[test]
+    fn test_start_server_progress_callback() {
+        let port = free_port();
+        let output = std::env::temp_dir().join(format!("primesocket_callback_{}.txt", port));
+        let output_arg = output.to_string_lossy().into_owned();
+
+        pyo3::prepare_freethreaded_python();
+        let server = thread::spawn(move || {
+            Python::with_gil(|py| {
+                let events = pyo3::types::PyList::empty(py);
+                let globals = pyo3::types::PyDict::new(py);
+                globals.set_item("events", &events).unwrap();
+                let callback = py
+                    .eval(
+                        c"lambda last_checked, end: events.append((last_checked, end))",
+                        Some(&globals),
+                        None,
+                    )
+                    .unwrap();
+                start_with_options(
+                    py,
+                    port,
+                    Some(10_000),
+                    None,
+                    ServerOptions {
+                        progress_callback: Some(callback.unbind()),
+                        output: Some(output_arg),
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+                events.extract::<Vec<(u64, u64)>>().unwrap()
+            })
+        });
+        thread::sleep(Duration::from_millis(200));
+        Python::with_gil(|py| {
+            start_client_with_options(
+                py,
+                "127.0.0.1",
+                port,
+                None,
+                &ClientOptions {
+                    timeout_seconds: Some(5),
+                    ..Default::default()
+                },
+            )
+        })
+        .unwrap();
+
+        let events = server.join().unwrap();
+        std::fs::remove_file(&output).unwrap();
+        assert!(!events.is_empty());
+        assert!(events.iter().all(|&(_, end)| end == 10_000));
+        assert!(events.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(events.last(), Some(&(10_000, 10_000)));
+    }
+
+    /// Tests that a client started while holding the GIL lets a server spawned in the
+    /// same process call its progress callback, as in `with spawn_server(...):`.
+    #[test]
+    fn test_spawned_server_callback_runs_during_client() {
+        pyo3::prepare_freethreaded_python();
+        let port = free_port();
+        let output = std::env::temp_dir().join(format!("primesocket_spawn_cb_{}.txt", port));
+        Python::with_gil(|py| {
+            let events = pyo3::types::PyList::empty(py);
+            let globals = pyo3::types::PyDict::new(py);
+            globals.set_item("events", &events).unwrap();
+            let callback = py
+                .eval(
+                    c"lambda last_checked, end: events.append(last_checked)",
+                    Some(&globals),
+                    None,
+                )
+                .unwrap();
+            let mut handle = spawn_with_options(
+                port,
+                Some(10_000),
+                None,
+                ServerOptions {
+                    progress_callback: Some(callback.unbind()),
+                    output: Some(output.display().to_string()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            py.allow_threads(|| thread::sleep(Duration::from_millis(200)));
+
+            start_client_with_options(
+                py,
+                "127.0.0.1",
+                port,
+                None,
+                &ClientOptions {
+                    timeout_seconds: Some(5),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            assert!(
+                !events.is_empty(),
+                "no callback ran while the client was running"
+            );
+            assert_eq!(handle.result(py).unwrap().count, 1_229);
+        });
+        std::fs::remove_file(&output).unwrap();
+    }
+
+    /// Tests that a server and a client agreeing on bincode complete a run.
+    #[cfg(feature = "binary")]
+    #[test]
+    fn test_bincode_run() {
+        pyo3::prepare_freethreaded_python();
+        let port = free_port();
+        let mut config = ServerConfig::new(port, 0);
+        config.format = WireFormat::Bincode;
+        let mut server_state = ServerState::new(2, 10_000, DEFAULT_STEP);
+        server_state.output_path =
+            std::env::temp_dir().join(format!("primesocket_bincode_{}.txt", port));
+        let output = server_state.output_path.clone();
+
+        let server = thread::spawn(move || {
+            Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(run_server(config, server_state))
+        });
+        thread::sleep(Duration::from_millis(200));
+        let summary = Python::with_gil(|py| {
+            start_client_with_options(
+                py,
+                "127.0.0.1",
+                port,
+                None,
+                &ClientOptions {
+                    timeout_seconds: Some(5),
+                    format: Some("bincode".to_string()),
+                    ..Default::default()
+                },
+            )
+        })
+        .unwrap();
+
+        let result = server.join().unwrap().unwrap();
+        std::fs::remove_file(&output).unwrap();
+        assert_eq!(result.count, 1_229);
+        assert_eq!(summary.ranges, 10);
+    }
+
+    /// Tests that a residue class sharing a factor with its modulus is rejected.
+    #[test]
+    fn test_start_server_rejects_non_coprime_residue() {
+        pyo3::prepare_freethreaded_python();
+        let result = Python::with_gil(|py| {
+            start_with_options(
+                py,
+                free_port(),
+                Some(100),
+                None,
+                ServerOptions {
+                    residue: Some((2, 4)),
+                    ..Default::default()
+                },
+            )
+        });
+
+        assert!(result.is_err());
+    }
+
+    /// Tests that an unsupported output radix is rejected before the server starts.
+    #[test]
+    fn test_start_server_rejects_output_radix() {
+        pyo3::prepare_freethreaded_python();
+        let result = Python::with_gil(|py| {
+            start_with_options(
+                py,
+                free_port(),
+                Some(100),
+                None,
+                ServerOptions {
+                    output_radix: Some(8),
+                    ..Default::default()
+                },
+            )
+        });
+
+        assert!(result.is_err());
+    }
 }
@@ -0,0 +1,116 @@
+use super::progress::ProgressEvent;
+use crate::utils::chunk::MAX_MESSAGE_SIZE;
+use crate::utils::codec::WireFormat;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// A callback receiving progress events on the reporter thread.
+pub type ProgressCallback = Arc<dyn Fn(ProgressEvent) + Send + Sync>;
+
+/// Holds the network settings of a server run.
+///
+/// Unlike `ServerState`, which tracks the computation itself, the
+/// `ServerConfig` describes how the server talks to the network.
+///
+/// # Fields
+///
+/// * `port` - The UDP port where the server listens.
+/// * `verbose` - Verbosity level for logging.
+/// * `bind_retries` - How many times binding is retried after a failure.
+/// * `bind_retry_delay` - How long to wait between bind attempts.
+/// * `progress_callback` - Called each time `last_checked` advances, if set.
+/// * `lock_warn_threshold` - How long waiting for the state lock may take before a warning is logged.
+/// * `keepalive_interval` - When set, clients holding an assignment receive a keepalive
+///   datagram at this interval, keeping NAT mappings open while they compute.
+/// * `on_complete_cmd` - A shell command run once the results are saved, if set.
+/// * `verify` - Whether the primes are checked with a primality test once the computation
+///   completes, logging any composite found.
+/// * `max_message_size` - The largest message sent or reassembled, in bytes.
+/// * `base_primes_path` - Where the base primes are written when a `"range"` carrying
+///   them inline would exceed `max_message_size`.
+/// * `debug_state_path` - When set, snapshots of the assignments and clients are appended
+///   to this file as JSON lines, every `debug_state_interval`.
+/// * `debug_state_interval` - How often debug snapshots are taken.
+/// * `send_rate` - When set, the most datagrams sent per second; responses are paced
+///   instead of sent in bursts.
+/// * `dual_stack` - When set, the server listens on `[::]` with `IPV6_V6ONLY` disabled,
+///   serving IPv6 and IPv4 clients on one socket; IPv4 clients then appear as
+///   IPv4-mapped addresses (`::ffff:a.b.c.d`).
+/// * `format` - The encoding of requests and responses; clients must use the same.
+/// * `bind_addr` - The address of the interface to listen on. When unset, the server
+///   listens on every interface: `0.0.0.0`, or `[::]` with `dual_stack`.
+/// * `idle_timeout` - When set, the server saves its results and shuts down once no
+///   message arrived for this long.
+/// * `max_requests_per_second` - When set, the most requests per second accepted from
+///   each client address; requests over the limit are answered with `"rate_limited"`.
+/// * `checksum` - Whether datagrams start with the CRC-32 of their content; received
+///   datagrams that don't match are dropped. Clients must use the same setting.
+/// * `stop_requested` - Notified to stop the server from outside, as a `"shutdown"`
+///   request would: the primes found so far are saved and the run ends.
+#[derive(Clone)]
+pub struct ServerConfig {
+    pub port: u16,
+    pub verbose: u8,
+    pub bind_retries: u32,
+    pub bind_retry_delay: Duration,
+    pub progress_callback: Option<ProgressCallback>,
+    pub lock_warn_threshold: Duration,
+    pub keepalive_interval: Option<Duration>,
+    pub on_complete_cmd: Option<String>,
+    pub verify: bool,
+    pub max_message_size: usize,
+    pub base_primes_path: PathBuf,
+    pub debug_state_path: Option<PathBuf>,
+    pub debug_state_interval: Duration,
+    pub send_rate: Option<u32>,
+    pub dual_stack: bool,
+    pub format: WireFormat,
+    pub bind_addr: Option<IpAddr>,
+    pub idle_timeout: Option<Duration>,
+    pub max_requests_per_second: Option<u32>,
+    pub checksum: bool,
+    pub stop_requested: Arc<Notify>,
+}
+
+impl ServerConfig {
+    /// Creates a new `ServerConfig` with the default settings.
+    ///
+    /// Binding is attempted once, without retries.
+    ///
+    /// # Arguments
+    ///
+    /// * `port` - The UDP port where the server listens.
+    /// * `verbose` - Verbosity level for logging.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `ServerConfig`.
+    pub fn new(port: u16, verbose: u8) -> ServerConfig {
+        ServerConfig {
+            port,
+            verbose,
+            bind_retries: 0,
+            bind_retry_delay: Duration::from_millis(500),
+            progress_callback: None,
+            lock_warn_threshold: Duration::from_secs(1),
+            keepalive_interval: None,
+            on_complete_cmd: None,
+            verify: false,
+            max_message_size: MAX_MESSAGE_SIZE,
+            base_primes_path: PathBuf::from("base_primes.txt"),
+            debug_state_path: None,
+            debug_state_interval: Duration::from_secs(10),
+            send_rate: None,
+            dual_stack: false,
+            format: WireFormat::Json,
+            bind_addr: None,
+            idle_timeout: None,
+            max_requests_per_second: None,
+            checksum: false,
+            stop_requested: Arc::new(Notify::new()),
+        }
+    }
+}
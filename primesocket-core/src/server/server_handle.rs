@@ -0,0 +1,132 @@
+use super::run_result::RunResult;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyType;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use tokio::sync::Notify;
+
+/// Controls a server running on a background thread.
+///
+/// The `ServerHandle` is returned by `spawn_server`, which returns as soon as the
+/// server thread is started instead of blocking until the computation is over.
+/// Used as a context manager, the server is stopped when the block exits.
+///
+/// # Fields
+///
+/// * `stop_requested` - Notified to make the server save its results and shut down.
+/// * `thread` - The thread running the server, until it was joined.
+/// * `outcome` - What the run returned, once the thread was joined.
+///
+/// # Example (Python)
+///
+/// ```python
+/// import primesocket_core
+/// with primesocket_core.spawn_server(8080, 1000) as server:
+///     primesocket_core.start_client("127.0.0.1", 8080)
+/// print(server.result().count)
+/// ```
+#[pyclass]
+pub struct ServerHandle {
+    stop_requested: Arc<Notify>,
+    thread: Option<JoinHandle<PyResult<RunResult>>>,
+    outcome: Option<PyResult<RunResult>>,
+}
+
+impl ServerHandle {
+    /// Creates a `ServerHandle` for a server already running on `thread`.
+    ///
+    /// # Arguments
+    ///
+    /// * `stop_requested` - The notification the server stops on.
+    /// * `thread` - The thread running the server and its runtime.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `ServerHandle`.
+    pub fn new(
+        stop_requested: Arc<Notify>,
+        thread: JoinHandle<PyResult<RunResult>>,
+    ) -> ServerHandle {
+        ServerHandle {
+            stop_requested,
+            thread: Some(thread),
+            outcome: None,
+        }
+    }
+
+    /// Waits for the server thread to end and keeps what the run returned.
+    ///
+    /// The GIL is released while waiting, so the progress callback can still run.
+    fn join(&mut self, py: Python<'_>) {
+        if let Some(thread) = self.thread.take() {
+            let outcome = py.allow_threads(|| thread.join()).unwrap_or_else(|_| {
+                Err(PyErr::new::<PyValueError, _>(
+                    "Server thread panicked before returning a result",
+                ))
+            });
+            self.outcome = Some(outcome);
+        }
+    }
+}
+
+#[pymethods]
+impl ServerHandle {
+    /// Checks whether the server is still running.
+    ///
+    /// # Returns
+    ///
+    /// `true` until the computation is over, the server was stopped, or it failed.
+    pub fn is_running(&self) -> bool {
+        self.thread
+            .as_ref()
+            .is_some_and(|thread| !thread.is_finished())
+    }
+
+    /// Stops the server and waits for its runtime to shut down.
+    ///
+    /// The server saves the primes found so far, as on a `"shutdown"` request. Stopping
+    /// a server that already ended does nothing.
+    pub fn stop(&mut self, py: Python<'_>) {
+        if self.thread.is_some() {
+            self.stop_requested.notify_one();
+        }
+        self.join(py);
+    }
+
+    /// Waits for the server to end, without stopping it, and returns its result.
+    ///
+    /// # Returns
+    ///
+    /// The `RunResult` of the run.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error the server failed with, such as a `PyValueError` when the
+    /// socket couldn't be bound.
+    pub fn result(&mut self, py: Python<'_>) -> PyResult<RunResult> {
+        self.join(py);
+        match self.outcome.as_ref() {
+            Some(Ok(result)) => Ok(result.clone()),
+            Some(Err(e)) => Err(e.clone_ref(py)),
+            None => unreachable!("the outcome is kept once the thread is joined"),
+        }
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    /// Stops the server when the `with` block exits; exceptions are not suppressed.
+    #[pyo3(signature = (_exc_type=None, _exc_value=None, _traceback=None))]
+    fn __exit__(
+        &mut self,
+        py: Python<'_>,
+        _exc_type: Option<&Bound<'_, PyType>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> bool {
+        self.stop(py);
+        false
+    }
+}
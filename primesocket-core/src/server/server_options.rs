@@ -0,0 +1,138 @@
+use pyo3::exceptions::{PyAttributeError, PyTypeError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyString};
+
+/// Holds the optional settings of a server, passed by keyword to `start_server` and
+/// `spawn_server`.
+///
+/// Every option is declared once here: both functions take them as `**options` and
+/// hand them to `configure_server`, which validates them.
+///
+/// # Fields
+///
+/// Each field is the keyword argument of the same name, documented by `start_server`;
+/// `None` when it wasn't given.
+#[pyclass(set_all)]
+#[derive(Default)]
+pub struct ServerOptions {
+    pub grace_period_ms: Option<u64>,
+    pub admin_secret: Option<String>,
+    pub bind_retries: Option<u32>,
+    pub bind_retry_delay_ms: Option<u64>,
+    pub output_radix: Option<u32>,
+    pub progress_callback: Option<PyObject>,
+    pub read_only_from: Option<String>,
+    pub max_ranges_per_client: Option<u32>,
+    pub residue: Option<(u64, u64)>,
+    pub assignment_order: Option<String>,
+    pub prime_count_tolerance: Option<f64>,
+    pub pi_interval: Option<u64>,
+    pub pi_output_path: Option<String>,
+    pub keepalive_interval_ms: Option<u64>,
+    pub on_complete_cmd: Option<String>,
+    pub ranges: Option<Vec<(u64, u64)>>,
+    pub unsorted_primes: Option<String>,
+    pub debug_state_path: Option<String>,
+    pub debug_state_interval_ms: Option<u64>,
+    pub send_rate: Option<u32>,
+    pub dual_stack: Option<bool>,
+    pub assignment_unit: Option<u64>,
+    pub output: Option<String>,
+    pub max_expired_leases: Option<u32>,
+    pub resume: Option<String>,
+    pub checkpoint_path: Option<String>,
+    pub checkpoint_every: Option<u32>,
+    pub checkpoint_segment_size: Option<u64>,
+    pub bloom_output: Option<String>,
+    pub bloom_false_positive_rate: Option<f64>,
+    pub lease_timeout_seconds: Option<u64>,
+    pub sum_only: Option<bool>,
+    pub overlapping_ranges: Option<String>,
+    pub format: Option<String>,
+    pub shard: Option<(u32, u32)>,
+    pub output_format: Option<String>,
+    pub bind_addr: Option<String>,
+    pub idle_timeout_seconds: Option<u64>,
+    pub max_requests_per_second: Option<u32>,
+    pub seed_primes: Option<Vec<u64>>,
+    pub verify: Option<bool>,
+    pub checksum: Option<bool>,
+    pub step: Option<u32>,
+}
+
+impl ServerOptions {
+    /// Builds the options from the keyword arguments of a server function.
+    ///
+    /// Each argument is assigned to the field of the same name, so values are
+    /// converted exactly as typed arguments would be.
+    ///
+    /// # Arguments
+    ///
+    /// * `py` - The Python GIL token.
+    /// * `kwargs` - The keyword arguments, if any were given.
+    ///
+    /// # Returns
+    ///
+    /// The `ServerOptions`, with the options not given left to `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PyTypeError` if an argument is not an option of the server, or if
+    /// its value has the wrong type.
+    pub fn from_kwargs(
+        py: Python<'_>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<ServerOptions> {
+        let options = Bound::new(py, ServerOptions::default())?;
+        for (name, value) in kwargs.into_iter().flatten() {
+            let name = name.downcast_into::<PyString>()?;
+            options.as_any().setattr(&name, value).map_err(|e| {
+                if e.is_instance_of::<PyAttributeError>(py) {
+                    PyTypeError::new_err(format!("Unexpected keyword argument '{}'", name))
+                } else {
+                    e
+                }
+            })?;
+        }
+        let options = std::mem::take(&mut *options.borrow_mut());
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that keyword arguments fill the options of the same name, and that unknown
+    /// names or mistyped values raise a `TypeError`.
+    #[test]
+    fn test_from_kwargs() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let kwargs = PyDict::new(py);
+            kwargs.set_item("assignment_unit", 250).unwrap();
+            kwargs.set_item("residue", (1, 4)).unwrap();
+            kwargs.set_item("output", py.None()).unwrap();
+            let options = ServerOptions::from_kwargs(py, Some(&kwargs)).unwrap();
+            assert_eq!(options.assignment_unit, Some(250));
+            assert_eq!(options.residue, Some((1, 4)));
+            assert_eq!(options.output, None);
+            assert_eq!(options.step, None);
+            assert!(ServerOptions::from_kwargs(py, None)
+                .unwrap()
+                .verify
+                .is_none());
+
+            let unknown = PyDict::new(py);
+            unknown.set_item("stpe", 250).unwrap();
+            let mistyped = PyDict::new(py);
+            mistyped.set_item("send_rate", "fast").unwrap();
+            for kwargs in [unknown, mistyped] {
+                let Err(error) = ServerOptions::from_kwargs(py, Some(&kwargs)) else {
+                    panic!("{} was accepted", kwargs);
+                };
+                assert!(error.is_instance_of::<PyTypeError>(py), "{}", error);
+            }
+        });
+    }
+}
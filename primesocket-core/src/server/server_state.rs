@@ -1,5 +1,132 @@
-use std::fs::File;
-use std::io::{self, Write};
+use super::assignment::{AssignmentOrder, AssignmentTracker};
+use super::checkpoint::Checkpoint;
+use super::client_stats::ClientStats;
+use super::intervals::IntervalSet;
+use crate::utils::bloom::BloomFilter;
+use crate::utils::primality::{is_prime, max_primes_in_range};
+use crate::utils::sieve::primes_up_to;
+use std::cmp::{max, min};
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, ErrorKind, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::Notify;
+
+/// Counts the epochs created by this process, so runs started together still differ.
+static EPOCHS: AtomicU64 = AtomicU64::new(0);
+
+/// Returns an id unique to a new run, from the clock, the process id and a counter.
+fn new_epoch() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64);
+    nanos ^ (u64::from(std::process::id()) << 32) ^ EPOCHS.fetch_add(1, Ordering::Relaxed)
+}
+
+/// The size of the ranges handed out to clients when none is configured.
+pub const DEFAULT_STEP: u64 = 1_000;
+
+/// The primes a state starts from when no others are given: every prime below 100.
+pub const DEFAULT_SEED_PRIMES: [u64; 25] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+];
+
+/// What to do with a `"save"` whose primes are not in strictly ascending order.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum UnsortedPolicy {
+    /// Sort the primes and drop duplicates before storing them.
+    #[default]
+    Sort,
+    /// Reject the whole submission.
+    Reject,
+}
+
+impl UnsortedPolicy {
+    /// Looks up a policy by name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - `"sort"` or `"reject"`.
+    ///
+    /// # Returns
+    ///
+    /// `Some(UnsortedPolicy)`, or `None` if the name is unknown.
+    pub fn from_name(name: &str) -> Option<UnsortedPolicy> {
+        match name {
+            "sort" => Some(UnsortedPolicy::Sort),
+            "reject" => Some(UnsortedPolicy::Reject),
+            _ => None,
+        }
+    }
+}
+
+/// What to do with a `"save"` whose range partially overlaps ranges saved before.
+///
+/// Consecutive ranges share a bound and a range saved twice is a harmless retry;
+/// any other overlap means ranges were handed out inconsistently.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OverlapPolicy {
+    /// Merge the primes; the union counts the overlap once.
+    #[default]
+    Merge,
+    /// Reject the whole submission.
+    Reject,
+}
+
+impl OverlapPolicy {
+    /// Looks up a policy by name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - `"merge"` or `"reject"`.
+    ///
+    /// # Returns
+    ///
+    /// `Some(OverlapPolicy)`, or `None` if the name is unknown.
+    pub fn from_name(name: &str) -> Option<OverlapPolicy> {
+        match name {
+            "merge" => Some(OverlapPolicy::Merge),
+            "reject" => Some(OverlapPolicy::Reject),
+            _ => None,
+        }
+    }
+}
+
+/// The layout of the output file holding the primes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutputFormat {
+    /// One prime per line.
+    #[default]
+    Text,
+    /// A single JSON array of numbers.
+    Json,
+    /// A `prime` header line followed by one prime per line.
+    Csv,
+}
+
+impl OutputFormat {
+    /// Looks up a format by name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - `"text"`, `"json"` or `"csv"`.
+    ///
+    /// # Returns
+    ///
+    /// `Some(OutputFormat)`, or `None` if the name is unknown.
+    pub fn from_name(name: &str) -> Option<OutputFormat> {
+        match name {
+            "text" => Some(OutputFormat::Text),
+            "json" => Some(OutputFormat::Json),
+            "csv" => Some(OutputFormat::Csv),
+            _ => None,
+        }
+    }
+}
 
 /// Represents the server state for prime number computations.
 ///
@@ -8,18 +135,107 @@ use std::io::{self, Write};
 ///
 /// # Fields
 ///
+/// * `start` - The lower limit of the number range to be processed.
 /// * `end` - The upper limit of the number range to be processed.
-/// * `step` - The step size used for processing the range.
+/// * `step` - The assignment unit: the size of the ranges handed out to clients. Clients
+///   may sieve and save a range in smaller buffers of their own.
 /// * `last_checked` - The last number that has been processed.
 /// * `primes` - A list of identified prime numbers.
-/// * `status` - The current status of the computation (e.g., "processing", "completed").
+/// * `status` - The current status of the computation (e.g., "processing", "completed", "shutdown").
+/// * `output_path` - The file where the identified primes are written.
+/// * `output_radix` - The base primes are written in: `10` (decimal) or `16` (hexadecimal).
+/// * `output_format` - The layout of the output file: text, JSON or CSV.
+/// * `grace_period` - How long late submissions are still merged after completion.
+/// * `completed_at` - The moment the computation was marked as completed, if it was.
+/// * `admin_secret` - The shared secret required by admin tasks; admin tasks are disabled when unset.
+/// * `assignments` - The ranges handed out to clients and not saved yet.
+/// * `assigned_up_to` - The highest number handed out to a client so far.
+/// * `completed` - The ranges confirmed as sieved by a `"save"`.
+/// * `client_stats` - The work saved by each client address.
+/// * `max_ranges_per_client` - How many ranges a single client may save; unlimited when unset.
+/// * `max_expired_leases` - How many leases a client may let expire before it is quarantined
+///   and gets no more work; unlimited when unset.
+/// * `residue` - The residue class `(a, m)` the computation is restricted to, if any.
+/// * `prime_count_tolerance` - How far above the theoretical maximum prime count of a range
+///   a `"save"` may go before being rejected, as a factor (`1.0` allows exactly the bound).
+/// * `pi_interval` - When set, `π(x)` is recorded at every multiple `x` of this interval.
+/// * `pi_checkpoints` - The recorded `(x, π(x))` pairs, in ascending order.
+/// * `pi_output_path` - The CSV file where the recorded `π(x)` values are written.
+/// * `assignment_order` - Whether ranges are handed out from the low or the high end.
+/// * `base_primes` - The primes up to `sqrt(end)`, computed once and sent to clients with
+///   every range.
+/// * `ranges` - The disjoint `(start, end)` ranges the computation is restricted to, in
+///   ascending order; the whole of `[start, end]` is computed when empty.
+/// * `unsorted_policy` - Whether submitted primes out of ascending order are sorted or rejected.
+/// * `checkpoint_path` - Where checkpoints are written periodically, if set.
+/// * `checkpoint_every` - How many accepted saves separate two periodic checkpoints.
+/// * `checkpoint_segment_size` - When set, checkpoints are written as a directory of
+///   segments covering this many values each, plus a manifest, instead of a single file.
+/// * `saves_since_checkpoint` - The accepted saves since the last periodic checkpoint.
+/// * `epoch` - A unique id of the run, sent along with ranges; saves carrying another
+///   epoch belong to a different run and are rejected.
+/// * `bloom` - A Bloom filter of the primes accepted so far, when enabled.
+/// * `bloom_output_path` - The file the Bloom filter is written to, when enabled.
+/// * `sum_only` - Whether clients save the sum and count of their primes instead of the list.
+/// * `prime_sum` - The sum of the primes saved so far, in sum-only mode.
+/// * `prime_count` - The number of primes saved so far, in sum-only mode.
+/// * `overlap_policy` - Whether saves partially overlapping saved ranges are merged or rejected.
+/// * `overlapping_saves` - How many saves partially overlapping saved ranges were received.
+/// * `segments_saved` - How many saves were accepted, including sub-blocks of a range.
+/// * `primes_saved` - How many primes the accepted saves carried, or counted in sum-only mode.
+/// * `bytes_received` - The size of all the requests received, in bytes.
+/// * `started_at` - The moment the state was created, the start of the throughput window.
+/// * `seed_primes` - Every prime up to some bound, known before the run: they start the
+///   list of primes and the base primes, which are only sieved above them.
+/// * `stop_signal` - Notified whenever the computation is marked completed or shut down,
+///   waking the server loop.
 #[derive(Clone, Debug)]
 pub struct ServerState {
-    pub end: u32,
-    pub step: u32,
-    pub last_checked: u32,
-    pub primes: Vec<u32>,
+    pub start: u64,
+    pub end: u64,
+    pub step: u64,
+    pub last_checked: u64,
+    pub primes: Vec<u64>,
     pub status: String,
+    pub output_path: PathBuf,
+    pub output_radix: u32,
+    pub output_format: OutputFormat,
+    pub grace_period: Duration,
+    pub completed_at: Option<Instant>,
+    pub admin_secret: Option<String>,
+    pub assignments: AssignmentTracker,
+    pub assigned_up_to: u64,
+    pub completed: IntervalSet,
+    pub client_stats: HashMap<SocketAddr, ClientStats>,
+    pub max_ranges_per_client: Option<u32>,
+    pub max_expired_leases: Option<u32>,
+    pub residue: Option<(u64, u64)>,
+    pub base_primes: Vec<u64>,
+    pub assignment_order: AssignmentOrder,
+    pub prime_count_tolerance: f64,
+    pub pi_interval: Option<u64>,
+    pub pi_checkpoints: Vec<(u64, u64)>,
+    pub pi_output_path: PathBuf,
+    pub ranges: Vec<(u64, u64)>,
+    pub unsorted_policy: UnsortedPolicy,
+    pub checkpoint_path: Option<PathBuf>,
+    pub checkpoint_every: u32,
+    pub checkpoint_segment_size: Option<u64>,
+    pub saves_since_checkpoint: u32,
+    pub epoch: u64,
+    pub bloom: Option<BloomFilter>,
+    pub bloom_output_path: Option<PathBuf>,
+    pub sum_only: bool,
+    pub prime_sum: u128,
+    pub prime_count: u64,
+    pub overlap_policy: OverlapPolicy,
+    pub overlapping_saves: u64,
+    pub segments_saved: u64,
+    pub primes_saved: u64,
+    pub bytes_received: u64,
+    pub started_at: Instant,
+    pub stop_signal: Arc<Notify>,
+    pub seed_primes: Vec<u64>,
 }
 
 impl ServerState {
@@ -32,47 +248,943 @@ impl ServerState {
     ///
     /// * `start` - The starting number of the range.
     /// * `end` - The upper limit of the number range.
+    /// * `step` - The size of the ranges handed out to clients.
     ///
     /// # Returns
     ///
     /// A new instance of `ServerState` initialized with the given parameters.
-    pub fn new(start: u32, end: u32) -> ServerState {
-        ServerState {
+    pub fn new(start: u64, end: u64, step: u64) -> ServerState {
+        ServerState::with_seed_primes(start, end, step, None)
+    }
+
+    /// Creates a new instance of `ServerState` starting from the given seed primes.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The starting number of the range.
+    /// * `end` - The upper limit of the number range.
+    /// * `step` - The size of the ranges handed out to clients.
+    /// * `seed_primes` - Every prime up to some bound, in ascending order, such as the
+    ///   primes below `sqrt(end)`; see `check_seed_primes`. Defaults to
+    ///   `DEFAULT_SEED_PRIMES`.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `ServerState` whose primes and base primes start with the seeds.
+    pub fn with_seed_primes(
+        start: u64,
+        end: u64,
+        step: u64,
+        seed_primes: Option<Vec<u64>>,
+    ) -> ServerState {
+        let seed_primes = seed_primes.unwrap_or_else(|| DEFAULT_SEED_PRIMES.to_vec());
+        let mut server_state = ServerState {
+            start,
             end,
-            step: 1000,
+            step,
             last_checked: start,
             primes: {
                 let mut primes = Vec::with_capacity(10000);
-                primes.extend(vec![
-                    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73,
-                    79, 83, 89, 97,
-                ]);
+                // Seeds above `end` would end up in the output of small runs.
+                primes.extend(seed_primes.iter().copied().filter(|&p| p <= end));
                 primes
             },
             status: String::from("processing"),
+            output_path: PathBuf::from("primes.txt"),
+            output_radix: 10,
+            output_format: OutputFormat::Text,
+            grace_period: Duration::ZERO,
+            completed_at: None,
+            admin_secret: None,
+            assignments: AssignmentTracker::default(),
+            assigned_up_to: start,
+            completed: IntervalSet::new(),
+            client_stats: HashMap::new(),
+            max_ranges_per_client: None,
+            max_expired_leases: None,
+            residue: None,
+            base_primes: Vec::new(),
+            assignment_order: AssignmentOrder::Ascending,
+            prime_count_tolerance: 1.0,
+            pi_interval: None,
+            pi_checkpoints: Vec::new(),
+            pi_output_path: PathBuf::from("pi.csv"),
+            ranges: Vec::new(),
+            unsorted_policy: UnsortedPolicy::Sort,
+            checkpoint_path: None,
+            checkpoint_every: 10,
+            checkpoint_segment_size: None,
+            saves_since_checkpoint: 0,
+            epoch: new_epoch(),
+            bloom: None,
+            bloom_output_path: None,
+            sum_only: false,
+            prime_sum: 0,
+            prime_count: 0,
+            overlap_policy: OverlapPolicy::Merge,
+            overlapping_saves: 0,
+            segments_saved: 0,
+            primes_saved: 0,
+            bytes_received: 0,
+            started_at: Instant::now(),
+            stop_signal: Arc::new(Notify::new()),
+            seed_primes,
+        };
+        server_state.compute_base_primes();
+        server_state
+    }
+
+    /// Creates a read-only `ServerState` from a saved output file.
+    ///
+    /// The state answers read queries over `[2, end]` and never hands out work.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The output file to load, one prime per line.
+    /// * `radix` - The base the primes were written in: `10` or `16`.
+    /// * `end` - The upper limit the file covers. Defaults to its largest prime.
+    ///
+    /// # Returns
+    ///
+    /// A `ServerState` with status `"read_only"` whose whole range is completed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` if the file can't be read or contains an invalid line.
+    pub fn read_replica(path: PathBuf, radix: u32, end: Option<u64>) -> io::Result<ServerState> {
+        let contents = fs::read_to_string(&path)?;
+        let primes = contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| u64::from_str_radix(line.trim(), radix))
+            .collect::<Result<BTreeSet<u64>, _>>()
+            .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+
+        let start = 2;
+        let end = end.unwrap_or_else(|| primes.last().copied().unwrap_or(start));
+        let mut server_state = ServerState::new(start, end, DEFAULT_STEP);
+        server_state.primes = primes.into_iter().collect();
+        server_state.last_checked = end;
+        server_state.assigned_up_to = end;
+        server_state.completed.insert(start, end);
+        server_state.status = "read_only".to_string();
+        server_state.output_path = path;
+        server_state.output_radix = radix;
+        Ok(server_state)
+    }
+
+    /// Returns the contiguous-verified frontier of the computation.
+    ///
+    /// Unlike `last_checked`, which is the highest end saved so far, the frontier
+    /// only advances when every range below it has been saved.
+    ///
+    /// # Returns
+    ///
+    /// The largest value such that `[start, value]` is entirely completed, or
+    /// `start` if the first range is not completed yet.
+    pub fn completed_frontier(&self) -> u64 {
+        self.completed.contiguous_end(self.start)
+    }
+
+    /// Restricts the computation to the numbers congruent to `a` modulo `m`.
+    ///
+    /// The primes found so far are filtered to the class; the base primes sent to
+    /// the clients are left untouched.
+    ///
+    /// # Arguments
+    ///
+    /// * `a` - The residue of the class.
+    /// * `m` - The modulus of the class, coprime with `a`.
+    pub fn restrict_to_residue(&mut self, a: u64, m: u64) {
+        self.residue = Some((a % m, m));
+        self.primes.retain(|p| p % m == a % m);
+    }
+
+    /// Restricts the computation to the union of several ranges.
+    ///
+    /// The bounds of the state become the smallest and largest numbers of the
+    /// union. The gaps between the ranges are marked as completed so they are
+    /// never handed out, and the base primes are computed up to `sqrt(end)`.
+    ///
+    /// # Arguments
+    ///
+    /// * `ranges` - The inclusive `(start, end)` ranges; they may overlap and come in any order.
+    pub fn restrict_to_ranges(&mut self, ranges: &[(u64, u64)]) {
+        let mut union = IntervalSet::new();
+        for &(start, end) in ranges {
+            union.insert(start, end);
+        }
+        self.ranges = union.iter().collect();
+        let (Some(&(start, _)), Some(&(_, end))) = (self.ranges.first(), self.ranges.last()) else {
+            return;
+        };
+
+        self.start = start;
+        self.end = end;
+        self.last_checked = start;
+        self.assigned_up_to = start;
+        for window in self.ranges.windows(2) {
+            self.completed.insert(window[0].1 + 1, window[1].0 - 1);
+        }
+        self.primes.retain(|&p| union.covers(p, p));
+        self.compute_base_primes();
+    }
+
+    /// Restricts the computation to one shard of `[start, end]`.
+    ///
+    /// The bounds are cut into `count` contiguous slices whose sizes differ by at most
+    /// one, so the shards of all indices cover the bounds with no gaps or overlaps.
+    /// When the computation is already restricted to `ranges`, they are clipped to the
+    /// slice.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The shard to keep, below `count`.
+    /// * `count` - The number of shards.
+    ///
+    /// # Returns
+    ///
+    /// `false`, leaving the state untouched, if the shard holds no numbers.
+    pub fn restrict_to_shard(&mut self, index: u32, count: u32) -> bool {
+        let total = (self.end - self.start) as u128 + 1;
+        let bound = |i: u32| self.start as u128 + total * i as u128 / count as u128;
+        let (low, next) = (bound(index), bound(index + 1));
+        if low == next {
+            return false;
+        }
+        let (low, high) = (low as u64, (next - 1) as u64);
+
+        let ranges = match self.ranges.is_empty() {
+            true => vec![(self.start, self.end)],
+            false => self.ranges.clone(),
+        };
+        let clipped: Vec<(u64, u64)> = ranges
+            .into_iter()
+            .map(|(start, end)| (start.max(low), end.min(high)))
+            .filter(|&(start, end)| start <= end)
+            .collect();
+        if clipped.is_empty() {
+            return false;
+        }
+        self.restrict_to_ranges(&clipped);
+        true
+    }
+
+    /// Sets the order ranges are handed out in.
+    ///
+    /// # Arguments
+    ///
+    /// * `order` - The new assignment order.
+    pub fn set_assignment_order(&mut self, order: AssignmentOrder) {
+        self.assignment_order = order;
+    }
+
+    /// Raises the end of the computation while it runs.
+    ///
+    /// Ranges beyond the old end are handed out as usual afterwards; the base
+    /// primes are extended to cover the new end.
+    ///
+    /// # Arguments
+    ///
+    /// * `end` - The new upper limit, larger than the current one.
+    pub fn extend_end(&mut self, end: u64) {
+        self.end = max(self.end, end);
+        if let Some(last) = self.ranges.last_mut() {
+            last.1 = self.end;
+        }
+        self.compute_base_primes();
+    }
+
+    /// Computes the primes up to `sqrt(end)` into `base_primes`, taking those covered
+    /// by the seed primes from them.
+    ///
+    /// The seeds alone rarely suffice (the default ones stop at 97, short of any `end`
+    /// above 9409), so the primes above them are sieved up to the exact integer square
+    /// root, plus one; a float square root may round down for large `end`.
+    fn compute_base_primes(&mut self) {
+        let bound = self.end.isqrt() + 1;
+        let seeded = self.seed_primes.partition_point(|&p| p <= bound);
+        let covered = self.seed_primes[..seeded].last().copied().unwrap_or(1);
+        self.base_primes = self.seed_primes[..seeded].to_vec();
+        if covered < bound {
+            self.base_primes
+                .extend(primes_up_to(bound).into_iter().filter(|&p| p > covered));
+        }
+    }
+
+    /// Checks every prime found with a primality test.
+    ///
+    /// A client with a faulty sieve, or a corrupted datagram, may have submitted
+    /// composites; this finds them before the results are trusted. Missing primes
+    /// are not detected.
+    ///
+    /// # Errors
+    ///
+    /// Returns the values of `primes` that are not prime, in order.
+    pub fn verify(&self) -> Result<(), Vec<u64>> {
+        let composites: Vec<u64> = self
+            .primes
+            .iter()
+            .copied()
+            .filter(|&n| !is_prime(n))
+            .collect();
+        if composites.is_empty() {
+            Ok(())
+        } else {
+            Err(composites)
+        }
+    }
+
+    /// Checks that seed primes are every prime up to the largest of them.
+    ///
+    /// Base primes missing a prime would let its multiples through as primes, so
+    /// the seeds must hold them all, in strictly ascending order.
+    ///
+    /// # Arguments
+    ///
+    /// * `seed_primes` - The seed primes to check.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message naming the first offending value if the seeds are empty, out of
+    /// order, not prime, or skip a prime.
+    pub fn check_seed_primes(seed_primes: &[u64]) -> Result<(), String> {
+        let Some(&largest) = seed_primes.last() else {
+            return Err("no seed primes given".to_string());
+        };
+        if let Some(pair) = seed_primes.windows(2).find(|pair| pair[0] >= pair[1]) {
+            return Err(format!(
+                "seed primes not strictly ascending at {} then {}",
+                pair[0], pair[1]
+            ));
+        }
+        if let Some(&composite) = seed_primes.iter().find(|&&n| !is_prime(n)) {
+            return Err(format!("seed {} is not prime", composite));
+        }
+        let expected = primes_up_to(largest);
+        if let Some(missing) = expected
+            .iter()
+            .find(|p| seed_primes.binary_search(p).is_err())
+        {
+            return Err(format!("seed primes skip the prime {}", missing));
+        }
+        Ok(())
+    }
+
+    /// Returns the base primes sent to clients along with a range.
+    ///
+    /// Every range gets the same list, so a client caching it never sees it change
+    /// during the run; only raising `end` extends it.
+    ///
+    /// # Returns
+    ///
+    /// The primes up to `sqrt(end)`, computed when the state was created.
+    pub fn sieving_primes(&self) -> Vec<u64> {
+        self.base_primes.clone()
+    }
+
+    /// Checks whether a client has saved as many ranges as it is allowed to.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The address of the client.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a per-client cap is set and `client` reached it.
+    pub fn client_limit_reached(&self, client: &SocketAddr) -> bool {
+        self.max_ranges_per_client.is_some_and(|cap| {
+            self.client_stats
+                .get(client)
+                .is_some_and(|stats| stats.ranges >= cap)
+        })
+    }
+
+    /// Counts the distinct client addresses that saved at least one range.
+    pub fn contributing_clients(&self) -> usize {
+        self.client_stats
+            .values()
+            .filter(|stats| stats.ranges > 0)
+            .count()
+    }
+
+    /// Returns the primes saved per second since the state was created.
+    ///
+    /// Primes in saves repeating a range are counted again, so this measures the
+    /// work received rather than the distinct primes found.
+    pub fn primes_per_second(&self) -> f64 {
+        let elapsed = self.started_at.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            self.primes_saved as f64 / elapsed
+        } else {
+            0.0
+        }
+    }
+
+    /// Drops the assignments whose lease expired, counting them against their client.
+    ///
+    /// Their ranges, unless completed meanwhile, return to the reclaimed pool so the
+    /// next `"start"` hands them out again.
+    ///
+    /// # Returns
+    ///
+    /// The number of ranges returned to the pool.
+    pub fn reap_expired_leases(&mut self) -> usize {
+        let mut reclaimed = 0;
+        for assignment in self.assignments.take_expired(Instant::now()) {
+            self.client_stats
+                .entry(assignment.assigned_to)
+                .or_default()
+                .expired_leases += 1;
+            if !self.completed.covers(assignment.start, assignment.end) {
+                self.assignments
+                    .reclaimed
+                    .insert(assignment.start, assignment.end);
+                reclaimed += 1;
+            }
+        }
+        reclaimed
+    }
+
+    /// Checks whether a client let too many leases expire to get more work.
+    ///
+    /// Quarantined clients may still save the ranges they hold.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The address of the client.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a cap is set and `client` let that many leases expire.
+    pub fn client_quarantined(&self, client: &SocketAddr) -> bool {
+        self.max_expired_leases.is_some_and(|cap| {
+            self.client_stats
+                .get(client)
+                .is_some_and(|stats| stats.expired_leases >= cap)
+        })
+    }
+
+    /// Returns the next range to hand out, following `assignment_order`.
+    ///
+    /// Ascending ranges start at `last_checked`; descending ranges end where the
+    /// completed coverage below `end` starts. With `ranges` set, the range handed
+    /// out always lies within the first (or last, descending) unfinished one.
+    ///
+    /// Ranges reclaimed from expired leases come first. Ranges still in flight are
+    /// skipped, so concurrent clients never get the same work. When everything left
+    /// is in flight, the first (or last, descending) of those ranges is handed out again.
+    ///
+    /// # Returns
+    ///
+    /// The `(start, end)` bounds of the next range.
+    pub fn next_range(&self) -> (u64, u64) {
+        let reclaimed = match self.assignment_order {
+            AssignmentOrder::Ascending => self.assignments.reclaimed.iter().next(),
+            AssignmentOrder::Descending => self.assignments.reclaimed.iter().next_back(),
+        };
+        if let Some((&start, &end)) = reclaimed {
+            return (start, end);
+        }
+        match self.assignment_order {
+            AssignmentOrder::Ascending => {
+                let (low, high) = self
+                    .ranges
+                    .iter()
+                    .find(|&&(_, high)| !self.completed.covers(high, high))
+                    .map_or((self.start, self.end), |&range| range);
+                let first = max(self.last_checked, low);
+                let mut start = first;
+                while let Some(assignment) = self.assignments.in_flight_at(start) {
+                    start = assignment.end;
+                }
+                if start >= high {
+                    if let Some(oldest) = self.assignments.assignments.values().next() {
+                        return (oldest.start, oldest.end);
+                    }
+                    start = first;
+                }
+                let next_in_flight = self
+                    .assignments
+                    .assignments
+                    .range(start + 1..)
+                    .next()
+                    .map_or(high, |(&next, _)| next);
+                (
+                    start,
+                    min(start.saturating_add(self.step), min(high, next_in_flight)),
+                )
+            }
+            AssignmentOrder::Descending => {
+                let (low, high) = self
+                    .ranges
+                    .iter()
+                    .rev()
+                    .find(|&&(low, high)| !self.completed.covers(low, high))
+                    .map_or((self.start, self.end), |&range| range);
+                let last = self.completed.contiguous_start(high);
+                let mut end = last;
+                while let Some(assignment) = self.assignments.in_flight_at(end.saturating_sub(1)) {
+                    end = assignment.start;
+                }
+                if end <= low {
+                    if let Some(oldest) = self.assignments.assignments.values().next_back() {
+                        return (oldest.start, oldest.end);
+                    }
+                    end = last;
+                }
+                let previous_in_flight = self
+                    .assignments
+                    .assignments
+                    .range(..end)
+                    .next_back()
+                    .map(|(_, assignment)| assignment.end)
+                    .filter(|&previous| previous <= end)
+                    .unwrap_or(low);
+                (
+                    max(end.saturating_sub(self.step), max(low, previous_in_flight)),
+                    end,
+                )
+            }
+        }
+    }
+
+    /// Checks whether every range of the computation was saved.
+    ///
+    /// # Returns
+    ///
+    /// `true` once `last_checked` reached `end` with no range left in flight or to redo
+    /// (ascending), or once the completed coverage below `end` reaches `start`
+    /// (descending). With `ranges` set, once every one of them is completed.
+    pub fn is_finished(&self) -> bool {
+        if !self.ranges.is_empty() {
+            return self
+                .ranges
+                .iter()
+                .all(|&(low, high)| self.completed.covers(low, high));
+        }
+        match self.assignment_order {
+            AssignmentOrder::Ascending => {
+                self.last_checked >= self.end
+                    && self.assignments.assignments.is_empty()
+                    && self.assignments.reclaimed.is_empty()
+            }
+            AssignmentOrder::Descending => {
+                self.completed.contiguous_start(self.end) <= self.start
+                    && self.completed.contiguous_end(self.start) >= self.end
+            }
+        }
+    }
+
+    /// Checks whether a submission holds no more primes than its range can contain.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The start of the submitted range.
+    /// * `end` - The end of the submitted range.
+    /// * `count` - The number of primes submitted.
+    ///
+    /// # Returns
+    ///
+    /// `true` if `count` is within `prime_count_tolerance` times the theoretical maximum.
+    pub fn plausible_prime_count(&self, start: u64, end: u64, count: usize) -> bool {
+        let bound = max_primes_in_range(start, end) as f64;
+        count as f64 <= bound * self.prime_count_tolerance
+    }
+
+    /// Merges a batch of submitted primes into the state.
+    ///
+    /// The resulting list is kept sorted and free of duplicates.
+    ///
+    /// # Arguments
+    ///
+    /// * `primes` - The prime numbers reported by a client.
+    pub fn merge_primes(&mut self, primes: Vec<u64>) {
+        if let Some(bloom) = &mut self.bloom {
+            primes.iter().for_each(|&prime| bloom.insert(prime));
+        }
+        self.primes.extend(primes);
+        self.primes = self
+            .primes
+            .iter()
+            .cloned()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+    }
+
+    /// Sorts the primes and drops duplicates before they are written out.
+    ///
+    /// `merge_primes` keeps the list ordered already; this also covers primes added
+    /// by any other path, such as the seed primes or a restored checkpoint.
+    pub fn finalize_primes(&mut self) {
+        self.primes.sort_unstable();
+        self.primes.dedup();
+    }
+
+    /// Checks whether a saved range partially overlaps the ranges saved before.
+    ///
+    /// Sharing a bound with a saved range, or repeating one, is not an overlap.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The start of the saved range.
+    /// * `end` - The end of the saved range.
+    ///
+    /// # Returns
+    ///
+    /// `true` if some, but not all, of the numbers strictly inside the range were saved.
+    pub fn overlaps_saved(&self, start: u64, end: u64) -> bool {
+        end.saturating_sub(start) >= 2
+            && !self.completed.covers(start, end)
+            && self.completed.intersects(start + 1, end - 1)
+    }
+
+    /// Adds the sum and count of the primes of a saved range to the running totals.
+    ///
+    /// Consecutive ranges share their bounds, so a bound already covered by a saved
+    /// range is taken out of the totals if it is prime; a range saved twice is ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The start of the saved range.
+    /// * `end` - The end of the saved range.
+    /// * `sum` - The sum of the primes in `[start, end]`.
+    /// * `count` - The number of primes in `[start, end]`.
+    ///
+    /// # Returns
+    ///
+    /// `false`, leaving the totals unchanged, if the sum would overflow.
+    pub fn record_sum(&mut self, start: u64, end: u64, sum: u128, count: u64) -> bool {
+        if self.completed.covers(start, end) {
+            return true;
+        }
+        let (mut sum, mut count) = (sum, count);
+        for bound in BTreeSet::from([start, end]) {
+            let in_class = self.residue.is_none_or(|(a, m)| bound % m == a);
+            if self.completed.covers(bound, bound) && in_class && is_prime(bound) {
+                sum = sum.saturating_sub(u128::from(bound));
+                count = count.saturating_sub(1);
+            }
+        }
+        let Some(total) = self.prime_sum.checked_add(sum) else {
+            return false;
+        };
+        self.prime_sum = total;
+        self.prime_count += count;
+        true
+    }
+
+    /// Starts building a Bloom filter of the primes, written to `path` with the results.
+    ///
+    /// The filter is sized for the most primes `[start, end]` can hold and seeded with
+    /// the primes found so far; every prime accepted afterwards is added to it.
+    /// Raising `end` later makes the filter fuller than planned, so its
+    /// false-positive rate grows.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file the filter is written to.
+    /// * `false_positive_rate` - The wanted false-positive rate, in `(0, 1)`.
+    pub fn enable_bloom(&mut self, path: PathBuf, false_positive_rate: f64) {
+        let mut bloom = BloomFilter::with_rate(
+            max_primes_in_range(self.start, self.end),
+            false_positive_rate,
+        );
+        self.primes.iter().for_each(|&prime| bloom.insert(prime));
+        self.bloom = Some(bloom);
+        self.bloom_output_path = Some(path);
+    }
+
+    /// Writes the Bloom filter to `bloom_output_path`, if enabled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` if the file could not be written.
+    pub fn save_bloom(&self) -> io::Result<()> {
+        match (&self.bloom, &self.bloom_output_path) {
+            (Some(bloom), Some(path)) => bloom.save(path),
+            _ => Ok(()),
+        }
+    }
+
+    /// Marks the computation as completed and records when it happened.
+    pub fn mark_completed(&mut self) {
+        self.status = "completed".to_string();
+        self.completed_at = Some(Instant::now());
+        self.stop_signal.notify_one();
+    }
+
+    /// Marks the server as shut down before the computation completed.
+    pub fn mark_shutdown(&mut self) {
+        self.status = "shutdown".to_string();
+        self.stop_signal.notify_one();
+    }
+
+    /// Checks whether the server stopped handing out work.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the computation is completed or the server was shut down.
+    pub fn is_stopped(&self) -> bool {
+        self.status == "completed" || self.status == "shutdown"
+    }
+
+    /// Checks whether late submissions are still accepted after completion.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the computation is completed and the grace period has not elapsed yet.
+    pub fn in_grace_period(&self) -> bool {
+        self.completed_at
+            .is_some_and(|completed_at| completed_at.elapsed() < self.grace_period)
+    }
+
+    /// Returns the moment the grace period ends, if the computation is completed.
+    pub fn grace_period_end(&self) -> Option<Instant> {
+        self.completed_at
+            .map(|completed_at| completed_at + self.grace_period)
+    }
+
+    /// Checks whether a request is allowed to run admin tasks.
+    ///
+    /// # Arguments
+    ///
+    /// * `secret` - The secret carried by the request, if any.
+    ///
+    /// # Returns
+    ///
+    /// `true` if an admin secret is configured and `secret` matches it.
+    pub fn is_authorized(&self, secret: Option<&str>) -> bool {
+        self.admin_secret.is_some() && self.admin_secret.as_deref() == secret
+    }
+
+    /// Writes a checkpoint of the computation to `path`.
+    ///
+    /// The checkpoint holds the progress, the outstanding assignments and the
+    /// per-client counters, so a restarted server keeps its fairness guarantees.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to write the checkpoint to.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` if the file could not be written.
+    pub fn save_checkpoint(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_vec(&Checkpoint::from_state(self))?;
+        // Write aside first, so a crash mid-write never corrupts the last checkpoint.
+        let staging = path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&staging, json)?;
+        fs::rename(staging, path)
+    }
+
+    /// Writes a checkpoint as a directory of segment files plus a manifest.
+    ///
+    /// See `Checkpoint::write_chunked`.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The checkpoint directory.
+    /// * `segment_size` - The range of values each segment covers.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` if a file could not be written.
+    pub fn save_chunked_checkpoint(&self, dir: &Path, segment_size: u64) -> io::Result<()> {
+        Checkpoint::from_state(self).write_chunked(dir, segment_size)
+    }
+
+    /// Restores a `ServerState` from a checkpoint written by `save_chunked_checkpoint`,
+    /// loading its segments in parallel.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The checkpoint directory.
+    ///
+    /// # Returns
+    ///
+    /// The restored `ServerState`, with default settings.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` if a file can't be read or fails its checksum.
+    pub fn load_chunked_checkpoint(dir: &Path) -> io::Result<ServerState> {
+        Ok(Checkpoint::read_chunked(dir)?.into_state())
+    }
+
+    /// Writes a checkpoint to `path`, chunked when `checkpoint_segment_size` is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The checkpoint file, or directory when chunked.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` if the checkpoint could not be written.
+    pub fn write_checkpoint(&self, path: &Path) -> io::Result<()> {
+        match self.checkpoint_segment_size {
+            Some(segment_size) => self.save_chunked_checkpoint(path, segment_size),
+            None => self.save_checkpoint(path),
+        }
+    }
+
+    /// Writes a checkpoint to `checkpoint_path` once `checkpoint_every` saves were accepted
+    /// since the last one.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a checkpoint was written.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` if the checkpoint could not be written; it is attempted
+    /// again after the next save.
+    pub fn checkpoint_if_due(&mut self) -> io::Result<bool> {
+        let Some(path) = &self.checkpoint_path else {
+            return Ok(false);
+        };
+        if self.saves_since_checkpoint < self.checkpoint_every {
+            return Ok(false);
         }
+        self.write_checkpoint(path)?;
+        self.saves_since_checkpoint = 0;
+        Ok(true)
+    }
+
+    /// Restores a `ServerState` from a checkpoint written by `save_checkpoint`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The checkpoint file.
+    ///
+    /// # Returns
+    ///
+    /// The restored `ServerState`, with default settings.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` if the file can't be read or is not a valid checkpoint.
+    pub fn load_checkpoint(path: &Path) -> io::Result<ServerState> {
+        let checkpoint: Checkpoint = serde_json::from_slice(&fs::read(path)?)?;
+        Ok(checkpoint.into_state())
     }
 
-    /// Saves the list of identified prime numbers to a file.
+    /// Records `π(x)` for every multiple of `pi_interval` the computation went past.
     ///
-    /// This function writes the contents of `primes` into a file named `primes.txt`.
-    /// Each prime number is written on a separate line.
+    /// The completed frontier is used rather than `last_checked`, since every prime
+    /// up to `x` must have been saved for the count to be exact.
+    pub fn record_pi_checkpoints(&mut self) {
+        let Some(interval) = self.pi_interval.filter(|&interval| interval > 0) else {
+            return;
+        };
+        let frontier = min(self.completed_frontier(), self.end);
+        let mut x = self
+            .pi_checkpoints
+            .last()
+            .map_or(interval, |&(x, _)| x.saturating_add(interval));
+        while x <= frontier {
+            let pi_x = self.primes.partition_point(|&p| p <= x) as u64;
+            self.pi_checkpoints.push((x, pi_x));
+            match x.checked_add(interval) {
+                Some(next) => x = next,
+                None => break,
+            }
+        }
+    }
+
+    /// Writes the recorded `π(x)` values to `pi_output_path` as `x,pi_x` CSV rows.
     ///
     /// # Errors
     ///
-    /// Returns an `io::Result<()>` indicating whether the file was successfully created and written.
-    pub fn save_primes_to_file(&self) -> io::Result<()> {
-        let mut file = File::create("primes.txt")?;
-        for prime in &self.primes {
-            writeln!(file, "{}", prime)?;
+    /// Returns an `io::Error` if the file could not be created or written.
+    pub fn save_pi_checkpoints(&self) -> io::Result<()> {
+        let mut file = File::create(&self.pi_output_path)?;
+        writeln!(file, "x,pi_x")?;
+        for (x, pi_x) in &self.pi_checkpoints {
+            writeln!(file, "{},{}", x, pi_x)?;
         }
         Ok(())
     }
+
+    /// Saves the list of identified prime numbers to the output file.
+    ///
+    /// Writes to `output_path` (`primes.txt` by default) in `output_format`; see
+    /// `save_primes`.
+    ///
+    /// # Returns
+    ///
+    /// The number of bytes written to the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` if the file could not be created or written.
+    pub fn save_primes_to_file(&self) -> io::Result<u64> {
+        self.save_primes(&self.output_path, self.output_format)
+    }
+
+    /// Saves the list of identified prime numbers to a file in the given format.
+    ///
+    /// As text, each prime is written on a separate line, in decimal or, when
+    /// `output_radix` is `16`, in lowercase hexadecimal without prefix. CSV adds a
+    /// `prime` header line and follows the radix too. JSON writes a single array of
+    /// decimal numbers, as JSON has no hexadecimal literals. In sum-only mode, the file
+    /// holds the sum of the primes instead: a single number, under a `sum` header in CSV.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to write.
+    /// * `format` - The layout of the file.
+    ///
+    /// # Returns
+    ///
+    /// The number of bytes written to the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` if the file could not be created or written.
+    pub fn save_primes(&self, path: &Path, format: OutputFormat) -> io::Result<u64> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let mut bytes = 0;
+        let mut write = |text: String| -> io::Result<()> {
+            file.write_all(text.as_bytes())?;
+            bytes += text.len() as u64;
+            Ok(())
+        };
+        if self.sum_only {
+            if format == OutputFormat::Csv {
+                write("sum\n".to_string())?;
+            }
+            write(format!("{}\n", self.prime_sum))?;
+        } else if format == OutputFormat::Json {
+            write("[".to_string())?;
+            for (i, prime) in self.primes.iter().enumerate() {
+                write(match i {
+                    0 => prime.to_string(),
+                    _ => format!(",{}", prime),
+                })?;
+            }
+            write("]\n".to_string())?;
+        } else {
+            if format == OutputFormat::Csv {
+                write("prime\n".to_string())?;
+            }
+            for prime in &self.primes {
+                write(match self.output_radix {
+                    16 => format!("{:x}\n", prime),
+                    _ => format!("{}\n", prime),
+                })?;
+            }
+        }
+        file.flush()?;
+        Ok(bytes)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs;
 
     /// Tests the creation of a `ServerState` instance.
     ///
@@ -86,12 +1198,298 @@ mod tests {
         let start = 0;
         let end = 100;
 
-        let server_state = ServerState::new(start, end);
+        let step = 5;
+
+        let server_state = ServerState::new(start, end, step);
 
         assert_eq!(server_state.last_checked, 0);
         assert_eq!(server_state.end, 100);
-        assert_eq!(server_state.step, 1000);
+        assert_eq!(server_state.step, 5);
         assert!(!server_state.primes.is_empty());
         assert_eq!(server_state.status, "processing");
+        assert_eq!(server_state.completed_frontier(), 0);
+    }
+
+    /// Tests that the grace period only applies once the computation is completed.
+    #[test]
+    fn test_server_state_grace_period() {
+        let mut server_state = ServerState::new(2, 100, DEFAULT_STEP);
+        server_state.grace_period = Duration::from_secs(60);
+        assert!(!server_state.in_grace_period());
+
+        server_state.mark_completed();
+        assert_eq!(server_state.status, "completed");
+        assert!(server_state.in_grace_period());
+
+        server_state.grace_period = Duration::ZERO;
+        assert!(!server_state.in_grace_period());
+    }
+
+    /// Tests that admin tasks require a configured and matching secret.
+    #[test]
+    fn test_server_state_is_authorized() {
+        let mut server_state = ServerState::new(2, 100, DEFAULT_STEP);
+        assert!(!server_state.is_authorized(None));
+
+        server_state.admin_secret = Some("hunter2".to_string());
+        assert!(server_state.is_authorized(Some("hunter2")));
+        assert!(!server_state.is_authorized(Some("wrong")));
+        assert!(!server_state.is_authorized(None));
+    }
+
+    /// Tests that verification passes on sieved primes and flags injected composites.
+    #[test]
+    fn test_verify_flags_composites() {
+        let mut server_state = ServerState::new(2, 10_000, DEFAULT_STEP);
+        server_state.primes = primes_up_to(10_000);
+        assert_eq!(server_state.verify(), Ok(()));
+
+        server_state.merge_primes(vec![91, 7_919, 9_999]);
+        server_state.primes.push(1);
+        assert_eq!(server_state.verify(), Err(vec![91, 9_999, 1]));
+    }
+
+    /// Tests that seed primes are accepted only when they are every prime up to the largest.
+    #[test]
+    fn test_check_seed_primes() {
+        assert!(ServerState::check_seed_primes(&primes_up_to(1_000)).is_ok());
+        assert!(ServerState::check_seed_primes(&DEFAULT_SEED_PRIMES).is_ok());
+        assert!(ServerState::check_seed_primes(&[]).is_err());
+        let unsorted = ServerState::check_seed_primes(&[2, 5, 3]).unwrap_err();
+        assert!(unsorted.contains("ascending"), "{}", unsorted);
+        let composite = ServerState::check_seed_primes(&[2, 3, 4]).unwrap_err();
+        assert!(composite.contains("4 is not prime"), "{}", composite);
+        let gap = ServerState::check_seed_primes(&[2, 3, 7]).unwrap_err();
+        assert!(gap.contains("skip the prime 5"), "{}", gap);
+    }
+
+    /// Tests the exact bytes written by each output format, for primes and in sum-only mode.
+    #[test]
+    fn test_save_primes_formats() {
+        let mut server_state = ServerState::new(2, 10, DEFAULT_STEP);
+        let path =
+            std::env::temp_dir().join(format!("primesocket_formats_{}.out", std::process::id()));
+        let saved = |server_state: &ServerState, format| {
+            let bytes = server_state.save_primes(&path, format).unwrap();
+            let contents = fs::read_to_string(&path).unwrap();
+            assert_eq!(bytes, contents.len() as u64);
+            contents
+        };
+
+        assert_eq!(saved(&server_state, OutputFormat::Text), "2\n3\n5\n7\n");
+        assert_eq!(saved(&server_state, OutputFormat::Json), "[2,3,5,7]\n");
+        assert_eq!(
+            saved(&server_state, OutputFormat::Csv),
+            "prime\n2\n3\n5\n7\n"
+        );
+
+        server_state.primes.clear();
+        assert_eq!(saved(&server_state, OutputFormat::Json), "[]\n");
+
+        server_state.sum_only = true;
+        server_state.prime_sum = 17;
+        assert_eq!(saved(&server_state, OutputFormat::Text), "17\n");
+        assert_eq!(saved(&server_state, OutputFormat::Json), "17\n");
+        assert_eq!(saved(&server_state, OutputFormat::Csv), "sum\n17\n");
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(OutputFormat::from_name("csv"), Some(OutputFormat::Csv));
+        assert_eq!(OutputFormat::from_name("xml"), None);
+    }
+
+    /// Tests that primes written in hexadecimal parse back to the same values.
+    #[test]
+    fn test_save_primes_to_file_hex() {
+        let mut server_state = ServerState::new(2, 100, DEFAULT_STEP);
+        server_state.output_radix = 16;
+        server_state.output_path =
+            std::env::temp_dir().join(format!("primesocket_hex_{}.txt", std::process::id()));
+
+        server_state.save_primes_to_file().unwrap();
+        let contents = fs::read_to_string(&server_state.output_path).unwrap();
+        fs::remove_file(&server_state.output_path).unwrap();
+
+        let parsed: Vec<u64> = contents
+            .lines()
+            .map(|line| u64::from_str_radix(line, 16).unwrap())
+            .collect();
+        assert_eq!(parsed, server_state.primes);
+        assert_eq!(contents.lines().last(), Some("61"));
+    }
+
+    /// Tests that a state restricted to disjoint ranges only hands out numbers inside them.
+    #[test]
+    fn test_restrict_to_ranges_skips_gaps() {
+        let mut server_state = ServerState::new(2, 2, 10);
+        server_state.restrict_to_ranges(&[(100, 115), (10, 20)]);
+
+        assert_eq!(server_state.ranges, vec![(10, 20), (100, 115)]);
+        assert_eq!((server_state.start, server_state.end), (10, 115));
+        assert!(server_state.primes.iter().all(|p| (10..=20).contains(p)));
+
+        let mut handed_out = Vec::new();
+        while !server_state.is_finished() {
+            let (start, end) = server_state.next_range();
+            handed_out.push((start, end));
+            server_state.completed.insert(start, end);
+            server_state.last_checked = end;
+        }
+        assert_eq!(handed_out, vec![(10, 20), (100, 110), (110, 115)]);
+    }
+
+    /// Tests that a range left unsaved past its lease returns to the pool, is handed out
+    /// again before new work, and keeps the computation from finishing until saved.
+    #[test]
+    fn test_expired_range_is_reassigned() {
+        let crashed: SocketAddr = "127.0.0.1:4000".parse().unwrap();
+        let healthy: SocketAddr = "127.0.0.1:4001".parse().unwrap();
+        let mut server_state = ServerState::new(2, 2_002, DEFAULT_STEP);
+        server_state.assignments.lease_duration = Duration::from_millis(20);
+
+        for client in [crashed, healthy] {
+            let (start, end) = server_state.next_range();
+            server_state.assignments.assign(start, end, client);
+        }
+        server_state.assignments.complete(2_002);
+        server_state.completed.insert(1_002, 2_002);
+        server_state.last_checked = 2_002;
+        assert_eq!(server_state.reap_expired_leases(), 0);
+        assert!(!server_state.is_finished());
+        assert_eq!(server_state.next_range(), (2, 1_002));
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(server_state.reap_expired_leases(), 1);
+        assert!(!server_state.is_finished());
+        assert_eq!(server_state.next_range(), (2, 1_002));
+
+        let restored = Checkpoint::from_state(&server_state).into_state();
+        assert_eq!(restored.next_range(), (2, 1_002));
+
+        server_state.assignments.assign(2, 1_002, healthy);
+        server_state.assignments.complete(1_002);
+        assert!(server_state.assignments.reclaimed.is_empty());
+        assert!(server_state.is_finished());
+    }
+
+    /// Tests that periodic checkpoints are written every `checkpoint_every` saves and reload.
+    #[test]
+    fn test_periodic_checkpoint_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("primesocket_periodic_{}.json", std::process::id()));
+        let mut server_state = ServerState::new(2, 10_000, 500);
+        server_state.checkpoint_path = Some(path.clone());
+        server_state.checkpoint_every = 2;
+
+        server_state.merge_primes(primes_up_to(1_000));
+        server_state.last_checked = 1_000;
+        server_state.saves_since_checkpoint = 1;
+        assert!(!server_state.checkpoint_if_due().unwrap());
+        assert!(!path.exists());
+
+        server_state.saves_since_checkpoint = 2;
+        assert!(server_state.checkpoint_if_due().unwrap());
+        assert_eq!(server_state.saves_since_checkpoint, 0);
+
+        let restored = ServerState::load_checkpoint(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(restored.last_checked, 1_000);
+        assert_eq!(restored.end, 10_000);
+        assert_eq!(restored.step, 500);
+        assert_eq!(restored.primes, server_state.primes);
+        assert_eq!(restored.epoch, server_state.epoch);
+    }
+
+    /// Tests that a chunked checkpoint reloads in parallel into the same state, and that
+    /// rewriting it only replaces the segments that changed.
+    #[test]
+    fn test_chunked_checkpoint_round_trip() {
+        let dir = std::env::temp_dir().join(format!("primesocket_chunked_{}", std::process::id()));
+        let client: SocketAddr = "127.0.0.1:4000".parse().unwrap();
+        let mut server_state = ServerState::new(2, 100_000, DEFAULT_STEP);
+        server_state.merge_primes(primes_up_to(50_000));
+        server_state.last_checked = 50_000;
+        server_state.completed.insert(2, 50_000);
+        server_state.assignments.assign(50_000, 51_000, client);
+        server_state
+            .client_stats
+            .entry(client)
+            .or_default()
+            .record_save(5_133);
+
+        server_state.save_chunked_checkpoint(&dir, 10_000).unwrap();
+        let segments = |dir: &Path| -> Vec<String> {
+            let mut names: Vec<String> = fs::read_dir(dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+                .filter(|name| name.starts_with("segment_"))
+                .collect();
+            names.sort();
+            names
+        };
+        let before = segments(&dir);
+        assert_eq!(before.len(), 5);
+
+        let restored = ServerState::load_chunked_checkpoint(&dir).unwrap();
+        assert_eq!(restored.primes, server_state.primes);
+        assert_eq!(restored.last_checked, 50_000);
+        assert_eq!(restored.completed_frontier(), 50_000);
+        assert_eq!(restored.client_stats, server_state.client_stats);
+        assert_eq!(restored.epoch, server_state.epoch);
+
+        server_state.merge_primes(vec![50_021]);
+        server_state.save_chunked_checkpoint(&dir, 10_000).unwrap();
+        let after = segments(&dir);
+        assert_eq!(after.len(), 6);
+        assert_eq!(before[..4], after[..4]);
+
+        let segment = dir.join(&after[0]);
+        fs::write(&segment, [2, 1]).unwrap();
+        let error = ServerState::load_chunked_checkpoint(&dir).unwrap_err();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    /// Tests that assignments and per-client counters survive a checkpoint round trip.
+    #[test]
+    fn test_checkpoint_restores_assignments_and_client_stats() {
+        let first: SocketAddr = "127.0.0.1:4000".parse().unwrap();
+        let second: SocketAddr = "127.0.0.1:4001".parse().unwrap();
+        let mut server_state = ServerState::new(2, 10_000, DEFAULT_STEP);
+        server_state.assignments.assign(2, 1_002, first);
+        server_state.assignments.assign(1_002, 2_002, second);
+        server_state.assignments.complete(1_002);
+        server_state.completed.insert(2, 1_002);
+        server_state.last_checked = 1_002;
+        server_state.assigned_up_to = 2_002;
+        server_state
+            .client_stats
+            .entry(first)
+            .or_default()
+            .record_save(3);
+
+        let path = std::env::temp_dir().join(format!(
+            "primesocket_checkpoint_{}.json",
+            std::process::id()
+        ));
+        server_state.save_checkpoint(&path).unwrap();
+        let mut restored = ServerState::load_checkpoint(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(restored.last_checked, 1_002);
+        assert_eq!(restored.assigned_up_to, 2_002);
+        assert_eq!(restored.completed_frontier(), 1_002);
+        assert_eq!(restored.primes, server_state.primes);
+        assert_eq!(
+            restored.assignments.outstanding().len(),
+            server_state.assignments.outstanding().len()
+        );
+        let assignment = &restored.assignments.assignments[&1_002];
+        assert_eq!((assignment.end, assignment.assigned_to), (2_002, second));
+        assert!(assignment.lease_expires > Instant::now());
+        assert_eq!(restored.client_stats, server_state.client_stats);
+
+        restored.max_ranges_per_client = Some(1);
+        assert!(restored.client_limit_reached(&first));
+        assert!(!restored.client_limit_reached(&second));
     }
 }
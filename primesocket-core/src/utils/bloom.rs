@@ -0,0 +1,178 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+
+/// Mixes the bits of a number (the SplitMix64 finalizer).
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// A Bloom filter over `u64` values.
+///
+/// Membership queries never give false negatives; false positives occur at
+/// roughly the rate the filter was sized for, as long as no more values than
+/// expected are inserted. Bit positions come from double hashing of two
+/// SplitMix64 mixes of the value.
+///
+/// On disk, the filter is stored as the number of bits (`u64`), the number of
+/// hashes (`u32`), then the bit words (`u64` each), all little-endian.
+///
+/// # Fields
+///
+/// * `num_bits` - The number of bits of the filter.
+/// * `num_hashes` - The number of bits set per value.
+/// * `bits` - The bit array, 64 bits per word.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BloomFilter {
+    pub num_bits: u64,
+    pub num_hashes: u32,
+    pub bits: Vec<u64>,
+}
+
+impl BloomFilter {
+    /// Creates an empty filter sized for `expected` values at a false-positive rate.
+    ///
+    /// # Arguments
+    ///
+    /// * `expected` - The number of values expected to be inserted.
+    /// * `false_positive_rate` - The wanted false-positive rate, in `(0, 1)`.
+    ///
+    /// # Returns
+    ///
+    /// A `BloomFilter` with `-n ln p / ln² 2` bits and `(m / n) ln 2` hashes.
+    pub fn with_rate(expected: u64, false_positive_rate: f64) -> BloomFilter {
+        let n = expected.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = ((-n * false_positive_rate.ln() / (ln2 * ln2)).ceil() as u64).max(64);
+        let num_hashes = ((num_bits as f64 / n * ln2).round() as u32).clamp(1, 32);
+        BloomFilter {
+            num_bits,
+            num_hashes,
+            bits: vec![0; num_bits.div_ceil(64) as usize],
+        }
+    }
+
+    /// Returns the bit positions of a value.
+    fn positions(&self, value: u64) -> impl Iterator<Item = u64> + '_ {
+        let first = mix(value);
+        let second = mix(first ^ 0x9e37_79b9_7f4a_7c15) | 1;
+        (0..self.num_hashes as u64)
+            .map(move |i| first.wrapping_add(i.wrapping_mul(second)) % self.num_bits)
+    }
+
+    /// Adds a value to the filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value to add.
+    pub fn insert(&mut self, value: u64) {
+        let positions: Vec<u64> = self.positions(value).collect();
+        for bit in positions {
+            self.bits[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+    }
+
+    /// Checks whether a value may be in the filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value to look up.
+    ///
+    /// # Returns
+    ///
+    /// `false` if the value was never inserted; `true` if it was, or on a false positive.
+    pub fn contains(&self, value: u64) -> bool {
+        self.positions(value)
+            .all(|bit| self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Writes the filter to a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to write.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` if the file could not be written.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut bytes = Vec::with_capacity(12 + self.bits.len() * 8);
+        bytes.extend_from_slice(&self.num_bits.to_le_bytes());
+        bytes.extend_from_slice(&self.num_hashes.to_le_bytes());
+        for word in &self.bits {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        fs::write(path, bytes)
+    }
+
+    /// Reads a filter written by `save`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to read.
+    ///
+    /// # Returns
+    ///
+    /// The `BloomFilter` stored in the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` if the file can't be read, or one of kind `InvalidData`
+    /// if its size doesn't match its header.
+    pub fn load(path: &Path) -> io::Result<BloomFilter> {
+        let bytes = fs::read(path)?;
+        let invalid = || io::Error::new(ErrorKind::InvalidData, "malformed Bloom filter");
+        if bytes.len() < 12 {
+            return Err(invalid());
+        }
+        let num_bits = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+        let num_hashes = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
+        let words = &bytes[12..];
+        if num_bits == 0 || num_hashes == 0 || words.len() as u64 != num_bits.div_ceil(64) * 8 {
+            return Err(invalid());
+        }
+        Ok(BloomFilter {
+            num_bits,
+            num_hashes,
+            bits: words
+                .chunks_exact(8)
+                .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::sieve::primes_up_to;
+
+    /// Tests that every prime tests positive, that the false-positive rate on
+    /// composites stays close to the configured one, and that the filter survives a
+    /// file round trip.
+    #[test]
+    fn test_bloom_filter_false_positive_rate() {
+        let primes = primes_up_to(200_000);
+        let mut filter = BloomFilter::with_rate(primes.len() as u64, 0.01);
+        for &prime in &primes {
+            filter.insert(prime);
+        }
+        assert!(primes.iter().all(|&prime| filter.contains(prime)));
+
+        let composites: Vec<u64> = (200_001..400_000u64)
+            .filter(|&n| n % 2 == 0 || n % 3 == 0)
+            .collect();
+        let false_positives = composites.iter().filter(|&&n| filter.contains(n)).count();
+        let rate = false_positives as f64 / composites.len() as f64;
+        assert!(rate < 0.02, "false-positive rate {} above tolerance", rate);
+
+        let path =
+            std::env::temp_dir().join(format!("primesocket_bloom_{}.bin", std::process::id()));
+        filter.save(&path).unwrap();
+        let loaded = BloomFilter::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), filter);
+    }
+}
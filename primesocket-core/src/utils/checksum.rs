@@ -0,0 +1,114 @@
+/// The size of the checksum prepended to each datagram when checksums are enabled.
+pub const CHECKSUM_SIZE: usize = 4;
+
+/// The lookup table of the CRC-32 (IEEE 802.3) polynomial, in reflected form.
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+/// Builds `CRC32_TABLE` at compile time.
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// Computes the CRC-32 of `bytes`, as used by Ethernet, zlib and PNG.
+///
+/// # Arguments
+///
+/// * `bytes` - The data to checksum.
+///
+/// # Returns
+///
+/// The CRC-32 of the data.
+///
+/// # Example
+///
+/// ```
+/// assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+/// ```
+pub fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, &byte| {
+        CRC32_TABLE[((crc ^ u32::from(byte)) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Prepends the big-endian CRC-32 of a datagram to it.
+///
+/// # Arguments
+///
+/// * `datagram` - The datagram to protect.
+///
+/// # Returns
+///
+/// A `Vec<u8>` containing the checksum followed by the datagram.
+pub fn seal(datagram: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(CHECKSUM_SIZE + datagram.len());
+    bytes.extend_from_slice(&crc32(datagram).to_be_bytes());
+    bytes.extend_from_slice(datagram);
+    bytes
+}
+
+/// Checks the checksum of a datagram written by `seal`.
+///
+/// # Arguments
+///
+/// * `bytes` - The received datagram, checksum first.
+///
+/// # Returns
+///
+/// `Some` with the datagram without its checksum, or `None` if it is too short or its
+/// checksum doesn't match, i.e. it was corrupted on the way.
+pub fn open(bytes: &[u8]) -> Option<&[u8]> {
+    if bytes.len() < CHECKSUM_SIZE {
+        return None;
+    }
+    let (checksum, datagram) = bytes.split_at(CHECKSUM_SIZE);
+    let expected = u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]);
+    (crc32(datagram) == expected).then_some(datagram)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests the CRC-32 against known check values.
+    #[test]
+    fn test_crc32_known_values() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(
+            crc32(b"The quick brown fox jumps over the lazy dog"),
+            0x414F_A339
+        );
+    }
+
+    /// Tests that a sealed datagram opens intact, and that any flipped byte or a
+    /// truncated datagram is rejected.
+    #[test]
+    fn test_open_rejects_corrupted_datagrams() {
+        let datagram = br#"{"task":"save","primes":[2,3,5,7]}"#;
+        let sealed = seal(datagram);
+        assert_eq!(open(&sealed), Some(&datagram[..]));
+
+        for i in 0..sealed.len() {
+            let mut corrupted = sealed.clone();
+            corrupted[i] ^= 0x01;
+            assert_eq!(open(&corrupted), None, "flipped byte {} accepted", i);
+        }
+        assert_eq!(open(&sealed[..3]), None);
+    }
+}
@@ -0,0 +1,528 @@
+use super::checksum::{self, CHECKSUM_SIZE};
+use log::warn;
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::time::Interval;
+
+/// The largest payload that fits in a single UDP datagram over IPv4.
+pub const MAX_DATAGRAM_SIZE: usize = 65_507;
+
+/// The smallest datagram size a peer is allowed to negotiate.
+pub const MIN_DATAGRAM_SIZE: usize = 256;
+
+/// Marks a datagram as a chunk. `0xFF` never appears in UTF-8, so chunks
+/// can't be mistaken for plain JSON messages.
+const CHUNK_MAGIC: [u8; 4] = [0xFF, b'P', b'S', b'C'];
+
+/// The size of the chunk header: magic, message id, sequence number and total.
+pub const CHUNK_HEADER_SIZE: usize = 12;
+
+/// The largest message accepted once reassembled, bounding the memory a peer can claim.
+pub const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// How long an incomplete message is kept before being discarded.
+const REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(30);
+
+static NEXT_MESSAGE_ID: AtomicU32 = AtomicU32::new(1);
+
+/// Represents one fragment of a message split across several datagrams.
+///
+/// # Fields
+///
+/// * `id` - Identifies the message the chunk belongs to.
+/// * `seq` - The position of the chunk within the message, starting at `0`.
+/// * `total` - The number of chunks the message was split into.
+/// * `data` - The bytes of the message carried by this chunk.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chunk {
+    pub id: u32,
+    pub seq: u16,
+    pub total: u16,
+    pub data: Vec<u8>,
+}
+
+impl Chunk {
+    /// Encodes the chunk as a datagram, header first.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec<u8>` containing the header followed by the chunk data.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(CHUNK_HEADER_SIZE + self.data.len());
+        bytes.extend_from_slice(&CHUNK_MAGIC);
+        bytes.extend_from_slice(&self.id.to_be_bytes());
+        bytes.extend_from_slice(&self.seq.to_be_bytes());
+        bytes.extend_from_slice(&self.total.to_be_bytes());
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    /// Decodes a datagram into a chunk.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The received datagram.
+    ///
+    /// # Returns
+    ///
+    /// `Some(Chunk)` if the datagram carries a valid chunk header, or `None` if it
+    /// is a plain (unchunked) message.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Chunk> {
+        if bytes.len() < CHUNK_HEADER_SIZE || bytes[..4] != CHUNK_MAGIC {
+            return None;
+        }
+
+        let id = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        let seq = u16::from_be_bytes([bytes[8], bytes[9]]);
+        let total = u16::from_be_bytes([bytes[10], bytes[11]]);
+        if total == 0 || seq >= total {
+            return None;
+        }
+
+        Some(Chunk {
+            id,
+            seq,
+            total,
+            data: bytes[CHUNK_HEADER_SIZE..].to_vec(),
+        })
+    }
+}
+
+/// Splits a payload into chunks whose encoded size never exceeds `max`.
+///
+/// # Arguments
+///
+/// * `bytes` - The payload to split.
+/// * `max` - The maximum size of each encoded datagram, header included.
+///
+/// # Returns
+///
+/// A `Vec<Chunk>` sharing a fresh message id, in sequence order.
+///
+/// # Example
+///
+/// ```
+/// let chunks = chunk_payload(&[0u8; 1000], 256);
+/// assert!(chunks.iter().all(|c| c.to_bytes().len() <= 256));
+/// ```
+pub fn chunk_payload(bytes: &[u8], max: usize) -> Vec<Chunk> {
+    let id = NEXT_MESSAGE_ID.fetch_add(1, Ordering::Relaxed);
+    let data_size = max.saturating_sub(CHUNK_HEADER_SIZE).max(1);
+    let total = bytes.len().div_ceil(data_size).max(1) as u16;
+
+    if bytes.is_empty() {
+        return vec![Chunk {
+            id,
+            seq: 0,
+            total,
+            data: Vec::new(),
+        }];
+    }
+
+    bytes
+        .chunks(data_size)
+        .enumerate()
+        .map(|(seq, data)| Chunk {
+            id,
+            seq: seq as u16,
+            total,
+            data: data.to_vec(),
+        })
+        .collect()
+}
+
+/// Clamps a peer's requested datagram size to the supported bounds.
+///
+/// # Arguments
+///
+/// * `requested` - The maximum payload size announced by the peer.
+///
+/// # Returns
+///
+/// The datagram size both sides will use.
+pub fn negotiate_datagram_size(requested: u32) -> usize {
+    (requested as usize).clamp(MIN_DATAGRAM_SIZE, MAX_DATAGRAM_SIZE)
+}
+
+/// A message being reassembled from its chunks.
+///
+/// Oversized messages keep their entry, with no parts, until they time out so
+/// that their remaining chunks are ignored.
+struct PartialMessage {
+    parts: Vec<Option<Vec<u8>>>,
+    received: usize,
+    size: usize,
+    oversized: bool,
+    updated_at: Instant,
+}
+
+/// Reassembles chunked messages received from one or more peers.
+///
+/// Messages are keyed by sender and message id, so chunks from different
+/// peers (or different messages from the same peer) never mix. Messages
+/// growing past `max_message_size` are discarded.
+pub struct Reassembler {
+    partial: HashMap<(SocketAddr, u32), PartialMessage>,
+    max_message_size: usize,
+}
+
+impl Default for Reassembler {
+    fn default() -> Reassembler {
+        Reassembler::with_max_message_size(MAX_MESSAGE_SIZE)
+    }
+}
+
+impl Reassembler {
+    /// Creates an empty `Reassembler` accepting messages up to `MAX_MESSAGE_SIZE`.
+    pub fn new() -> Reassembler {
+        Reassembler::default()
+    }
+
+    /// Creates an empty `Reassembler` accepting messages up to `max_message_size` bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_message_size` - The largest reassembled message accepted.
+    pub fn with_max_message_size(max_message_size: usize) -> Reassembler {
+        Reassembler {
+            partial: HashMap::new(),
+            max_message_size,
+        }
+    }
+
+    /// Adds a chunk and returns the full message once every chunk arrived.
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The address the chunk was received from.
+    /// * `chunk` - The received chunk.
+    ///
+    /// # Returns
+    ///
+    /// `Some(Vec<u8>)` with the reassembled message if `chunk` completed it,
+    /// otherwise `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` of kind `InvalidData` when `chunk` makes its message
+    /// exceed `max_message_size`. The message is discarded, along with any of its
+    /// chunks received later.
+    pub fn push(&mut self, from: SocketAddr, chunk: Chunk) -> io::Result<Option<Vec<u8>>> {
+        self.partial
+            .retain(|_, message| message.updated_at.elapsed() < REASSEMBLY_TIMEOUT);
+
+        let total = chunk.total as usize;
+        let message = self
+            .partial
+            .entry((from, chunk.id))
+            .or_insert_with(|| PartialMessage {
+                parts: vec![None; total],
+                received: 0,
+                size: 0,
+                oversized: false,
+                updated_at: Instant::now(),
+            });
+
+        if message.oversized || message.parts.len() != total {
+            return Ok(None);
+        }
+
+        let part = &mut message.parts[chunk.seq as usize];
+        if part.is_none() {
+            message.size += chunk.data.len();
+            *part = Some(chunk.data);
+            message.received += 1;
+        }
+        message.updated_at = Instant::now();
+
+        if message.size > self.max_message_size {
+            message.oversized = true;
+            message.parts = Vec::new();
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "message {} from {} exceeds {} bytes",
+                    chunk.id, from, self.max_message_size
+                ),
+            ));
+        }
+
+        if message.received < total {
+            return Ok(None);
+        }
+
+        Ok(self
+            .partial
+            .remove(&(from, chunk.id))
+            .map(|message| message.parts.into_iter().flatten().flatten().collect()))
+    }
+
+    /// Returns the number of messages still waiting for chunks.
+    pub fn pending(&self) -> usize {
+        self.partial.len()
+    }
+}
+
+/// Sends a message, splitting it into chunks if it exceeds `max`.
+///
+/// # Arguments
+///
+/// * `socket` - The `UdpSocket` to send through.
+/// * `bytes` - The message to send.
+/// * `target` - The destination address.
+/// * `max` - The maximum datagram size accepted by the destination.
+/// * `checksum` - Whether each datagram starts with the CRC-32 of the rest, which the
+///   destination must expect as well.
+///
+/// # Errors
+///
+/// Returns an `io::Error` if any datagram fails to send.
+pub async fn send_message(
+    socket: &UdpSocket,
+    bytes: &[u8],
+    target: SocketAddr,
+    max: usize,
+    checksum: bool,
+) -> io::Result<()> {
+    send_message_paced(socket, bytes, target, max, checksum, None).await
+}
+
+/// Sends a message like `send_message`, waiting for `pacer` before each datagram.
+///
+/// # Arguments
+///
+/// * `socket` - The `UdpSocket` to send through.
+/// * `bytes` - The message to send.
+/// * `target` - The destination address.
+/// * `max` - The maximum datagram size accepted by the destination, checksum included.
+/// * `checksum` - Whether each datagram starts with the CRC-32 of the rest.
+/// * `pacer` - Ticks once per datagram allowed, if sending is rate limited.
+///
+/// # Errors
+///
+/// Returns an `io::Error` if any datagram fails to send.
+pub async fn send_message_paced(
+    socket: &UdpSocket,
+    bytes: &[u8],
+    target: SocketAddr,
+    max: usize,
+    checksum: bool,
+    mut pacer: Option<&mut Interval>,
+) -> io::Result<()> {
+    let max = if checksum {
+        max.saturating_sub(CHECKSUM_SIZE)
+    } else {
+        max
+    };
+    if bytes.len() <= max {
+        if let Some(pacer) = pacer.as_mut() {
+            pacer.tick().await;
+        }
+        send_datagram(socket, bytes, target, checksum).await?;
+        return Ok(());
+    }
+
+    for chunk in chunk_payload(bytes, max) {
+        if let Some(pacer) = pacer.as_mut() {
+            pacer.tick().await;
+        }
+        send_datagram(socket, &chunk.to_bytes(), target, checksum).await?;
+    }
+    Ok(())
+}
+
+/// Sends a single datagram, sealed with its checksum if `checksum` is set.
+async fn send_datagram(
+    socket: &UdpSocket,
+    datagram: &[u8],
+    target: SocketAddr,
+    checksum: bool,
+) -> io::Result<()> {
+    if checksum {
+        socket.send_to(&checksum::seal(datagram), target).await?;
+    } else {
+        socket.send_to(datagram, target).await?;
+    }
+    Ok(())
+}
+
+/// Receives datagrams until a complete message is available.
+///
+/// Plain datagrams are returned as they are; chunks are fed to `reassembler`
+/// until their message is complete. The function is cancel-safe: chunks
+/// received before cancellation stay in the reassembler.
+///
+/// With `checksum`, datagrams whose CRC-32 doesn't match their content were
+/// corrupted on the way: they are logged and dropped, like lost datagrams.
+///
+/// # Arguments
+///
+/// * `socket` - The `UdpSocket` to receive from.
+/// * `reassembler` - Holds the chunks of incomplete messages.
+/// * `checksum` - Whether each datagram is expected to start with its CRC-32.
+///
+/// # Returns
+///
+/// The complete message and the address it came from.
+///
+/// # Errors
+///
+/// Returns an `io::Error` if receiving from the socket fails, or one of kind
+/// `InvalidData` if a chunked message exceeds the reassembler's size limit.
+pub async fn recv_message(
+    socket: &UdpSocket,
+    reassembler: &mut Reassembler,
+    checksum: bool,
+) -> io::Result<(Vec<u8>, SocketAddr)> {
+    let mut buffer = vec![0; 65535];
+    loop {
+        let (size, src) = socket.recv_from(&mut buffer).await?;
+        let datagram = if checksum {
+            match checksum::open(&buffer[..size]) {
+                Some(datagram) => datagram,
+                None => {
+                    warn!("⚠️ Dropped a datagram from {} with a bad checksum", src);
+                    continue;
+                }
+            }
+        } else {
+            &buffer[..size]
+        };
+        match Chunk::from_bytes(datagram) {
+            Some(chunk) => {
+                if let Some(message) = reassembler.push(src, chunk)? {
+                    return Ok((message, src));
+                }
+            }
+            None => return Ok((datagram.to_vec(), src)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that chunks respect the maximum size and reassemble to the original payload.
+    #[test]
+    fn test_chunk_payload_round_trip() {
+        let payload: Vec<u8> = (0..5_000).map(|i| (i % 251) as u8).collect();
+        let chunks = chunk_payload(&payload, 300);
+
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| c.to_bytes().len() <= 300));
+
+        let from: SocketAddr = "127.0.0.1:9999".parse().unwrap();
+        let mut reassembler = Reassembler::new();
+        let mut result = None;
+        for chunk in chunks.into_iter().rev() {
+            let decoded = Chunk::from_bytes(&chunk.to_bytes()).unwrap();
+            result = reassembler.push(from, decoded).unwrap();
+        }
+
+        assert_eq!(result, Some(payload));
+        assert_eq!(reassembler.pending(), 0);
+    }
+
+    /// Tests that a 200 KB response, far above a single datagram, reassembles identically.
+    #[test]
+    fn test_large_response_round_trip() {
+        let response = crate::utils::json::Response {
+            task: crate::utils::json::Task::Range,
+            primes: Some(crate::utils::sieve::primes_up_to(400_000)),
+            ..Default::default()
+        };
+        let payload = response.to_json().unwrap().into_bytes();
+        assert!(payload.len() > 200_000);
+
+        let chunks = chunk_payload(&payload, MAX_DATAGRAM_SIZE);
+        assert!(chunks.len() > 3);
+        assert!(chunks
+            .iter()
+            .all(|c| c.to_bytes().len() <= MAX_DATAGRAM_SIZE));
+
+        let from: SocketAddr = "127.0.0.1:9999".parse().unwrap();
+        let mut reassembler = Reassembler::new();
+        let mut result = None;
+        for chunk in chunks {
+            result = reassembler
+                .push(from, Chunk::from_bytes(&chunk.to_bytes()).unwrap())
+                .unwrap();
+        }
+
+        let message = String::from_utf8(result.unwrap()).unwrap();
+        let parsed = crate::utils::json::Response::from_json(&message).unwrap();
+        assert_eq!(message.as_bytes(), payload);
+        assert_eq!(parsed.primes, response.primes);
+    }
+
+    /// Tests that a message exceeding the size limit is discarded instead of buffered.
+    #[test]
+    fn test_reassembler_rejects_oversized_message() {
+        let payload = vec![7u8; 5_000];
+        let chunks = chunk_payload(&payload, 300);
+        let from: SocketAddr = "127.0.0.1:9999".parse().unwrap();
+        let mut reassembler = Reassembler::with_max_message_size(1_000);
+
+        let results: Vec<_> = chunks
+            .into_iter()
+            .map(|chunk| reassembler.push(from, chunk))
+            .collect();
+
+        let errors = results.iter().filter(|result| result.is_err()).count();
+        assert_eq!(errors, 1);
+        assert!(results.iter().all(|result| !matches!(result, Ok(Some(_)))));
+        let kind = results.into_iter().find_map(Result::err).unwrap().kind();
+        assert_eq!(kind, io::ErrorKind::InvalidData);
+        assert_eq!(reassembler.pending(), 1);
+    }
+
+    /// Tests that plain JSON datagrams are not taken for chunks.
+    #[test]
+    fn test_chunk_from_bytes_rejects_plain_messages() {
+        assert!(Chunk::from_bytes(br#"{"task":"start"}"#).is_none());
+        assert!(Chunk::from_bytes(&CHUNK_MAGIC).is_none());
+    }
+
+    /// Tests that with checksums, a datagram with a flipped byte is dropped while intact
+    /// messages, chunked or not, still arrive.
+    #[tokio::test]
+    async fn test_recv_message_rejects_flipped_byte() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let target = receiver.local_addr().unwrap();
+
+        let mut corrupted = checksum::seal(br#"{"task":"start"}"#);
+        corrupted[10] ^= 0x20;
+        sender.send_to(&corrupted, target).await.unwrap();
+        send_message(&sender, br#"{"task":"progress"}"#, target, 300, true)
+            .await
+            .unwrap();
+        let payload = vec![b'7'; 1_000];
+        send_message(&sender, &payload, target, 300, true)
+            .await
+            .unwrap();
+
+        let mut reassembler = Reassembler::new();
+        let (message, _) = recv_message(&receiver, &mut reassembler, true)
+            .await
+            .unwrap();
+        assert_eq!(message, br#"{"task":"progress"}"#);
+        let (message, _) = recv_message(&receiver, &mut reassembler, true)
+            .await
+            .unwrap();
+        assert_eq!(message, payload);
+    }
+
+    /// Tests that negotiated sizes are clamped to the supported bounds.
+    #[test]
+    fn test_negotiate_datagram_size() {
+        assert_eq!(negotiate_datagram_size(1), MIN_DATAGRAM_SIZE);
+        assert_eq!(negotiate_datagram_size(1_400), 1_400);
+        assert_eq!(negotiate_datagram_size(u32::MAX), MAX_DATAGRAM_SIZE);
+    }
+}
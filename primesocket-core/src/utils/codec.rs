@@ -0,0 +1,208 @@
+#[cfg(feature = "binary")]
+use crate::utils::json::{Response, Task};
+use crate::utils::json::{KEEPALIVE_JSON, SERIALIZATION_FAILED_JSON};
+#[cfg(feature = "binary")]
+use bincode::Options;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+
+/// The encoding of the messages exchanged by the server and its clients.
+///
+/// Both sides must use the same format: a message in the other format fails to
+/// decode and is dropped. `Bincode` is only available with the `binary` feature.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum WireFormat {
+    /// JSON text, readable and tolerant of fields added by other versions.
+    #[default]
+    Json,
+    /// The compact bincode encoding, with variable-length integers; both sides
+    /// must run the same version.
+    #[cfg(feature = "binary")]
+    Bincode,
+}
+
+/// The error raised when a message fails to encode or decode.
+#[derive(Debug)]
+pub enum CodecError {
+    /// The JSON codec failed.
+    Json(serde_json::Error),
+    /// The bincode codec failed.
+    #[cfg(feature = "binary")]
+    Bincode(bincode::Error),
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::Json(e) => write!(f, "invalid JSON: {}", e),
+            #[cfg(feature = "binary")]
+            CodecError::Bincode(e) => write!(f, "invalid bincode: {}", e),
+        }
+    }
+}
+
+impl WireFormat {
+    /// Looks up a format by name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - `"json"`, or `"bincode"` when built with the `binary` feature.
+    ///
+    /// # Returns
+    ///
+    /// `Some(WireFormat)`, or `None` if the name is unknown or not compiled in.
+    pub fn from_name(name: &str) -> Option<WireFormat> {
+        match name {
+            "json" => Some(WireFormat::Json),
+            #[cfg(feature = "binary")]
+            "bincode" => Some(WireFormat::Bincode),
+            _ => None,
+        }
+    }
+
+    /// Encodes a message.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The `Request` or `Response` to encode.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CodecError` if serialization fails.
+    pub fn encode<T: Serialize>(self, message: &T) -> Result<Vec<u8>, CodecError> {
+        match self {
+            WireFormat::Json => serde_json::to_vec(message).map_err(CodecError::Json),
+            #[cfg(feature = "binary")]
+            WireFormat::Bincode => bincode::DefaultOptions::new()
+                .serialize(message)
+                .map_err(CodecError::Bincode),
+        }
+    }
+
+    /// Decodes a message.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The encoded `Request` or `Response`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CodecError` describing why the bytes are not a valid message.
+    pub fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, CodecError> {
+        match self {
+            WireFormat::Json => serde_json::from_slice(bytes).map_err(CodecError::Json),
+            #[cfg(feature = "binary")]
+            WireFormat::Bincode => bincode::DefaultOptions::new()
+                .deserialize(bytes)
+                .map_err(CodecError::Bincode),
+        }
+    }
+
+    /// Returns the keepalive message in this format.
+    pub fn keepalive(self) -> Vec<u8> {
+        match self {
+            WireFormat::Json => KEEPALIVE_JSON.as_bytes().to_vec(),
+            #[cfg(feature = "binary")]
+            WireFormat::Bincode => self.encode_fixed(Task::Keepalive, ""),
+        }
+    }
+
+    /// Returns the reply sent in place of a response that failed to encode.
+    pub fn serialization_failed(self) -> Vec<u8> {
+        match self {
+            WireFormat::Json => SERIALIZATION_FAILED_JSON.as_bytes().to_vec(),
+            #[cfg(feature = "binary")]
+            WireFormat::Bincode => self.encode_fixed(Task::Error, "serialization_failed"),
+        }
+    }
+
+    /// Encodes a response carrying only a task and a status, which can't fail.
+    #[cfg(feature = "binary")]
+    fn encode_fixed(self, task: Task, status: &str) -> Vec<u8> {
+        let response = Response {
+            task,
+            status: status.to_string(),
+            ..Default::default()
+        };
+        self.encode(&response).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::json::{Request, Response, Task};
+
+    /// Tests that requests and responses carrying primes survive a round trip in
+    /// every format, and that the fixed messages decode.
+    #[test]
+    fn test_codec_round_trip() {
+        let formats = [
+            WireFormat::Json,
+            #[cfg(feature = "binary")]
+            WireFormat::Bincode,
+        ];
+        let primes: Vec<u64> = vec![2, 3, 5, 7, 1_000_003, u64::MAX - 58];
+        for format in formats {
+            let save = Request {
+                task: Task::Save,
+                start: Some(2),
+                end: Some(u64::MAX - 58),
+                primes: Some(primes.clone()),
+                sum: Some(u128::MAX),
+                ..Default::default()
+            };
+            let decoded: Request = format.decode(&format.encode(&save).unwrap()).unwrap();
+            assert_eq!(decoded.task, Task::Save);
+            assert_eq!((decoded.start, decoded.end), (save.start, save.end));
+            assert_eq!(decoded.primes, Some(primes.clone()));
+            assert_eq!(decoded.sum, Some(u128::MAX));
+
+            let range = Response {
+                task: Task::Range,
+                status: "processing".to_string(),
+                primes: Some(primes.clone()),
+                residue: Some((1, 4)),
+                ..Default::default()
+            };
+            let decoded: Response = format.decode(&format.encode(&range).unwrap()).unwrap();
+            assert_eq!(decoded.task, Task::Range);
+            assert_eq!(decoded.status, "processing");
+            assert_eq!(decoded.primes, Some(primes.clone()));
+            assert_eq!(decoded.residue, Some((1, 4)));
+
+            let keepalive: Response = format.decode(&format.keepalive()).unwrap();
+            assert_eq!(keepalive.task, Task::Keepalive);
+            let failed: Response = format.decode(&format.serialization_failed()).unwrap();
+            assert_eq!(failed.status, "serialization_failed");
+            assert!(format.decode::<Request>(b"\xff").is_err());
+        }
+    }
+
+    /// Tests that formats are looked up by name.
+    #[test]
+    fn test_wire_format_names() {
+        assert_eq!(WireFormat::from_name("json"), Some(WireFormat::Json));
+        #[cfg(feature = "binary")]
+        assert_eq!(WireFormat::from_name("bincode"), Some(WireFormat::Bincode));
+        assert_eq!(WireFormat::from_name("xml"), None);
+    }
+
+    /// Tests that bincode messages are smaller than JSON ones for large prime vectors,
+    /// and that `to_bytes` and `from_bytes` use them.
+    #[cfg(feature = "binary")]
+    #[test]
+    fn test_bincode_is_compact() {
+        let primes = crate::utils::sieve::primes_up_to(1_000_000);
+        let save = Request {
+            task: Task::Save,
+            primes: Some(primes.clone()),
+            ..Default::default()
+        };
+        let json = WireFormat::Json.encode(&save).unwrap();
+        let binary = save.to_bytes().unwrap();
+        assert!(binary.len() < json.len());
+        assert_eq!(Request::from_bytes(&binary).unwrap().primes, Some(primes));
+    }
+}
@@ -1,37 +1,207 @@
+#[cfg(feature = "binary")]
+use crate::utils::codec::{CodecError, WireFormat};
 use serde::{Deserialize, Serialize};
 
+/// The version of the protocol spoken by this release, carried by every message.
+///
+/// Bump it whenever a field changes meaning, so peers from other releases are
+/// rejected instead of misreading each other. Messages without a version are
+/// taken as the current one.
+pub const PROTOCOL_VERSION: u8 = 1;
+
+/// The reply sent in place of a response that failed to serialize.
+pub const SERIALIZATION_FAILED_JSON: &str = r#"{"task":"error","status":"serialization_failed"}"#;
+
+/// The tiny datagram sent to keep a client's NAT mapping alive.
+pub const KEEPALIVE_JSON: &str = r#"{"task":"keepalive","status":""}"#;
+
+/// Serializes a message into a JSON string.
+///
+/// # Arguments
+///
+/// * `value` - The message to serialize.
+///
+/// # Errors
+///
+/// Returns the `serde_json::Error` raised by serialization, if any.
+fn to_json_string<T: Serialize>(value: &T) -> serde_json::Result<String> {
+    serde_json::to_string(value)
+}
+
+/// The version assumed for messages that don't carry one.
+fn protocol_version() -> u8 {
+    PROTOCOL_VERSION
+}
+
+/// The task carried by a request or a response.
+///
+/// Tasks travel as snake_case strings (e.g. `"query_range"`); any task this
+/// version doesn't know deserializes to `Unknown` and is answered with an error.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Task {
+    /// Asks the server for a range to compute.
+    Start,
+    /// Hands a range to a client.
+    Range,
+    /// Submits the results of a range.
+    Save,
+    /// Tells the client loop to keep requesting work.
+    Continue,
+    /// Acknowledges a save, echoing the `end` recorded; more work is available.
+    SaveAck,
+    /// The computation is over.
+    Done,
+    /// The request failed; the `status` tells why.
+    Error,
+    /// Queries or reports the progress of the computation.
+    Progress,
+    /// Queries or returns the primes found so far, without taking any work.
+    Fetch,
+    /// Queries or reports the throughput of the computation.
+    Stats,
+    /// (Admin) Writes the primes to disk without completing.
+    Flush,
+    /// Acknowledges a flush.
+    Flushed,
+    /// (Admin) Queries or reports the outstanding assignments.
+    Outstanding,
+    /// (Admin) Raises the end of the computation.
+    Extend,
+    /// Acknowledges an extension.
+    Extended,
+    /// Queries or answers whether a number is prime.
+    IsPrime,
+    /// Queries or answers the primes in a range.
+    QueryRange,
+    /// Queries or reports the address the server observed.
+    Whoami,
+    /// Keeps the NAT mapping of a client holding an assignment alive; carries no data.
+    Keepalive,
+    /// Tells the client loop to stop.
+    Close,
+    /// (Admin) Stops the server, saving the primes found so far.
+    Shutdown,
+    /// Requests or answers a page of the primes of a finished computation.
+    Download,
+    /// Any task this version doesn't know.
+    #[default]
+    #[serde(other)]
+    Unknown,
+}
+
 /// Represents a response from the server.
 ///
 /// This struct contains information about the status of the request,
 /// along with a range of numbers processed by the server. It can include
-/// a sieve (a list of primes).
+/// the base primes needed to sieve that range.
+///
+/// The same schema is used by the server to answer and by the client to parse.
 ///
 /// # Fields
 ///
-/// * `task` - A string representing the type of task the server is performing.
+/// * `version` - The protocol version of the server, `PROTOCOL_VERSION` by default.
+/// * `task` - The `Task` the response answers with.
 /// * `status` - A string indicating the status of the task (e.g., "in_progress", "completed").
 /// * `start` - The starting number in the range being processed (optional).
 /// * `end` - The ending number in the range being processed (optional).
 /// * `primes` - An optional vector containing the prime numbers identified so far.
+/// * `bytes` - The number of bytes written to disk by an admin `"flush"` (optional).
+/// * `max_payload` - The datagram size negotiated with the client, in bytes (optional).
+/// * `outstanding` - The ranges assigned but not completed yet, for the admin `"outstanding"` task (optional).
+/// * `last_checked` - The highest number saved so far (optional).
+/// * `completed_frontier` - The largest value below which every range is completed (optional).
+/// * `assigned_up_to` - The highest number handed out to clients so far (optional).
+/// * `is_prime` - The answer to an `"is_prime"` query (optional).
+/// * `residue` - The residue class `(a, m)` a `"range"` is restricted to (optional).
+/// * `address` - The source address the server observed, for `"whoami"` (optional).
+/// * `primes_path` - The file holding the base primes of a `"range"` too large to send
+///   inline; `primes` is then left out (optional).
+/// * `epoch` - The id of the run a `"range"` belongs to, to send back with its `"save"` (optional).
+/// * `offset` - The index of the first prime of a `"download"` or `"fetch"` page (optional).
+/// * `total` - The number of primes available for `"download"` or `"fetch"` (optional).
+/// * `sum_only` - Whether to save the sum and count of the primes of a `"range"` instead
+///   of the list (optional).
+/// * `overlapping_saves` - How many saves partially overlapped saved ranges, for `"progress"`
+///   (optional).
+/// * `segments` - How many saves were accepted, for `"stats"` (optional).
+/// * `bytes_received` - The size of all the requests received, in bytes, for `"stats"` (optional).
+/// * `primes_per_second` - The primes saved per second since the server started, for
+///   `"stats"` (optional).
+/// * `elapsed_ms` - The time since the server started, in milliseconds, for `"stats"` (optional).
 ///
 /// # Example
 ///
 /// ```
 /// let response = Response {
-///     task: "processing".to_string(),
+///     task: Task::Range,
 ///     status: "in_progress".to_string(),
 ///     start: Some(1),
 ///     end: Some(100),
 ///     primes: Some(vec![2, 3, 5, 7]),
+///     ..Default::default()
 /// };
 /// ```
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Response {
-    pub task: String,
+    #[serde(default = "protocol_version")]
+    pub version: u8,
+    pub task: Task,
     pub status: String,
-    pub start: Option<u32>,
-    pub end: Option<u32>,
-    pub primes: Option<Vec<u32>>,
+    pub start: Option<u64>,
+    pub end: Option<u64>,
+    pub primes: Option<Vec<u64>>,
+    pub bytes: Option<u64>,
+    pub max_payload: Option<u32>,
+    pub outstanding: Option<Vec<OutstandingRange>>,
+    pub last_checked: Option<u64>,
+    pub completed_frontier: Option<u64>,
+    pub assigned_up_to: Option<u64>,
+    pub is_prime: Option<bool>,
+    pub residue: Option<(u64, u64)>,
+    pub address: Option<String>,
+    pub primes_path: Option<String>,
+    pub epoch: Option<u64>,
+    pub offset: Option<u64>,
+    pub total: Option<u64>,
+    pub sum_only: Option<bool>,
+    pub overlapping_saves: Option<u64>,
+    pub segments: Option<u64>,
+    pub bytes_received: Option<u64>,
+    pub primes_per_second: Option<f64>,
+    pub elapsed_ms: Option<u64>,
+}
+
+impl Default for Response {
+    fn default() -> Response {
+        Response {
+            version: PROTOCOL_VERSION,
+            task: Task::default(),
+            status: String::new(),
+            start: None,
+            end: None,
+            primes: None,
+            bytes: None,
+            max_payload: None,
+            outstanding: None,
+            last_checked: None,
+            completed_frontier: None,
+            assigned_up_to: None,
+            is_prime: None,
+            residue: None,
+            address: None,
+            primes_path: None,
+            epoch: None,
+            offset: None,
+            total: None,
+            sum_only: None,
+            overlapping_saves: None,
+            segments: None,
+            bytes_received: None,
+            primes_per_second: None,
+            elapsed_ms: None,
+        }
+    }
 }
 
 impl Response {
@@ -41,20 +211,26 @@ impl Response {
     ///
     /// A `String` representing the serialized `Response` object in JSON format.
     ///
+    /// # Errors
+    ///
+    /// Returns a `serde_json::Error` if serialization fails, so callers can log and
+    /// handle it instead of sending an empty object.
+    ///
     /// # Example
     ///
     /// ```
     /// let response = Response {
-    ///     task: "done".to_string(),
+    ///     task: Task::Done,
     ///     status: "success".to_string(),
     ///     start: Some(1),
     ///     end: Some(100),
     ///     primes: Some(vec![2, 3, 5, 7]),
+    ///     ..Default::default()
     /// };
-    /// let json = response.to_json();
+    /// let json = response.to_json().unwrap();
     /// ```
-    pub fn to_json(&self) -> String {
-        serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string())
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        to_json_string(self)
     }
 
     /// Converts a JSON string into a `Response` struct.
@@ -75,35 +251,135 @@ impl Response {
     /// let response = Response::from_json(json);
     /// ```
     pub fn from_json(json: &str) -> Option<Response> {
-        serde_json::from_str(json).ok()
+        Response::try_from_json(json).ok()
+    }
+
+    /// Converts a JSON string into a `Response` struct, keeping the parse error.
+    ///
+    /// # Arguments
+    ///
+    /// * `json` - A JSON string to be deserialized into a `Response` object.
+    ///
+    /// # Errors
+    ///
+    /// Returns the `serde_json::Error` describing why and where parsing failed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let error = Response::try_from_json(r#"{"task":"start""#).unwrap_err();
+    /// assert!(error.is_eof());
+    /// ```
+    pub fn try_from_json(json: &str) -> serde_json::Result<Response> {
+        serde_json::from_str(json)
+    }
+
+    /// Converts the `Response` struct into bincode bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CodecError` if serialization fails.
+    #[cfg(feature = "binary")]
+    pub fn to_bytes(&self) -> Result<Vec<u8>, CodecError> {
+        WireFormat::Bincode.encode(self)
+    }
+
+    /// Converts bincode bytes into a `Response` struct.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The bytes written by `to_bytes`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CodecError` if the bytes are not a valid `Response`.
+    #[cfg(feature = "binary")]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Response, CodecError> {
+        WireFormat::Bincode.decode(bytes)
     }
 }
 
+/// Represents a range assigned to a client that has not been completed yet.
+///
+/// # Fields
+///
+/// * `start` - The first number of the range.
+/// * `end` - The last number of the range.
+/// * `assigned_to` - The address of the client the range was assigned to.
+/// * `lease_expires` - When the assignment expires, as a UNIX timestamp in milliseconds.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OutstandingRange {
+    pub start: u64,
+    pub end: u64,
+    pub assigned_to: String,
+    pub lease_expires: u64,
+}
+
 /// Represents a request sent to the server.
 ///
 /// This struct is used to send information to the server, such as the task
-/// being requested and an optional sieve that may accompany the request.
+/// being requested and the primes found in a range, for a `"save"`.
 ///
 /// # Fields
 ///
-/// * `task` - A string representing the type of task the client wants the server to perform.
-/// * `end` - An optional `u32` representing the end of the range for the task, if applicable.
+/// * `version` - The protocol version of the client, `PROTOCOL_VERSION` by default.
+/// * `task` - The `Task` the client wants the server to perform.
+/// * `start` - An optional `u64` representing the start of the range for the task, if applicable.
+/// * `end` - An optional `u64` representing the end of the range for the task, if applicable.
 /// * `primes` - An optional vector containing the prime numbers to be used for the task.
+/// * `secret` - An optional shared secret authorizing admin tasks such as `"flush"`.
+/// * `max_payload` - The largest datagram the client accepts, in bytes (optional).
+/// * `epoch` - The id of the run the range of a `"save"` was handed out by (optional).
+/// * `offset` - The index of the first prime of the requested `"download"` or `"fetch"` page (optional).
+/// * `limit` - The most primes wanted in the `"download"` or `"fetch"` page (optional).
+/// * `sum` - The sum of the primes of a `"save"` sent in place of the list (optional).
+/// * `count` - The number of primes of a `"save"` sent in place of the list (optional).
 ///
 /// # Example
 ///
 /// ```
 /// let request = Request {
-///     task: "start_process".to_string(),
+///     task: Task::Save,
+///     start: Some(90),
 ///     end: Some(100),
-///     primes: Some(vec![1, 0, 1, 1]),
+///     primes: Some(vec![97]),
+///     ..Default::default()
 /// };
 /// ```
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Request {
-    pub task: String,
-    pub end: Option<u32>,
-    pub primes: Option<Vec<u32>>,
+    #[serde(default = "protocol_version")]
+    pub version: u8,
+    pub task: Task,
+    pub start: Option<u64>,
+    pub end: Option<u64>,
+    pub primes: Option<Vec<u64>>,
+    pub secret: Option<String>,
+    pub max_payload: Option<u32>,
+    pub epoch: Option<u64>,
+    pub offset: Option<u64>,
+    pub limit: Option<u64>,
+    pub sum: Option<u128>,
+    pub count: Option<u64>,
+}
+
+impl Default for Request {
+    fn default() -> Request {
+        Request {
+            version: PROTOCOL_VERSION,
+            task: Task::default(),
+            start: None,
+            end: None,
+            primes: None,
+            secret: None,
+            max_payload: None,
+            epoch: None,
+            offset: None,
+            limit: None,
+            sum: None,
+            count: None,
+        }
+    }
 }
 
 impl Request {
@@ -113,18 +389,22 @@ impl Request {
     ///
     /// A `String` representing the serialized `Request` object in JSON format.
     ///
+    /// # Errors
+    ///
+    /// Returns a `serde_json::Error` if serialization fails, so callers can log and
+    /// handle it instead of sending an empty object.
+    ///
     /// # Example
     ///
     /// ```
     /// let request = Request {
-    ///     task: "start_process".to_string(),
-    ///     end: None,
-    ///     primes: None,
+    ///     task: Task::Start,
+    ///     ..Default::default()
     /// };
-    /// let json = request.to_json();
+    /// let json = request.to_json().unwrap();
     /// ```
-    pub fn to_json(&self) -> String {
-        serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string())
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        to_json_string(self)
     }
 
     /// Converts a JSON string into a `Request` struct.
@@ -141,10 +421,139 @@ impl Request {
     /// # Example
     ///
     /// ```
-    /// let json = r#"{"task":"start_process", "end":100, "primes":null}"#;
+    /// let json = r#"{"task":"save", "start":90, "end":100, "primes":[97]}"#;
     /// let request = Request::from_json(json);
     /// ```
     pub fn from_json(json: &str) -> Option<Request> {
-        serde_json::from_str(json).ok()
+        Request::try_from_json(json).ok()
+    }
+
+    /// Converts a JSON string into a `Request` struct, keeping the parse error.
+    ///
+    /// # Arguments
+    ///
+    /// * `json` - A JSON string to be deserialized into a `Request` object.
+    ///
+    /// # Errors
+    ///
+    /// Returns the `serde_json::Error` describing why and where parsing failed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let error = Request::try_from_json(r#"{"task":"start""#).unwrap_err();
+    /// assert!(error.is_eof());
+    /// ```
+    pub fn try_from_json(json: &str) -> serde_json::Result<Request> {
+        serde_json::from_str(json)
+    }
+
+    /// Converts the `Request` struct into bincode bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CodecError` if serialization fails.
+    #[cfg(feature = "binary")]
+    pub fn to_bytes(&self) -> Result<Vec<u8>, CodecError> {
+        WireFormat::Bincode.encode(self)
+    }
+
+    /// Converts bincode bytes into a `Request` struct.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The bytes written by `to_bytes`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CodecError` if the bytes are not a valid `Request`.
+    #[cfg(feature = "binary")]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Request, CodecError> {
+        WireFormat::Bincode.decode(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::ser::{Error, Serializer};
+
+    /// A message whose serialization always fails.
+    struct Unserializable;
+
+    impl Serialize for Unserializable {
+        fn serialize<S: Serializer>(&self, _serializer: S) -> Result<S::Ok, S::Error> {
+            Err(S::Error::custom("unserializable"))
+        }
+    }
+
+    /// Tests that serialization returns the real JSON and surfaces failures.
+    #[test]
+    fn test_to_json_surfaces_errors() {
+        let request = Request {
+            task: Task::Start,
+            max_payload: Some(1_400),
+            ..Default::default()
+        };
+        let json = request.to_json().unwrap();
+        assert!(json.contains(r#""task":"start""#));
+        assert_eq!(Request::from_json(&json).unwrap().task, Task::Start);
+        assert!(json.contains(r#""max_payload":1400"#));
+        assert_eq!(Request::from_json(&json).unwrap().max_payload, Some(1_400));
+
+        let error = to_json_string(&Unserializable).unwrap_err();
+        assert!(error.to_string().contains("unserializable"));
+        assert!(Response::from_json(SERIALIZATION_FAILED_JSON).is_some());
+    }
+
+    /// Tests that parse errors are reported with their cause and position.
+    #[test]
+    fn test_try_from_json_reports_errors() {
+        let error = Request::try_from_json(r#"{"task":"save","start":"ninety"}"#).unwrap_err();
+        assert!(error.is_data());
+        assert!(error
+            .to_string()
+            .contains("invalid type: string \"ninety\""));
+        assert_eq!(error.column(), 31);
+
+        let error = Response::try_from_json(r#"{"task":"range","status":"#).unwrap_err();
+        assert!(error.is_eof());
+        assert!(Response::from_json(r#"{"task":"range","status":"#).is_none());
+    }
+
+    /// Tests that task names round-trip and unknown tasks map to `Unknown`.
+    #[test]
+    fn test_task_names() {
+        let response = Response {
+            task: Task::QueryRange,
+            ..Default::default()
+        };
+        assert!(response
+            .to_json()
+            .unwrap()
+            .contains(r#""task":"query_range""#));
+
+        let request = Request::from_json(r#"{"task":"is_prime","start":7}"#).unwrap();
+        assert_eq!(request.task, Task::IsPrime);
+
+        let request = Request::from_json(r#"{"task":"launch_missiles"}"#).unwrap();
+        assert_eq!(request.task, Task::Unknown);
+    }
+
+    /// Tests that messages carry the protocol version, and that messages without one
+    /// are taken as the current version.
+    #[test]
+    fn test_protocol_version() {
+        let json = Request::default().to_json().unwrap();
+        assert!(json.contains(&format!(r#""version":{}"#, PROTOCOL_VERSION)));
+
+        let request = Request::from_json(r#"{"task":"start"}"#).unwrap();
+        assert_eq!(request.version, PROTOCOL_VERSION);
+        let request = Request::from_json(r#"{"version":200,"task":"start"}"#).unwrap();
+        assert_eq!(request.version, 200);
+        assert_eq!(
+            Response::from_json(KEEPALIVE_JSON).unwrap().version,
+            PROTOCOL_VERSION
+        );
     }
 }
@@ -0,0 +1,57 @@
+use log::{LevelFilter, Log, Metadata, Record};
+use std::sync::Once;
+
+/// Writes every enabled record to stderr, one line each, leaving stdout to the caller.
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("{}", record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+/// Guards the installation of `LOGGER`.
+static INSTALL: Once = Once::new();
+
+/// Maps a `verbose` level to the most detailed records logged.
+///
+/// `0` keeps warnings and errors, `1` adds progress messages, `2` adds the
+/// details of every request and response, and `3` or more adds keepalives.
+///
+/// # Arguments
+///
+/// * `verbose` - The verbosity level passed from Python.
+pub fn level_filter(verbose: u8) -> LevelFilter {
+    match verbose {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// Installs the stderr logger, once per process, and sets the level from `verbose`.
+///
+/// A logger installed beforehand by the embedding application is kept, so records
+/// can be captured or redirected; only the level is set then. The level is global:
+/// the last server or client started decides it.
+///
+/// # Arguments
+///
+/// * `verbose` - The verbosity level passed from Python.
+pub fn init(verbose: u8) {
+    INSTALL.call_once(|| {
+        let _ = log::set_logger(&LOGGER);
+    });
+    log::set_max_level(level_filter(verbose));
+}
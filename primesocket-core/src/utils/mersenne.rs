@@ -0,0 +1,71 @@
+/// The largest exponent `lucas_lehmer` can test with native arithmetic.
+///
+/// For `p <= 63`, `2^p - 1` fits in a `u64` and squares fit in a `u128`.
+pub const MAX_EXPONENT: u32 = 63;
+
+/// Tests whether the Mersenne number `2^p - 1` is prime using Lucas–Lehmer.
+///
+/// The sequence `s(0) = 4`, `s(k + 1) = s(k)^2 - 2 (mod 2^p - 1)` reaches zero
+/// after `p - 2` steps exactly when `2^p - 1` is prime (for odd prime `p`).
+///
+/// # Arguments
+///
+/// * `p` - The exponent of the Mersenne number.
+///
+/// # Returns
+///
+/// `Some(true)` if `2^p - 1` is prime, `Some(false)` if it is not, or `None` if `p`
+/// exceeds `MAX_EXPONENT`.
+///
+/// # Example
+///
+/// ```
+/// assert_eq!(lucas_lehmer(7), Some(true)); // 127
+/// assert_eq!(lucas_lehmer(11), Some(false)); // 2047 = 23 * 89
+/// ```
+pub fn lucas_lehmer(p: u32) -> Option<bool> {
+    if p > MAX_EXPONENT {
+        return None;
+    }
+    if p < 2 {
+        return Some(false);
+    }
+    if p == 2 {
+        return Some(true);
+    }
+    // 2^p - 1 can only be prime if p itself is prime.
+    if (2..p)
+        .take_while(|d| d * d <= p)
+        .any(|d| p.is_multiple_of(d))
+    {
+        return Some(false);
+    }
+
+    let modulus = (1u128 << p) - 1;
+    let mut s: u128 = 4;
+    for _ in 0..p - 2 {
+        s = (s * s + modulus - 2) % modulus;
+    }
+    Some(s == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that the known Mersenne prime exponents below 32 are identified.
+    #[test]
+    fn test_lucas_lehmer_known_exponents() {
+        let exponents: Vec<u32> = (0..32).filter(|&p| lucas_lehmer(p) == Some(true)).collect();
+
+        assert_eq!(exponents, vec![2, 3, 5, 7, 13, 17, 19, 31]);
+    }
+
+    /// Tests the largest supported exponents and the unsupported range.
+    #[test]
+    fn test_lucas_lehmer_bounds() {
+        assert_eq!(lucas_lehmer(61), Some(true));
+        assert_eq!(lucas_lehmer(59), Some(false));
+        assert_eq!(lucas_lehmer(MAX_EXPONENT + 1), None);
+    }
+}
@@ -1,2 +1,11 @@
+pub mod bloom;
+pub mod checksum;
+pub mod chunk;
+pub mod codec;
 pub mod json;
+pub mod logging;
+#[cfg(feature = "mersenne")]
+pub mod mersenne;
+pub mod primality;
 pub mod sieve;
+pub mod varint;
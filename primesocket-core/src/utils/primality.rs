@@ -0,0 +1,289 @@
+use pyo3::prelude::*;
+
+/// Witnesses making Miller-Rabin deterministic for every `u64`.
+const WITNESSES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+/// Computes `(a * b) % m` without overflowing.
+fn mul_mod(a: u64, b: u64, m: u64) -> u64 {
+    ((a as u128 * b as u128) % m as u128) as u64
+}
+
+/// Computes `(base ^ exp) % m` by repeated squaring.
+fn pow_mod(mut base: u64, mut exp: u64, m: u64) -> u64 {
+    let mut result = 1;
+    base %= m;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = mul_mod(result, base, m);
+        }
+        base = mul_mod(base, base, m);
+        exp >>= 1;
+    }
+    result
+}
+
+/// Computes the greatest common divisor of two numbers.
+///
+/// # Arguments
+///
+/// * `a` - The first number.
+/// * `b` - The second number.
+///
+/// # Returns
+///
+/// The greatest common divisor of `a` and `b`, or `0` if both are `0`.
+pub fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Returns an upper bound on the number of primes in `[start, end]`.
+///
+/// Uses the Montgomery–Vaughan form of the Brun–Titchmarsh inequality,
+/// `π(x + y) - π(x) < 2y / ln y`, together with the trivial bounds given by the
+/// length of the range and its odd numbers.
+///
+/// # Arguments
+///
+/// * `start` - The start of the range (inclusive).
+/// * `end` - The end of the range (inclusive).
+///
+/// # Returns
+///
+/// A number no smaller than the count of primes in the range.
+pub fn max_primes_in_range(start: u64, end: u64) -> u64 {
+    if end < start {
+        return 0;
+    }
+    let length = end - start + 1;
+    let odd_bound = length / 2 + 2;
+    if length < 3 {
+        return length.min(odd_bound);
+    }
+    let brun_titchmarsh = (2.0 * length as f64 / (length as f64).ln()).ceil() as u64;
+    length.min(odd_bound).min(brun_titchmarsh)
+}
+
+/// Tests whether a number is prime.
+///
+/// Uses a deterministic Miller-Rabin test: checking the first twelve primes as
+/// witnesses is enough for every 64-bit number, so no sieve is needed.
+///
+/// # Arguments
+///
+/// * `n` - The number to test.
+///
+/// # Returns
+///
+/// `true` if `n` is prime, `false` otherwise.
+///
+/// # Example (Python)
+///
+/// ```python
+/// import primesocket_core
+/// primesocket_core.is_prime(97)  # True
+/// ```
+#[pyfunction]
+pub fn is_prime(n: u64) -> bool {
+    if n < 2 {
+        return false;
+    }
+    for &p in &WITNESSES {
+        if n.is_multiple_of(p) {
+            return n == p;
+        }
+    }
+
+    // Write n - 1 as d * 2^s with d odd.
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+
+    'witness: for &a in &WITNESSES {
+        let mut x = pow_mod(a, d, n);
+        if x == 1 || x == n - 1 {
+            continue;
+        }
+        for _ in 1..s {
+            x = mul_mod(x, x, n);
+            if x == n - 1 {
+                continue 'witness;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+/// Finds the smallest prime strictly greater than `n`.
+///
+/// # Arguments
+///
+/// * `n` - The number to search above.
+///
+/// # Returns
+///
+/// `Some(prime)` with the next prime, or `None` if no prime above `n` fits in a `u64`.
+///
+/// # Example (Python)
+///
+/// ```python
+/// import primesocket_core
+/// primesocket_core.next_prime(13)  # 17
+/// ```
+#[pyfunction]
+pub fn next_prime(n: u64) -> Option<u64> {
+    (n.checked_add(1)?..=u64::MAX).find(|&candidate| is_prime(candidate))
+}
+
+/// Finds the largest prime strictly smaller than `n`.
+///
+/// # Arguments
+///
+/// * `n` - The number to search below.
+///
+/// # Returns
+///
+/// `Some(prime)` with the previous prime, or `None` if there is none (`n <= 2`).
+///
+/// # Example (Python)
+///
+/// ```python
+/// import primesocket_core
+/// primesocket_core.prev_prime(13)  # 11
+/// primesocket_core.prev_prime(2)   # None
+/// ```
+#[pyfunction]
+pub fn prev_prime(n: u64) -> Option<u64> {
+    (2..n).rev().find(|&candidate| is_prime(candidate))
+}
+
+/// How many unreported candidates `verify_primes` checks for missed primes.
+const VERIFY_SAMPLE_SIZE: u64 = 64;
+
+/// Checks a list of primes found in `[start, end]` with Miller-Rabin.
+///
+/// Every reported number must be prime, within the range (and the residue
+/// class, if any) and strictly ascending. Missed primes are caught on a sample:
+/// up to `VERIFY_SAMPLE_SIZE` evenly spaced candidates that were not reported
+/// must be composite.
+///
+/// # Arguments
+///
+/// * `start` - The start of the range (inclusive).
+/// * `end` - The end of the range (inclusive).
+/// * `primes` - The primes reported for the range.
+/// * `residue` - The residue class `(a, m)` the primes are restricted to, if any.
+///
+/// # Returns
+///
+/// `true` if no error was found.
+pub fn verify_primes(start: u64, end: u64, primes: &[u64], residue: Option<(u64, u64)>) -> bool {
+    let in_class = |n: u64| residue.is_none_or(|(a, m)| n % m == a % m);
+    let reported_ok = primes.windows(2).all(|pair| pair[0] < pair[1])
+        && primes
+            .iter()
+            .all(|&p| (start..=end).contains(&p) && in_class(p) && is_prime(p));
+    if !reported_ok || start > end {
+        return reported_ok;
+    }
+
+    let stride = ((end - start) / VERIFY_SAMPLE_SIZE).max(1);
+    (start..=end)
+        .step_by(stride as usize)
+        .filter(|&n| in_class(n) && primes.binary_search(&n).is_err())
+        .all(|n| !is_prime(n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that wrong, missing or unordered primes fail verification.
+    #[test]
+    fn test_verify_primes() {
+        let primes = [101, 103, 107, 109, 113];
+        assert!(verify_primes(100, 120, &primes, None));
+        assert!(verify_primes(100, 120, &[101, 109, 113], Some((1, 4))));
+
+        assert!(!verify_primes(100, 120, &[101, 105, 107], None));
+        assert!(!verify_primes(100, 120, &[103, 101], None));
+        assert!(!verify_primes(100, 120, &[97, 101], None));
+        assert!(!verify_primes(100, 120, &[101, 103], None));
+        assert!(!verify_primes(100, 120, &primes, Some((1, 4))));
+    }
+
+    /// Tests small primes and the edge cases 0 and 1.
+    #[test]
+    fn test_is_prime_small_numbers() {
+        let primes: Vec<u64> = (0..50).filter(|&n| is_prime(n)).collect();
+
+        assert_eq!(
+            primes,
+            vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47]
+        );
+        assert!(!is_prime(0));
+        assert!(!is_prime(1));
+    }
+
+    /// Tests composites that fool weaker primality tests.
+    #[test]
+    fn test_is_prime_composites() {
+        // Carmichael numbers and strong pseudoprimes to small bases.
+        for n in [561, 1_105, 2_047, 3_215_031_751, 3_825_123_056_546_413_051] {
+            assert!(!is_prime(n), "{} is composite", n);
+        }
+        assert!(!is_prime(4_294_967_297)); // 641 * 6700417
+    }
+
+    /// Tests large 64-bit primes.
+    #[test]
+    fn test_is_prime_large_primes() {
+        assert!(is_prime(4_294_967_291)); // Largest 32-bit prime.
+        assert!(is_prime(1_000_000_007));
+        assert!(is_prime(2_305_843_009_213_693_951)); // 2^61 - 1
+        assert!(is_prime(18_446_744_073_709_551_557)); // Largest 64-bit prime.
+        assert!(!is_prime(u64::MAX));
+    }
+
+    /// Tests searching for the nearest primes around a number.
+    #[test]
+    fn test_next_and_prev_prime() {
+        assert_eq!(next_prime(13), Some(17));
+        assert_eq!(prev_prime(13), Some(11));
+        assert_eq!(next_prime(1_000_000_000), Some(1_000_000_007));
+    }
+
+    /// Tests the edge cases around 2 and the top of the `u64` range.
+    #[test]
+    fn test_next_and_prev_prime_edges() {
+        assert_eq!(next_prime(0), Some(2));
+        assert_eq!(next_prime(1), Some(2));
+        assert_eq!(next_prime(2), Some(3));
+        assert_eq!(prev_prime(3), Some(2));
+        assert_eq!(prev_prime(2), None);
+        assert_eq!(prev_prime(0), None);
+        assert_eq!(next_prime(18_446_744_073_709_551_557), None);
+        assert_eq!(prev_prime(u64::MAX), Some(18_446_744_073_709_551_557));
+    }
+
+    /// Tests that the bound on primes in a range is never below the actual count.
+    #[test]
+    fn test_max_primes_in_range() {
+        let count = |start: u64, end: u64| (start..=end).filter(|&n| is_prime(n)).count() as u64;
+        for (start, end) in [
+            (0, 0),
+            (2, 2),
+            (2, 3),
+            (0, 10),
+            (2, 100),
+            (1_000, 1_100),
+            (2, 10_000),
+        ] {
+            assert!(max_primes_in_range(start, end) >= count(start, end));
+        }
+        assert_eq!(max_primes_in_range(10, 5), 0);
+        assert!(max_primes_in_range(1_000_000, 1_001_000) < 300);
+    }
+}
@@ -1,10 +1,18 @@
-use std::cmp::max;
+use pyo3::prelude::*;
+use std::cmp::{max, min};
+
+/// The default number of candidates sieved at once, sized to stay in L2 cache.
+pub const DEFAULT_BLOCK_SIZE: usize = 256 * 1024;
+
+/// How many numbers `primes_below` hands to each `sieve_segment` call.
+const PRIMES_BELOW_SEGMENT: u64 = 1 << 22;
 
 /// Performs a segmented sieve to find prime numbers in a given range.
 ///
 /// This function takes a starting number, an ending number, and a list of
 /// known primes and returns a vector containing the prime numbers within
-/// the range `[start, end]`. It uses a boolean vector to mark non-prime numbers.
+/// the range `[start, end]`. It marks non-prime odd numbers in a bitset, processing
+/// the range in blocks of `DEFAULT_BLOCK_SIZE` numbers.
 ///
 /// # Arguments
 ///
@@ -14,7 +22,8 @@ use std::cmp::max;
 ///
 /// # Returns
 ///
-/// A `Vec<u32>` containing the prime numbers in the given range.
+/// A `Vec<u64>` containing the prime numbers in the given range, empty when
+/// `start > end`.
 ///
 /// # Example
 ///
@@ -23,35 +32,401 @@ use std::cmp::max;
 /// let result = sieve_segment(10, 30, primes);
 /// assert_eq!(result, vec![11, 13, 17, 19, 23, 29]);
 /// ```
-pub fn sieve_segment(start: u32, end: u32, primes: Vec<u32>) -> Vec<u32> {
-    let size = (end - start + 1) as usize;
-    let mut is_prime = vec![1; size];
+pub fn sieve_segment(start: u64, end: u64, primes: Vec<u64>) -> Vec<u64> {
+    sieve_segment_blocked(start, end, primes, DEFAULT_BLOCK_SIZE)
+}
+
+/// Performs a segmented sieve over `[start, end]` in cache-friendly blocks.
+///
+/// Instead of marking one buffer spanning the whole range, the range is split
+/// into windows of `block_size` candidates that are sieved one after the other,
+/// reusing the same buffer. The result is identical to a single-buffer sieve.
+///
+/// # Arguments
+///
+/// * `start` - The starting number of the range (inclusive).
+/// * `end` - The ending number of the range (inclusive).
+/// * `primes` - A vector of prime numbers used to mark non-primes in the range.
+/// * `block_size` - The number of candidates sieved at once (at least `1`).
+///
+/// # Returns
+///
+/// A `Vec<u64>` containing the prime numbers in the given range.
+pub fn sieve_segment_blocked(
+    start: u64,
+    end: u64,
+    primes: Vec<u64>,
+    block_size: usize,
+) -> Vec<u64> {
+    let mut result = Vec::new();
+    for_each_prime_blocked(start, end, &primes, block_size, |prime| result.push(prime));
+    result
+}
+
+/// Performs a segmented sieve over `[start, end]`, sieving blocks concurrently.
+///
+/// The range is split into blocks of `DEFAULT_BLOCK_SIZE` candidates that are
+/// sieved on the rayon thread pool, then concatenated in order, so the result
+/// is identical to `sieve_segment`. Only worth it for segments spanning many blocks.
+///
+/// # Arguments
+///
+/// * `start` - The starting number of the range (inclusive).
+/// * `end` - The ending number of the range (inclusive).
+/// * `primes` - A vector of prime numbers used to mark non-primes in the range.
+///
+/// # Returns
+///
+/// A `Vec<u64>` containing the prime numbers in the given range.
+#[cfg(feature = "parallel")]
+pub fn sieve_segment_parallel(start: u64, end: u64, primes: Vec<u64>) -> Vec<u64> {
+    use rayon::prelude::*;
+
+    let block = DEFAULT_BLOCK_SIZE as u64;
+    let blocks: Vec<(u64, u64)> = (0..=(end.saturating_sub(start) / block))
+        .map(|i| start + i * block)
+        .filter(|&low| low <= end)
+        .map(|low| (low, min(end, low.saturating_add(block - 1))))
+        .collect();
+    blocks
+        .into_par_iter()
+        .map(|(low, high)| {
+            let mut result = Vec::new();
+            for_each_prime_blocked(low, high, &primes, DEFAULT_BLOCK_SIZE, |prime| {
+                result.push(prime)
+            });
+            result
+        })
+        .collect::<Vec<Vec<u64>>>()
+        .concat()
+}
+
+/// Counts the primes in `[start, end]` without collecting them.
+///
+/// This gives the same count as `sieve_segment(start, end, primes).len()`, but
+/// never allocates the list of primes, only the sieve buffer.
+///
+/// # Arguments
+///
+/// * `start` - The starting number of the range (inclusive).
+/// * `end` - The ending number of the range (inclusive).
+/// * `primes` - A vector of prime numbers used to mark non-primes in the range.
+///
+/// # Returns
+///
+/// The number of primes in the given range.
+///
+/// # Example
+///
+/// ```
+/// assert_eq!(sieve_segment_count(10, 30, vec![2, 3, 5, 7]), 6);
+/// ```
+pub fn sieve_segment_count(start: u64, end: u64, primes: Vec<u64>) -> u64 {
+    let mut count = 0;
+    for_each_prime_blocked(start, end, &primes, DEFAULT_BLOCK_SIZE, |_| count += 1);
+    count
+}
+
+/// Sieves `[start, end]` in blocks of `block_size` numbers, calling `on_prime`
+/// for each prime found, in ascending order.
+///
+/// 2 is reported on its own and only odd candidates are sieved, so a block holds
+/// half as many candidates and even multiples are never marked. Composites are
+/// marked in a bitset, one bit per odd candidate: bit `i` of a block starting at
+/// `low` stands for `low + 2 * i`. Numbers below 2 are never reported.
+fn for_each_prime_blocked<F>(
+    start: u64,
+    end: u64,
+    primes: &[u64],
+    block_size: usize,
+    mut on_prime: F,
+) where
+    F: FnMut(u64),
+{
+    // An empty range; `end - start` would underflow below.
+    if start > end {
+        return;
+    }
+    if start <= 2 && 2 <= end {
+        on_prime(2);
+    }
+    let first_odd = max(start, 3) | 1;
+    if first_odd > end {
+        return;
+    }
+    let count = (end - first_odd) / 2 + 1;
+    let block = min((block_size as u64 / 2).max(1), count);
+    let mut composite = vec![0u64; block.div_ceil(64) as usize];
+
+    let mut index = 0;
+    loop {
+        let low = first_odd + 2 * index;
+        let bits = min(block, count - index);
+        let high = low + 2 * (bits - 1);
+        let words = bits.div_ceil(64) as usize;
+        composite[..words].fill(0);
+
+        // Squares of primes above 2^32, and multiples near `u64::MAX`, don't fit in a `u64`.
+        for &prime in primes {
+            let square = prime as u128 * prime as u128;
+            if square > high as u128 {
+                break;
+            }
+            if prime == 2 {
+                continue;
+            }
+
+            let first = first_multiple_in_range(prime, low);
+            if first > high as u128 {
+                continue;
+            }
+            for j in (first as u64..=high).step_by(2 * prime as usize) {
+                let offset = ((j - low) / 2) as usize;
+                composite[offset / 64] |= 1 << (offset % 64);
+            }
+        }
+
+        for (word_index, &word) in composite[..words].iter().enumerate() {
+            let mut candidates = !word;
+            if word_index == words - 1 && !bits.is_multiple_of(64) {
+                candidates &= (1 << (bits % 64)) - 1;
+            }
+            while candidates != 0 {
+                let bit = candidates.trailing_zeros() as u64;
+                on_prime(low + 2 * (word_index as u64 * 64 + bit));
+                candidates &= candidates - 1;
+            }
+        }
+
+        index += bits;
+        if index == count {
+            break;
+        }
+    }
+}
+
+/// Returns the first multiple of the odd `prime` to mark in a range starting at `start`.
+///
+/// That is the smallest odd multiple at least `start` and at least `prime * prime`:
+/// smaller multiples also have a smaller prime factor, so they are marked by it, and
+/// `prime` itself, when it lies in the range, is never marked. A `start` that is a
+/// multiple of `prime` is its own first multiple, if odd and not below the square.
+///
+/// # Arguments
+///
+/// * `prime` - An odd prime.
+/// * `start` - The lowest number of the range.
+///
+/// # Returns
+///
+/// The first multiple, as a `u128` since it may not fit in a `u64`.
+fn first_multiple_in_range(prime: u64, start: u64) -> u128 {
+    let prime = prime as u128;
+    let mut multiple = (start as u128).div_ceil(prime) * prime;
+    if multiple.is_multiple_of(2) {
+        multiple += prime;
+    }
+    max(prime * prime, multiple)
+}
+
+/// Finds every prime up to `limit` with a plain sieve of Eratosthenes.
+///
+/// # Arguments
+///
+/// * `limit` - The largest number considered (inclusive).
+///
+/// # Returns
+///
+/// A `Vec<u64>` containing the primes in `[2, limit]`.
+pub fn primes_up_to(limit: u64) -> Vec<u64> {
+    let limit = limit as usize;
+    let mut is_prime = vec![true; limit + 1];
+    let mut result = Vec::new();
+    for n in 2..=limit {
+        if is_prime[n] {
+            result.push(n as u64);
+            for multiple in (n * n..=limit).step_by(n) {
+                is_prime[multiple] = false;
+            }
+        }
+    }
+    result
+}
+
+/// Finds every prime below `n` in-process, without any networking.
+///
+/// This is the single-process path for jobs that don't need distribution: the range
+/// `[2, n)` is split into segments of `PRIMES_BELOW_SEGMENT` numbers, each sieved with
+/// `sieve_segment` using the base primes up to the square root of its end, like a
+/// client would sieve its assigned ranges.
+///
+/// # Arguments
+///
+/// * `n` - The bound of the primes (exclusive).
+///
+/// # Returns
+///
+/// A `Vec<u64>` containing the primes in `[2, n)`, in ascending order.
+///
+/// # Example (Python)
+///
+/// ```python
+/// import primesocket_core
+/// primesocket_core.primes_below(20)  # [2, 3, 5, 7, 11, 13, 17, 19]
+/// ```
+#[pyfunction]
+pub fn primes_below(n: u64) -> Vec<u64> {
+    if n <= 2 {
+        return Vec::new();
+    }
+    let last = n - 1;
+    let base_primes = primes_up_to(last.isqrt());
+    let mut result = Vec::new();
+    let mut low = 2u64;
+    loop {
+        let high = low.saturating_add(PRIMES_BELOW_SEGMENT - 1).min(last);
+        let needed = base_primes.partition_point(|&p| p * p <= high);
+        result.extend(sieve_segment(low, high, base_primes[..needed].to_vec()));
+        if high == last {
+            return result;
+        }
+        low = high + 1;
+    }
+}
+
+/// Finds the primes in `[start, end]` congruent to `a` modulo `m`.
+///
+/// Only the candidates of the residue class are sieved: for each base prime `p`
+/// not dividing `m`, the first candidate divisible by `p` is found through the
+/// inverse of `m` modulo `p`, then every `p`-th candidate is marked. Primes
+/// dividing `m` never divide a candidate when `gcd(a, m) == 1`, so they are skipped.
+///
+/// # Arguments
+///
+/// * `start` - The starting number of the range (inclusive).
+/// * `end` - The ending number of the range (inclusive).
+/// * `primes` - The base primes, covering at least `sqrt(end)`.
+/// * `a` - The residue of the class.
+/// * `m` - The modulus of the class (at least `1`).
+///
+/// # Returns
+///
+/// A `Vec<u64>` containing the primes `p` of the range with `p % m == a % m`.
+///
+/// # Example
+///
+/// ```
+/// let result = sieve_residue_class(10, 60, vec![2, 3, 5, 7], 1, 4);
+/// assert_eq!(result, vec![13, 17, 29, 37, 41, 53]);
+/// ```
+pub fn sieve_residue_class(start: u64, end: u64, primes: Vec<u64>, a: u64, m: u64) -> Vec<u64> {
+    let m = max(m, 1);
+    let a = a % m;
+    // The first candidate may lie beyond `u64::MAX` when `m` is huge.
+    let first = start as u128 + ((a as u128 + m as u128 - (start % m) as u128) % m as u128);
+    if first > end as u128 {
+        return Vec::new();
+    }
+    let first = first as u64;
+    let count = ((end - first) / m + 1) as usize;
+    let mut is_prime = vec![true; count];
 
     for &prime in &primes {
-        if prime * prime > end {
+        let square = prime as u128 * prime as u128;
+        if square > end as u128 {
             break;
         }
+        if m.is_multiple_of(prime) {
+            continue;
+        }
 
-        let mut mul = max(prime * prime, (start + prime - 1) / prime * prime);
-        if mul == prime {
-            mul += prime;
+        // Index of the first candidate divisible by `prime`.
+        let mut k = (prime - first % prime) % prime * mod_inverse(m % prime, prime) % prime;
+        // Skip `prime` itself and multiples already marked by smaller primes.
+        if (first as u128 + k as u128 * m as u128) < square {
+            let needed = (square - first as u128).div_ceil(m as u128) as u64;
+            k += (needed - k).div_ceil(prime) * prime;
         }
 
-        let prime = prime as usize;
-        for j in (mul..=end).step_by(prime) {
-            is_prime[(j - start) as usize] = 0;
+        for index in (k as usize..count).step_by(prime as usize) {
+            is_prime[index] = false;
         }
     }
 
-    (start..=end)
-        .filter(|&i| is_prime[(i - start) as usize] == 1)
-        .collect::<Vec<u32>>()
+    (0..count)
+        .filter(|&index| is_prime[index])
+        .map(|index| first + index as u64 * m)
+        .filter(|&n| n >= 2)
+        .collect()
+}
+
+/// Returns the inverse of `a` modulo the prime `p`, through the extended Euclidean algorithm.
+fn mod_inverse(a: u64, p: u64) -> u64 {
+    let (mut old_r, mut r) = (a as i64, p as i64);
+    let (mut old_s, mut s) = (1i64, 0i64);
+    while r != 0 {
+        let quotient = old_r / r;
+        (old_r, r) = (r, old_r - quotient * r);
+        (old_s, s) = (s, old_s - quotient * s);
+    }
+    old_s.rem_euclid(p as i64) as u64
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Tests `primes_below` against the known primes below 100, excluding `n` itself,
+    /// on tiny bounds, and across segment boundaries.
+    #[test]
+    fn test_primes_below() {
+        assert_eq!(
+            primes_below(100),
+            vec![
+                2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79,
+                83, 89, 97
+            ]
+        );
+        assert_eq!(primes_below(97).last(), Some(&89));
+        assert!(primes_below(0).is_empty());
+        assert!(primes_below(2).is_empty());
+        assert_eq!(primes_below(3), vec![2]);
+
+        let n = 2 * PRIMES_BELOW_SEGMENT + 1_000;
+        assert_eq!(primes_below(n), primes_up_to(n - 1));
+    }
+
+    /// Tests the first multiple marked for a prime, when `start` is a multiple of it,
+    /// lies below its square, is `0`, or is the prime itself.
+    #[test]
+    fn test_first_multiple_in_range() {
+        assert_eq!(first_multiple_in_range(3, 21), 21);
+        assert_eq!(first_multiple_in_range(3, 30), 33);
+        assert_eq!(first_multiple_in_range(7, 98), 105);
+        assert_eq!(first_multiple_in_range(7, 10), 49);
+        assert_eq!(first_multiple_in_range(7, 49), 49);
+        assert_eq!(first_multiple_in_range(7, 0), 49);
+        assert_eq!(first_multiple_in_range(3, 0), 9);
+        assert_eq!(first_multiple_in_range(11, 11), 121);
+        assert!(first_multiple_in_range(4_294_967_291, u64::MAX) > u64::MAX as u128);
+    }
+
+    /// Tests that base primes lying in the range are never culled, including when
+    /// the range starts at one of them or at a multiple of one.
+    #[test]
+    fn test_sieve_segment_keeps_base_primes() {
+        let base = primes_up_to(1_000);
+        for start in [0, 1, 2, 3, 7, 9, 21, 30, 49, 97, 121] {
+            let expected: Vec<u64> = base.iter().copied().filter(|&p| p >= start).collect();
+            assert_eq!(
+                sieve_segment(start, 1_000, base.clone()),
+                expected,
+                "start {}",
+                start
+            );
+        }
+    }
+
     /// Test the sieve_segment function with a known range and small primes.
     #[test]
     fn test_sieve_segment() {
@@ -70,6 +445,25 @@ mod tests {
         assert_eq!(result, vec![5, 7]);
     }
 
+    /// Tests sieve_segment on degenerate ranges: a single number, a reversed range
+    /// and ranges starting at 0.
+    #[test]
+    fn test_sieve_segment_edge_ranges() {
+        let primes = vec![2, 3, 5, 7];
+
+        assert_eq!(sieve_segment(29, 29, primes.clone()), vec![29]);
+        assert_eq!(sieve_segment(30, 30, primes.clone()), Vec::<u64>::new());
+        assert_eq!(sieve_segment(30, 29, primes.clone()), Vec::<u64>::new());
+        assert_eq!(
+            sieve_segment(u64::MAX, 0, primes.clone()),
+            Vec::<u64>::new()
+        );
+        assert_eq!(sieve_segment_count(30, 29, primes.clone()), 0);
+        assert_eq!(sieve_segment(0, 0, primes.clone()), Vec::<u64>::new());
+        assert_eq!(sieve_segment(0, 1, primes.clone()), Vec::<u64>::new());
+        assert_eq!(sieve_segment(0, 10, primes), vec![2, 3, 5, 7]);
+    }
+
     /// Test sieve_segment with a small range containing only one prime.
     #[test]
     fn test_sieve_segment_single_prime() {
@@ -82,9 +476,209 @@ mod tests {
     /// Test sieve_segment with a range where the primes are already in the list.
     #[test]
     fn test_sieve_segment_with_known_primes() {
-        let primes = vec![2, 3, 5];
-        let result = sieve_segment(1, 50, primes);
+        let primes = vec![2, 3, 5, 7];
+        let result = sieve_segment(2, 50, primes);
 
-        assert_eq!(result, vec![11, 13, 17, 19]);
+        assert_eq!(
+            result,
+            vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47]
+        );
+    }
+
+    /// Test that the blocked sieve gives the same output for any block size.
+    #[test]
+    fn test_sieve_segment_blocked_equivalence() {
+        let primes = sieve_segment(2, 1_000, vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31]);
+        let expected = sieve_segment_blocked(2, 100_000, primes.clone(), usize::MAX);
+
+        for block_size in [1, 7, 64, 1_000, 4_096, 99_999, DEFAULT_BLOCK_SIZE] {
+            let result = sieve_segment_blocked(2, 100_000, primes.clone(), block_size);
+            assert_eq!(result, expected, "block size {}", block_size);
+        }
+        assert_eq!(expected.len(), 9_592);
+    }
+
+    /// Sieves every integer of `[start, end]`, with a byte per candidate, as a reference.
+    fn byte_sieve(start: u64, end: u64, primes: &[u64]) -> Vec<u64> {
+        let mut is_prime = vec![1u8; (end - start + 1) as usize];
+        for &prime in primes {
+            let square = prime as u128 * prime as u128;
+            if square > end as u128 {
+                break;
+            }
+            let first = max(
+                square,
+                (start as u128).div_ceil(prime as u128) * prime as u128,
+            );
+            for j in (first..=end as u128).step_by(prime as usize) {
+                is_prime[(j - start as u128) as usize] = 0;
+            }
+        }
+        (start..=end)
+            .filter(|&n| is_prime[(n - start) as usize] == 1)
+            .collect()
+    }
+
+    /// Tests that sieving odd candidates only gives the same primes as sieving every
+    /// integer, including when the range holds only 2 or only 3.
+    #[test]
+    fn test_sieve_segment_odd_only() {
+        let primes = primes_up_to(100);
+        for (start, end) in [(2, 10_000), (3, 3), (2, 2), (2, 3), (4, 4), (9_998, 10_000)] {
+            assert_eq!(
+                sieve_segment(start, end, primes.clone()),
+                byte_sieve(start, end, &primes),
+                "range [{}, {}]",
+                start,
+                end
+            );
+        }
+        assert_eq!(sieve_segment(3, 3, primes.clone()), vec![3]);
+        assert_eq!(sieve_segment(2, 10_000, primes).len(), 1_229);
+    }
+
+    /// Tests that the bitset sieve matches a sieve with a byte per candidate, across
+    /// block sizes that are and aren't multiples of a word, and at the edges of `u64`.
+    #[test]
+    fn test_sieve_segment_matches_byte_sieve() {
+        let primes = primes_up_to(70_000);
+
+        for (start, end) in [
+            (2, 2),
+            (2, 63),
+            (2, 64),
+            (2, 65),
+            (10, 30),
+            (24, 28),
+            (2, 100_000),
+            (999_937, 1_000_193),
+            (u32::MAX as u64 - 500, u32::MAX as u64 + 500),
+            (u64::MAX - 1_000, u64::MAX),
+        ] {
+            let expected = byte_sieve(start, end, &primes);
+            for block_size in [1, 2, 63, 64, 65, 128, 129, 1_000, DEFAULT_BLOCK_SIZE] {
+                assert_eq!(
+                    sieve_segment_blocked(start, end, primes.clone(), block_size),
+                    expected,
+                    "range [{}, {}], block size {}",
+                    start,
+                    end,
+                    block_size
+                );
+            }
+        }
+    }
+
+    /// Tests that ranges around and beyond `u32::MAX` are sieved without overflowing.
+    #[test]
+    fn test_sieve_segment_large_ranges() {
+        let primes = primes_up_to(80_000);
+        let by_primality = |start: u64, end: u64| -> Vec<u64> {
+            (start..=end)
+                .filter(|&n| crate::utils::primality::is_prime(n))
+                .collect()
+        };
+
+        for (start, end) in [
+            (1_000_000_000, 1_000_001_000),
+            (u32::MAX as u64 - 1_000, u32::MAX as u64 + 1_000),
+            (4_999_999_000, 5_000_000_000),
+        ] {
+            assert_eq!(
+                sieve_segment(start, end, primes.clone()),
+                by_primality(start, end)
+            );
+            assert_eq!(
+                sieve_segment_blocked(start, end, primes.clone(), 64),
+                by_primality(start, end)
+            );
+        }
+    }
+
+    /// Tests that counting matches the number of primes found, for several ranges.
+    #[test]
+    fn test_sieve_segment_count_matches_len() {
+        let primes = sieve_segment(2, 1_000, vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31]);
+
+        for (start, end) in [(2, 2), (4, 4), (10, 30), (2, 100_000), (999_000, 1_000_000)] {
+            assert_eq!(
+                sieve_segment_count(start, end, primes.clone()),
+                sieve_segment(start, end, primes.clone()).len() as u64,
+                "range [{}, {}]",
+                start,
+                end
+            );
+        }
+        assert_eq!(sieve_segment_count(2, 100_000, primes), 9_592);
+    }
+
+    /// Benchmark comparing a single buffer with cache-sized blocks on a large segment.
+    ///
+    /// Run with `cargo test --release -- --ignored --nocapture bench_sieve_blocks`.
+    #[test]
+    #[ignore]
+    fn bench_sieve_blocks() {
+        let primes = sieve_segment(2, 65_535, vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37]);
+        let (start, end) = (100_000_000, 150_000_000);
+
+        let started = std::time::Instant::now();
+        let single = sieve_segment_blocked(start, end, primes.clone(), usize::MAX);
+        let single_time = started.elapsed();
+
+        let started = std::time::Instant::now();
+        let blocked = sieve_segment_blocked(start, end, primes, DEFAULT_BLOCK_SIZE);
+        let blocked_time = started.elapsed();
+
+        assert_eq!(single, blocked);
+        println!(
+            "single buffer: {:?}, blocks of {}: {:?}",
+            single_time, DEFAULT_BLOCK_SIZE, blocked_time
+        );
+    }
+
+    /// Tests the plain sieve on small limits.
+    #[test]
+    fn test_primes_up_to() {
+        assert!(primes_up_to(1).is_empty());
+        assert_eq!(primes_up_to(2), vec![2]);
+        assert_eq!(primes_up_to(30), vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29]);
+    }
+
+    /// Tests that sieving a residue class matches filtering a full sieve.
+    #[test]
+    fn test_sieve_residue_class_matches_filtered_sieve() {
+        let base = sieve_segment(2, 200, vec![2, 3, 5, 7, 11, 13]);
+        for (a, m) in [(1, 4), (3, 4), (1, 1), (2, 3), (7, 30), (1, 6), (5, 12)] {
+            for (start, end) in [(2, 40_000), (1, 10), (3_001, 4_000), (9_999, 10_001)] {
+                let expected: Vec<u64> = sieve_segment(start.max(2), end, base.clone())
+                    .into_iter()
+                    .filter(|p| p % m == a % m)
+                    .collect();
+                assert_eq!(
+                    sieve_residue_class(start, end, base.clone(), a, m),
+                    expected,
+                    "class {} mod {} over [{}, {}]",
+                    a,
+                    m,
+                    start,
+                    end
+                );
+            }
+        }
+    }
+
+    /// Tests that the parallel sieve gives the same primes as the sequential one,
+    /// within a single block and across several.
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_sieve_segment_parallel_matches_sequential() {
+        for (start, end) in [(2, 100_000), (2, 2_000_000), (1_000_003, 1_700_000)] {
+            let primes = primes_up_to((end as f64).sqrt() as u64 + 1);
+            assert_eq!(
+                sieve_segment_parallel(start, end, primes.clone()),
+                sieve_segment(start, end, primes)
+            );
+        }
+        assert!(sieve_segment_parallel(30, 29, vec![2, 3, 5]).is_empty());
     }
 }
@@ -0,0 +1,143 @@
+/// Encodes a list of numbers as little-endian base-128 varints of their gaps.
+///
+/// Each value is stored as its difference from the previous one (the first from
+/// `0`), seven bits per byte, least significant group first; the high bit of a
+/// byte is set when more bytes follow. Prime lists are ascending with small
+/// gaps, so most values fit in one or two bytes. Gaps wrap around, so any list
+/// round-trips, but unsorted lists compress poorly.
+///
+/// # Arguments
+///
+/// * `values` - The numbers to encode, ideally in ascending order.
+///
+/// # Returns
+///
+/// A `Vec<u8>` containing the encoded gaps.
+///
+/// # Example
+///
+/// ```
+/// assert_eq!(write_varints(&[2, 3, 131]), vec![2, 1, 0x80, 0x01]);
+/// ```
+pub fn write_varints(values: &[u64]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(values.len() * 2);
+    let mut previous = 0u64;
+    for &value in values {
+        let mut gap = value.wrapping_sub(previous);
+        previous = value;
+        while gap >= 0x80 {
+            bytes.push((gap & 0x7F) as u8 | 0x80);
+            gap >>= 7;
+        }
+        bytes.push(gap as u8);
+    }
+    bytes
+}
+
+/// Decodes numbers written by `write_varints`.
+///
+/// # Arguments
+///
+/// * `bytes` - The encoded gaps.
+///
+/// # Returns
+///
+/// `Some(Vec<u64>)` with the decoded numbers, or `None` if the input ends in the
+/// middle of a varint or a varint doesn't fit in a `u64`.
+pub fn read_varints(bytes: &[u8]) -> Option<Vec<u64>> {
+    let mut values = Vec::with_capacity(bytes.len());
+    let mut previous = 0u64;
+    let mut gap = 0u64;
+    let mut shift = 0;
+    for &byte in bytes {
+        let group = (byte & 0x7F) as u64;
+        if shift == 63 && group > 0x01 {
+            return None;
+        }
+        gap |= group << shift;
+        if byte & 0x80 == 0 {
+            previous = previous.wrapping_add(gap);
+            values.push(previous);
+            gap = 0;
+            shift = 0;
+        } else {
+            shift += 7;
+            if shift > 63 {
+                return None;
+            }
+        }
+    }
+    (shift == 0).then_some(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Generates a pseudo-random ascending list with gaps up to `max_gap`.
+    fn ascending(seed: u64, len: usize, max_gap: u64) -> Vec<u64> {
+        let mut state = seed.max(1);
+        let mut value = 0u64;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                value = value.saturating_add(1 + (state % max_gap));
+                value
+            })
+            .collect()
+    }
+
+    /// Tests the encoding of edge cases: empty lists, gaps of 1 and large gaps.
+    #[test]
+    fn test_varints_edge_cases() {
+        assert!(write_varints(&[]).is_empty());
+        assert_eq!(read_varints(&[]), Some(vec![]));
+
+        let consecutive: Vec<u64> = (1..=300).collect();
+        let bytes = write_varints(&consecutive);
+        assert_eq!(bytes, vec![1; 300]);
+        assert_eq!(read_varints(&bytes), Some(consecutive));
+
+        let large = vec![0, 127, 128, 16_511, u64::MAX - 1, u64::MAX];
+        assert_eq!(read_varints(&write_varints(&large)), Some(large));
+        assert_eq!(write_varints(&[u64::MAX]).len(), 10);
+    }
+
+    /// Tests that truncated and oversized varints are rejected.
+    #[test]
+    fn test_read_varints_rejects_malformed_input() {
+        assert_eq!(read_varints(&[0x80]), None);
+        assert_eq!(read_varints(&[2, 0xFF]), None);
+        assert_eq!(
+            read_varints(&[0xFF; 9].iter().chain(&[0x02]).copied().collect::<Vec<_>>()),
+            None
+        );
+        assert_eq!(
+            read_varints(
+                &[0xFF; 10]
+                    .iter()
+                    .chain(&[0x01])
+                    .copied()
+                    .collect::<Vec<_>>()
+            ),
+            None
+        );
+    }
+
+    /// Tests round trips over many generated lists, ascending or not.
+    #[test]
+    fn test_varints_round_trip_property() {
+        for seed in 1..200u64 {
+            for max_gap in [1, 2, 100, 70_000, u64::MAX / 64] {
+                let values = ascending(seed, (seed % 50) as usize, max_gap);
+                assert_eq!(read_varints(&write_varints(&values)), Some(values.clone()));
+
+                let mut shuffled = values.clone();
+                shuffled.reverse();
+                assert_eq!(read_varints(&write_varints(&shuffled)), Some(shuffled));
+            }
+        }
+    }
+}
@@ -5,6 +5,7 @@ start the client via CLI.
 """
 
 import argparse
+import sys
 
 import primesocket_core
 
@@ -57,10 +58,10 @@ class PrimeClient:  # pylint: disable=R0903
                 self.ip,
                 self.port,
                 self.verbose,
-                self.timeout
+                timeout_seconds=self.timeout
             )
         except ValueError as e:
-            print(f"[Error] Failed to start client: {e}")
+            print(f"[Error] Failed to start client: {e}", file=sys.stderr)
 
 
 def main():
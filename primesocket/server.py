@@ -5,6 +5,7 @@ the server via CLI.
 """
 
 import argparse
+import sys
 
 import primesocket_core
 
@@ -43,19 +44,26 @@ class PrimeServer:  # pylint: disable=R0903
         client requests. The server handles computations for prime detection
         asynchronously.
 
+        Returns
+        -------
+        RunResult or None
+            Summary of the finished run (count, largest, duration, path),
+            or None if the server failed to start.
+
         Raises
         ------
         ValueError
             If the `end` parameter is not provided or is invalid.
         """
         try:
-            primesocket_core.start_server(
+            return primesocket_core.start_server(
                 self.port,
                 self.end,
                 self.verbose
             )
         except ValueError as e:
-            print(f"[Error] Failed to start server: {e}")
+            print(f"[Error] Failed to start server: {e}", file=sys.stderr)
+            return None
 
 
 def main():